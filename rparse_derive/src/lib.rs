@@ -0,0 +1,393 @@
+//! Implements `#[derive(Parse)]` for rparse. See the rparse derive module for the
+//! attributes that are supported.
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, GenericArgument, GenericParam, Ident, Lifetime, LitStr, PathArguments, Result, Token, Type};
+
+/// Derives rparse::Parse for a struct or enum.
+#[proc_macro_derive(Parse, attributes(rparse))]
+pub fn derive_parse(input: proc_macro::TokenStream) -> proc_macro::TokenStream
+{
+	let input = parse_macro_input!(input as DeriveInput);
+	match expand(&input)
+	{
+		Ok(tokens) => tokens.into(),
+		Err(err) => err.to_compile_error().into(),
+	}
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream>
+{
+	let lifetime = lifetime(input)?;
+	let options = Options::from_attrs(&input.attrs)?;
+	let name = &input.ident;
+	let label = options.label.clone().unwrap_or_else(|| to_words(&name.to_string()));
+
+	let body = match &input.data
+	{
+		Data::Struct(data) =>
+		{
+			fields_parser(&quote!(Self), &data.fields, options.grammar.as_ref(), name, &lifetime)?
+		}
+		Data::Enum(data) =>
+		{
+			if data.variants.is_empty()
+			{
+				return Err(Error::new_spanned(name, "Parse can't be derived for enums without variants"));
+			}
+
+			let mut variants = Vec::new();
+			for variant in data.variants.iter()
+			{
+				let options = Options::from_attrs(&variant.attrs)?;
+				if options.label.is_some()
+				{
+					return Err(Error::new_spanned(variant, "labels can only be used with the type"));
+				}
+				let ident = &variant.ident;
+				variants.push(fields_parser(&quote!(Self::#ident), &variant.fields, options.grammar.as_ref(), ident, &lifetime)?);
+			}
+			quote!(::rparse::or_v(vec![#(#variants),*]))
+		}
+		Data::Union(_) =>
+		{
+			return Err(Error::new_spanned(name, "Parse can't be derived for unions"));
+		}
+	};
+
+	let (impl_generics, type_generics, _) = input.generics.split_for_impl();
+	let impl_generics = if input.generics.params.is_empty() {quote!(<#lifetime>)} else {quote!(#impl_generics)};
+	Ok(quote!
+	{
+		impl #impl_generics ::rparse::Parse<#lifetime> for #name #type_generics
+		{
+			fn parser() -> ::rparse::Parser<#lifetime, Self>
+			{
+				use ::rparse::Combinators as _;
+				use ::rparse::StringParsers as _;
+				let parser: ::rparse::Parser<#lifetime, Self> = #body;
+				parser.err(#label)
+			}
+		}
+	})
+}
+
+// Types may have at most one lifetime (which is used as the lifetime of the text).
+fn lifetime(input: &DeriveInput) -> Result<Lifetime>
+{
+	let mut result = None;
+	for param in input.generics.params.iter()
+	{
+		match param
+		{
+			GenericParam::Lifetime(param) if result.is_none() => result = Some(param.lifetime.clone()),
+			_ => return Err(Error::new_spanned(param, "Parse can only be derived for types with at most one lifetime parameter")),
+		}
+	}
+	Ok(result.unwrap_or_else(|| Lifetime::new("'a", Span::call_site())))
+}
+
+// ---- attributes --------------------------------------------------------------------------------
+// #[rparse("grammar", label = "text")] on types and variants.
+#[derive(Default)]
+struct Options
+{
+	grammar: Option<LitStr>,
+	label: Option<String>,
+}
+
+// #[rparse(parser = "expr", sep = "token")] on fields.
+#[derive(Default)]
+struct FieldOptions
+{
+	parser: Option<Expr>,
+	sep: Option<String>,
+}
+
+enum Arg
+{
+	Str(LitStr),
+	Named(Ident, LitStr),
+}
+
+impl Parse for Arg
+{
+	fn parse(input: ParseStream) -> Result<Self>
+	{
+		if input.peek(LitStr)
+		{
+			Ok(Arg::Str(input.parse()?))
+		}
+		else
+		{
+			let name: Ident = input.parse()?;
+			input.parse::<Token![=]>()?;
+			Ok(Arg::Named(name, input.parse()?))
+		}
+	}
+}
+
+fn args(attrs: &[Attribute]) -> Result<Vec<Arg>>
+{
+	let mut result = Vec::new();
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("rparse"))
+	{
+		result.extend(attr.parse_args_with(Punctuated::<Arg, Token![,]>::parse_terminated)?);
+	}
+	Ok(result)
+}
+
+impl Options
+{
+	fn from_attrs(attrs: &[Attribute]) -> Result<Options>
+	{
+		let mut options = Options::default();
+		for arg in args(attrs)?
+		{
+			match arg
+			{
+				Arg::Str(grammar) => options.grammar = Some(grammar),
+				Arg::Named(name, value) if name == "label" => options.label = Some(value.value()),
+				Arg::Named(name, _) => return Err(Error::new_spanned(&name, format!("unknown rparse option {}", name))),
+			}
+		}
+		Ok(options)
+	}
+}
+
+impl FieldOptions
+{
+	fn from_attrs(attrs: &[Attribute]) -> Result<FieldOptions>
+	{
+		let mut options = FieldOptions::default();
+		for arg in args(attrs)?
+		{
+			match arg
+			{
+				Arg::Named(name, value) if name == "parser" => options.parser = Some(value.parse()?),
+				Arg::Named(name, value) if name == "sep" => options.sep = Some(value.value()),
+				Arg::Named(name, _) => return Err(Error::new_spanned(&name, format!("unknown rparse option {}", name))),
+				Arg::Str(grammar) => return Err(Error::new_spanned(&grammar, "grammars can only be used with types and variants")),
+			}
+		}
+		Ok(options)
+	}
+}
+
+// ---- code generation ---------------------------------------------------------------------------
+enum Item
+{
+	Token(String),
+	Field(usize),
+}
+
+// Returns an expression for a parser that matches the items in grammar (or each of the
+// fields in order if there is no grammar) and then uses ctor to build the value.
+fn fields_parser(ctor: &TokenStream, fields: &Fields, grammar: Option<&LitStr>, name: &Ident, lifetime: &Lifetime) -> Result<TokenStream>
+{
+	let names: Vec<String> = fields.iter().enumerate()
+		.map(|(i, field)| field.ident.as_ref().map_or_else(|| i.to_string(), |ident| ident.to_string()))
+		.collect();
+	let items = match grammar
+	{
+		Some(grammar) => parse_grammar(grammar, &names)?,
+		None => (0..names.len()).map(Item::Field).collect(),
+	};
+	if items.is_empty()
+	{
+		return Err(Error::new_spanned(name, "a grammar is required when there are no fields"));
+	}
+
+	let mut parsers = Vec::new();
+	let mut steps = Vec::new();
+	for (i, item) in items.iter().enumerate()
+	{
+		let parser = format_ident!("p{}", i);
+		match item
+		{
+			Item::Token(token) =>
+			{
+				parsers.push(quote!(let #parser = #token.s0();));
+				steps.push(quote!
+				{
+					let pass = #parser(state).map_err(|failure| ::rparse::Failed {old_state: input.clone(), ..failure})?;
+					let state = pass.new_state;
+				});
+			}
+			Item::Field(index) =>
+			{
+				let field = fields.iter().nth(*index).unwrap();
+				let value = format_ident!("f{}", index);
+				let expr = field_parser(field, lifetime)?;
+				parsers.push(quote!(let #parser = #expr;));
+				steps.push(quote!
+				{
+					let pass = #parser(state).map_err(|failure| ::rparse::Failed {old_state: input.clone(), ..failure})?;
+					let #value = pass.value;
+					let state = pass.new_state;
+				});
+			}
+		}
+	}
+
+	let values: Vec<Ident> = (0..names.len()).map(|i| format_ident!("f{}", i)).collect();
+	let value = match fields
+	{
+		Fields::Named(fields) =>
+		{
+			let idents = fields.named.iter().map(|field| field.ident.as_ref().unwrap());
+			quote!(#ctor {#(#idents: #values),*})
+		}
+		Fields::Unnamed(_) => quote!(#ctor(#(#values),*)),
+		Fields::Unit => quote!(#ctor),
+	};
+
+	Ok(quote!
+	{{
+		#(#parsers)*
+		::rparse::to_parser(move |input: ::rparse::State<#lifetime>|
+		{
+			let state = input.clone();
+			#(#steps)*
+			Ok(::rparse::Succeeded {new_state: state, value: #value})
+		})
+	}})
+}
+
+fn field_parser(field: &syn::Field, lifetime: &Lifetime) -> Result<TokenStream>
+{
+	let options = FieldOptions::from_attrs(&field.attrs)?;
+	let ty = &field.ty;
+	match (options.parser, options.sep)
+	{
+		(Some(parser), None) =>
+		{
+			Ok(quote!((#parser).s0()))
+		}
+		(None, None) =>
+		{
+			Ok(quote!(::rparse::lazy(<#ty as ::rparse::Parse<#lifetime>>::parser)))
+		}
+		(parser, Some(sep)) =>
+		{
+			let element = match vec_element(ty)
+			{
+				Some(element) => element,
+				None => return Err(Error::new_spanned(ty, "sep can only be used with Vec fields")),
+			};
+			let parser = match parser
+			{
+				Some(parser) => quote!((#parser).s0()),
+				None => quote!(::rparse::lazy(<#element as ::rparse::Parse<#lifetime>>::parser)),
+			};
+			Ok(quote!(#parser.sep_by0(#sep.s0())))
+		}
+	}
+}
+
+// Returns T for Vec<T>.
+fn vec_element(ty: &Type) -> Option<&Type>
+{
+	if let Type::Path(path) = ty
+	{
+		let segment = path.path.segments.last()?;
+		if segment.ident == "Vec"
+		{
+			if let PathArguments::AngleBracketed(args) = &segment.arguments
+			{
+				if let Some(GenericArgument::Type(element)) = args.args.first()
+				{
+					return Some(element);
+				}
+			}
+		}
+	}
+	None
+}
+
+// grammar := (token | field)*
+// token := "'" [^']* "'" | '"' [^"]* '"'
+// field := name | index
+fn parse_grammar(grammar: &LitStr, names: &[String]) -> Result<Vec<Item>>
+{
+	let text = grammar.value();
+	let mut items = Vec::new();
+	let mut chars = text.chars().peekable();
+	while let Some(&ch) = chars.peek()
+	{
+		if ch.is_whitespace()
+		{
+			chars.next();
+		}
+		else if ch == '\'' || ch == '"'
+		{
+			chars.next();
+			let mut token = String::new();
+			let mut closed = false;
+			for c in chars.by_ref()
+			{
+				if c == ch
+				{
+					closed = true;
+					break;
+				}
+				token.push(c);
+			}
+			if token.is_empty() || !closed
+			{
+				return Err(Error::new_spanned(grammar, "tokens can't be empty or unterminated"));
+			}
+			items.push(Item::Token(token));
+		}
+		else
+		{
+			let mut name = String::new();
+			while let Some(&c) = chars.peek()
+			{
+				if c.is_whitespace() || c == '\'' || c == '"'
+				{
+					break;
+				}
+				name.push(c);
+				chars.next();
+			}
+			match names.iter().position(|n| *n == name)
+			{
+				Some(_) if items.iter().any(|item| matches!(item, Item::Field(i) if names[*i] == name)) =>
+				{
+					return Err(Error::new_spanned(grammar, format!("field {} appears more than once", name)));
+				}
+				Some(index) => items.push(Item::Field(index)),
+				None => return Err(Error::new_spanned(grammar, format!("{} is not a field", name))),
+			}
+		}
+	}
+
+	for (i, name) in names.iter().enumerate()
+	{
+		if !items.iter().any(|item| matches!(item, Item::Field(j) if *j == i))
+		{
+			return Err(Error::new_spanned(grammar, format!("field {} is missing from the grammar", name)));
+		}
+	}
+	Ok(items)
+}
+
+// "ComplexElement" => "complex element"
+fn to_words(name: &str) -> String
+{
+	let mut result = String::new();
+	for ch in name.chars()
+	{
+		if ch.is_uppercase() && !result.is_empty()
+		{
+			result.push(' ');
+		}
+		result.extend(ch.to_lowercase());
+	}
+	result
+}
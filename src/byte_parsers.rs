@@ -0,0 +1,273 @@
+//! Functions that can be used to parse binary data.
+//!
+//! These are normally used with the parse_bytes method. Note that they never
+//! advance the line number.
+use std::rc::Rc;
+
+use crate::parsers::*;
+use crate::types::*;
+
+// ---- byte parsers ------------------------------------------------------------------------------
+/// Consumes a byte which must satisfy the predicate.
+/// Returns the matched byte.
+pub fn anyb<'a>(predicate: impl Fn(u8) -> bool + 'a) -> Parser<'a, u8>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		match input.rest_bytes().first()
+		{
+			Some(&b) if predicate(b) =>
+			{
+				Ok(Succeeded {new_state: State {index: input.index + 1, ..input}, value: b})
+			}
+			_ =>
+			{
+				Err(Failed::new(input.clone(), input, String::new()))
+			}
+		}
+	})
+}
+
+/// Consumes exactly count bytes.
+/// Returns the matched bytes.
+pub fn take<'a>(count: usize) -> Parser<'a, &'a [u8]>
+{
+	let mesg = format!("{} bytes", count);
+	take_mesg(count, mesg)
+}
+
+/// Consumes bytes which must be identical to expected (e.g. b"\x89PNG\r\n\x1a\n").
+/// Returns the matched bytes.
+pub fn magic<'a>(expected: &'a [u8]) -> Parser<'a, &'a [u8]>
+{
+	let hex: Vec<String> = expected.iter().map(|b| format!("{:02X}", b)).collect();
+	let mesg = format!("magic number {}", hex.join(" "));
+
+	Rc::new(move |input: State<'a>|
+	{
+		let rest = input.rest_bytes();
+		if rest.starts_with(expected)
+		{
+			Ok(Succeeded {new_state: State {index: input.index + expected.len(), ..input}, value: &rest[..expected.len()]})
+		}
+		else
+		{
+			// Like lit the error is reported at the first byte that didn't match.
+			let i = rest.iter().zip(expected.iter()).take_while(|(a, b)| a == b).count();
+			Err(Failed::new(input.clone(), State {index: input.index + i, ..input}, mesg.clone()))
+		}
+	})
+}
+
+// ---- integer parsers ---------------------------------------------------------------------------
+/// Consumes one byte and returns it as an unsigned integer.
+pub fn u8<'a>() -> Parser<'a, u8>
+{
+	fixed(1, "u8", |b| b[0])
+}
+
+/// Consumes one byte and returns it as a signed integer.
+pub fn i8<'a>() -> Parser<'a, i8>
+{
+	fixed(1, "i8", |b| b[0] as i8)
+}
+
+/// Consumes two bytes and returns them as a big-endian unsigned integer.
+pub fn be_u16<'a>() -> Parser<'a, u16>
+{
+	fixed(2, "big-endian u16", |b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+/// Consumes two bytes and returns them as a little-endian unsigned integer.
+pub fn le_u16<'a>() -> Parser<'a, u16>
+{
+	fixed(2, "little-endian u16", |b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+/// Consumes two bytes and returns them as a big-endian signed integer.
+pub fn be_i16<'a>() -> Parser<'a, i16>
+{
+	fixed(2, "big-endian i16", |b| i16::from_be_bytes([b[0], b[1]]))
+}
+
+/// Consumes two bytes and returns them as a little-endian signed integer.
+pub fn le_i16<'a>() -> Parser<'a, i16>
+{
+	fixed(2, "little-endian i16", |b| i16::from_le_bytes([b[0], b[1]]))
+}
+
+/// Consumes four bytes and returns them as a big-endian unsigned integer.
+pub fn be_u32<'a>() -> Parser<'a, u32>
+{
+	fixed(4, "big-endian u32", |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Consumes four bytes and returns them as a little-endian unsigned integer.
+pub fn le_u32<'a>() -> Parser<'a, u32>
+{
+	fixed(4, "little-endian u32", |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Consumes four bytes and returns them as a big-endian signed integer.
+pub fn be_i32<'a>() -> Parser<'a, i32>
+{
+	fixed(4, "big-endian i32", |b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Consumes four bytes and returns them as a little-endian signed integer.
+pub fn le_i32<'a>() -> Parser<'a, i32>
+{
+	fixed(4, "little-endian i32", |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Consumes eight bytes and returns them as a big-endian unsigned integer.
+pub fn be_u64<'a>() -> Parser<'a, u64>
+{
+	fixed(8, "big-endian u64", |b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
+
+/// Consumes eight bytes and returns them as a little-endian unsigned integer.
+pub fn le_u64<'a>() -> Parser<'a, u64>
+{
+	fixed(8, "little-endian u64", |b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
+
+/// Consumes eight bytes and returns them as a big-endian signed integer.
+pub fn be_i64<'a>() -> Parser<'a, i64>
+{
+	fixed(8, "big-endian i64", |b| i64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
+
+/// Consumes eight bytes and returns them as a little-endian signed integer.
+pub fn le_i64<'a>() -> Parser<'a, i64>
+{
+	fixed(8, "little-endian i64", |b| i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
+
+// ---- length prefixed parsers -------------------------------------------------------------------
+/// length_data := length byte{length}
+///
+/// Returns the bytes following the length.
+pub fn length_data<'a, N: Into<u64> + 'a>(length: Parser<'a, N>) -> Parser<'a, &'a [u8]>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		match length(input.clone())
+		{
+			Ok(pass) =>
+			{
+				let count = pass.value.into() as usize;
+				take(count)(pass.new_state).map_err(|failure| Failed {old_state: input.clone(), ..failure})
+			}
+			Err(failure) =>
+			{
+				Err(failure)
+			}
+		}
+	})
+}
+
+/// length_value := length byte{length}
+///
+/// Parser is used to parse the bytes following the length: it cannot read past
+/// them and any bytes it does not consume are skipped.
+pub fn length_value<'a, N: Into<u64> + 'a, T: 'a>(length: Parser<'a, N>, parser: Parser<'a, T>) -> Parser<'a, T>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = length(input.clone())?;
+		let start = pass.new_state;
+		let count = pass.value.into() as usize;
+		if count > start.rest_bytes().len() && start.partial
+		{
+			return incomplete(start).map_err(|failure| Failed {old_state: input.clone(), ..failure});
+		}
+		else if count > start.rest_bytes().len()
+		{
+			return Err(Failed::new(input, start, format!("{} bytes", count)));
+		}
+
+		let end = start.index + count;
+		// The field is complete even if the input isn't. And because the field has
+		// different bytes memo results from outside the field aren't valid within it.
+		let field = State {bytes: &input.bytes[..end], partial: false, parse_id: next_parse_id(), ..start};
+		match parser(field)
+		{
+			Ok(pass) =>
+			{
+				Ok(Succeeded {new_state: State {bytes: input.bytes, index: end, partial: input.partial, parse_id: input.parse_id, ..pass.new_state}, value: pass.value})
+			}
+			Err(failure) =>
+			{
+				// Note that this is never an incomplete failure: more input won't change the field.
+				Err(Failed {old_state: input.clone(), err_state: State {bytes: input.bytes, parse_id: input.parse_id, ..failure.err_state}, ..failure})
+			}
+		}
+	})
+}
+
+// ---- bit parsers -------------------------------------------------------------------------------
+/// Consumes enough bytes to satisfy widths and returns a field for each width.
+///
+/// Fields are read from the most significant bit of the first byte. Widths must
+/// be at most 64 and their sum must be a multiple of eight, e.g. bits(&[4, 4]) for
+/// an IPv4 version and header length.
+pub fn bits<'a>(widths: &[u32]) -> Parser<'a, Vec<u64>>
+{
+	assert!(widths.iter().all(|&w| w <= 64), "bit widths must be <= 64");
+	let total: u32 = widths.iter().sum();
+	assert!(total.is_multiple_of(8), "bit widths must add up to whole bytes");
+
+	let widths = widths.to_vec();
+	let count = (total / 8) as usize;
+	let mesg = format!("{} bit fields", widths.len());
+	let bytes = take_mesg(count, mesg);
+
+	Rc::new(move |input: State<'a>|
+	{
+		bytes(input).map(|pass|
+		{
+			let mut values = Vec::with_capacity(widths.len());
+			let mut bit = 0;
+			for &width in widths.iter()
+			{
+				let mut value = 0u64;
+				for _ in 0..width
+				{
+					let b = pass.value[bit / 8];
+					let set = (b >> (7 - bit % 8)) & 1;
+					value = (value << 1) | set as u64;
+					bit += 1;
+				}
+				values.push(value);
+			}
+			Succeeded {new_state: pass.new_state, value: values}
+		})
+	})
+}
+
+// ---- helpers -----------------------------------------------------------------------------------
+fn take_mesg<'a>(count: usize, mesg: String) -> Parser<'a, &'a [u8]>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let rest = input.rest_bytes();
+		if count <= rest.len()
+		{
+			Ok(Succeeded {new_state: State {index: input.index + count, ..input}, value: &rest[..count]})
+		}
+		else if input.partial
+		{
+			incomplete(input)
+		}
+		else
+		{
+			Err(Failed::new(input.clone(), input, mesg.clone()))
+		}
+	})
+}
+
+fn fixed<'a, T: Clone + 'a>(size: usize, mesg: &str, convert: fn(&[u8]) -> T) -> Parser<'a, T>
+{
+	take_mesg(size, mesg.to_string()).thene(move |bytes| ret(convert(bytes)))
+}
@@ -3,154 +3,440 @@
 
 // See http://www.open-std.org/jtc1/sc22/wg14/www/docs/n1539.pdf
 
-use misc::*;
-use parsers::*;
-use types::*;
+use std::rc::Rc;
+
+use crate::misc::*;
+use crate::number_parsers::*;
+use crate::parsers::*;
+use crate::types::*;
+use crate::{alt, seq, seq_str};
 
 /// identifier := [a-zA-Z_] [a-zA-Z0-9_]*
-/// 
+///
 /// Note that match1_0 can be used to easily implement custom identifier parsers.
-pub fn identifier() -> Parser<@~str>
+pub fn identifier<'a>() -> Parser<'a, &'a str>
 {
-	// Supposed to support universal character names too, e.g.  
+	// Supposed to support universal character names too, e.g.
 	// fo\u006F is a valid C99 identifier.
 	match1_0(is_identifier_prefix, is_identifier_suffix)
 }
 
 /// decimal_number := [0-9]+
-/// 
+///
 /// Technically this is not supposed to match numbers with leading zeros,
 /// but we do so to make this parser more reusable.
-pub fn decimal_number() -> Parser<int>
+pub fn decimal_number<'a>() -> Parser<'a, i64>
 {
-	do match1(is_digit).thene
-		|text|
-		{
-			match int::from_str(*text)
-			{
-				option::Some(value) =>
-				{
-					ret(value)
-				}
-				_ =>
-				{
-					fails(fmt!("'%s' is out of range", *text))
-				}
-			}
-		}
+	integer(NumberFormat::new(10))
 }
 
 /// octal_number := 0 [0-7]*
-pub fn octal_number() -> Parser<int>
+pub fn octal_number<'a>() -> Parser<'a, i64>
 {
-	do match1_0(|c| c == '0', is_octal).thene
-		|text|
+	let digits = integer(NumberFormat::new(8));
+	Rc::new(move |input: State<'a>|
+	{
+		if input.rest().starts_with('0')
 		{
-			match from_base_8(*text)
-			{
-				result::Ok(value) =>
-				{
-					ret(value)
-				}
-				result::Err(copy mesg) =>
-				{
-					fails(mesg)
-				}
-			}
+			digits(input)
+		}
+		else
+		{
+			Err(Failed::new(input.clone(), input, String::new()))
 		}
+	})
 }
 
-/// hex_number := 0[xX] [0-9a-fA-F]+
-pub fn hex_number() -> Parser<int>
+/// hex_number := 0\[xX\] [0-9a-fA-F]+
+pub fn hex_number<'a>() -> Parser<'a, i64>
 {
 	let prefix = "0".lit().then("x".lit().or("X".lit()));
-	let digits = do match1(is_hex).thene()
-			|text| {
-			match from_base_16(*text)
-			{
-				result::Ok(value) =>
-				{
-					ret(value)
-				}
-				result::Err(copy mesg) =>
-				{
-					fails(mesg)
-				}
-			}
-		};
-	
-	seq2_ret1(prefix, digits)
+	seq2_ret1(prefix, integer(NumberFormat::new(16)))
 }
 
 /// float_number := float1 | float2 | float3
-/// 
+///
 /// float1 := [0-9]* '.' [0-9]+ exponent?
 /// float2 := [0-9]+ '.' exponent?
 /// float3 := [0-9]+ exponent
-/// exponent := [eE] [+-]? [0-9]+
-pub fn float_number() -> Parser<f64>
+/// exponent := \[eE\] [+-]? [0-9]+
+pub fn float_number<'a>() -> Parser<'a, f64>
 {
 	let exponent = seq3_ret_str("eE".anyc(), "+-".anyc().optional(), match1(is_digit));
-	
+
 	let float1 = seq4_ret_str(match0(is_digit), ".".lit(), match1(is_digit), exponent.optional()).err("");
 	let float2 = seq3_ret_str(match1(is_digit), ".".lit(), exponent.optional()).err("");
 	let float3 = seq2_ret_str(match1(is_digit), exponent).err("");
-	
-	let number = or_v(@~[float1, float2, float3]);
-	
-	do number.thene()
-		|text| {
-			do str::as_c_str(*text) |ptr|
+
+	let number = or_v(vec![float1, float2, float3]);
+
+	number.thene(
+		|text|
+		{
+			match text.parse::<f64>()
 			{
-				unsafe { ret(libc::strtod(ptr, ptr::null()) as f64) }
+				Ok(value) =>
+				{
+					ret(value)
+				}
+				Err(_) =>
+				{
+					fails(&format!("'{}' is not a valid float", text))
+				}
 			}
+		})
+}
+
+/// The types of integer constants. Widths are those of LP64 platforms: int is 32 bits
+/// and long and long long are 64 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntegerType
+{
+	Int,
+	UnsignedInt,
+	Long,
+	UnsignedLong,
+	LongLong,
+	UnsignedLongLong,
+}
+
+impl IntegerType
+{
+	/// Returns true for the unsigned types.
+	pub fn is_unsigned(self) -> bool
+	{
+		matches!(self, IntegerType::UnsignedInt | IntegerType::UnsignedLong | IntegerType::UnsignedLongLong)
+	}
+
+	/// Returns the width of the type in bits.
+	pub fn bits(self) -> u32
+	{
+		match self
+		{
+			IntegerType::Int | IntegerType::UnsignedInt => 32,
+			_ => 64,
+		}
+	}
+
+	/// Returns the largest value the type can represent.
+	pub fn max_value(self) -> u64
+	{
+		if self.is_unsigned()
+		{
+			u64::MAX >> (64 - self.bits())
+		}
+		else
+		{
+			u64::MAX >> (65 - self.bits())
 		}
+	}
 }
 
-/// char_literal := '\\'' c_char_sequence '\\''
-/// 
-/// c_char_sequence := [^'\n\r\\]
-/// c_char_sequence := escape_sequence
-pub fn char_literal() -> Parser<char>
+/// The types of floating constants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatType
 {
-	// We don't support the [LuU] prefix (so the parser is reusable in other contexts).
-	let case1 = "'\n\r\\".noc().err("");
-	let case2 = escape_sequence().err("escape character");
-	let char_sequence = case1.or(case2);
-	
-	seq3_ret1("'".lit(), char_sequence, "'".lit())
+	Float,
+	Double,
+
+	/// Long double constants are parsed with double precision.
+	LongDouble,
+}
+
+/// The value of an integer or floating constant. Note that constants are never negative:
+/// in C `-1` is the unary minus operator applied to `1`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constant
+{
+	Integer {value: u64, ty: IntegerType},
+
+	/// Float values are rounded to f32 precision (so they convert to f32 exactly).
+	Floating {value: f64, ty: FloatType},
+}
+
+/// integer_constant := (decimal | octal | hex) integer_suffix?
+///
+/// decimal := [1-9] [0-9]*
+/// octal := '0' [0-7]*
+/// hex := 0\[xX\] [0-9a-fA-F]+
+/// integer_suffix := \[uU\] (l | L | ll | LL)? | (l | L | ll | LL) \[uU\]?
+///
+/// The type is the first type in C99's list for the suffix and radix that can represent
+/// the value, e.g. `0xFFFFFFFF` is an unsigned int but `4294967295` is a long.
+pub fn integer_constant<'a>() -> Parser<'a, Constant>
+{
+	let hex = seq_str!("0".lit(), "xX".anyc(), match1(is_hex));
+	let parser = seq!(hex.or(match1(is_digit)), match0(is_identifier_suffix));
+
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+		let (digits, suffix) = pass.value;
+		match integer_value(digits, suffix)
+		{
+			Ok(value) => Ok(Succeeded {new_state: pass.new_state, value}),
+			Err(mesg) if mesg.starts_with("invalid suffix") => Err(Failed::message(input.clone(), input.advance(digits.len()), mesg)),
+			Err(mesg) => Err(Failed::message(input, pass.new_state, mesg)),
+		}
+	})
+}
+
+/// floating_constant := (float_number | hex_float) \[fFlL\]?
+///
+/// hex_float := 0\[xX\] hex_mantissa \[pP\] [+-]? [0-9]+
+/// hex_mantissa := [0-9a-fA-F]* '.' [0-9a-fA-F]+ | [0-9a-fA-F]+ '.'?
+///
+/// Values are correctly rounded, except that subnormal floats may be rounded twice.
+pub fn floating_constant<'a>() -> Parser<'a, Constant>
+{
+	let hex_mantissa = seq_str!(match0(is_hex), ".".lit(), match1(is_hex)).or(seq_str!(match1(is_hex), ".".lit().optional()));
+	let hex_float = seq_str!("0".lit(), "xX".anyc(), hex_mantissa, "pP".anyc(), "+-".anyc().optional(), match1(is_digit));
+	let parser = seq!(ret_str(float_number()).or(hex_float), match0(is_identifier_suffix));
+
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+		let (digits, suffix) = pass.value;
+		let ty = match suffix
+		{
+			"" => FloatType::Double,
+			"f" | "F" => FloatType::Float,
+			"l" | "L" => FloatType::LongDouble,
+			_ => return Err(Failed::message(input.clone(), input.advance(digits.len()), format!("invalid suffix '{}' on floating constant", suffix))),
+		};
+
+		let value = float_value(digits, ty);
+		if value.is_finite()
+		{
+			Ok(Succeeded {new_state: pass.new_state, value: Constant::Floating {value, ty}})
+		}
+		else
+		{
+			Err(Failed::message(input, pass.new_state, format!("'{}' is out of range", digits)))
+		}
+	})
+}
+
+/// char_literal := '\\'' c_char '\\''
+///
+/// c_char := [^'\n\r\\]
+/// c_char := escape_sequence
+///
+/// Numeric escapes are code points, e.g. `'\xE9'` is 'é'.
+pub fn char_literal<'a>() -> Parser<'a, char>
+{
+	// We don't support the encoding prefix (so the parser is reusable in other contexts).
+	// See c_char_constant for that.
+	let c_char = literal_char('\'', None).thene(|units| ret(char::from_u32(units[0]).unwrap()));
+	seq!(skip "'".lit(), c_char, skip "'".lit())
 }
 
 /// string_literal := '\"' s_char* '\"'
-/// 
+///
 /// s_char := [^\"\n\r\\]
 /// s_char := escape_sequence
-pub fn string_literal() -> Parser<@~str>
+///
+/// Numeric escapes are code points, e.g. `"\xE9"` is "é".
+pub fn string_literal<'a>() -> Parser<'a, String>
 {
-	// We don't support the encoding prefix (so the parser is reusable in other contexts).
-	let case1 = "\"\n\r\\".noc().err("");
-	let case2 = escape_sequence().err("escape character");
-	let s_char = case1.or(case2);
-	let body = do s_char.r0().thene() |chars| { ret(@str::from_chars(*chars))};
-	
-	seq3_ret1("\"".lit(), body, "\"".lit())
+	// We don't support the encoding prefix or concatenation (so the parser is reusable in
+	// other contexts). See c_string_literal for those.
+	let s_char = literal_char('"', None);
+	let body = s_char.many_till("\"".lit()).thene(|chars| ret(chars.iter().flatten().map(|&unit| char::from_u32(unit).unwrap()).collect::<String>()));
+	seq!(skip "\"".lit(), body)
+}
+
+/// The encodings of character constants and string literals. The plain encoding is
+/// assumed to be UTF-8 and wchar_t is assumed to be 32 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding
+{
+	/// No prefix.
+	Plain,
+
+	/// The u8 prefix.
+	Utf8,
+
+	/// The u prefix.
+	Utf16,
+
+	/// The U prefix.
+	Utf32,
+
+	/// The L prefix.
+	Wide,
+}
+
+impl Encoding
+{
+	/// Returns the prefix for the encoding, e.g. "u8".
+	pub fn prefix(self) -> &'static str
+	{
+		match self
+		{
+			Encoding::Plain => "",
+			Encoding::Utf8 => "u8",
+			Encoding::Utf16 => "u",
+			Encoding::Utf32 => "U",
+			Encoding::Wide => "L",
+		}
+	}
+
+	/// Returns the largest code unit (which is also the largest numeric escape).
+	pub fn max_unit(self) -> u32
+	{
+		match self
+		{
+			Encoding::Plain | Encoding::Utf8 => 0xFF,
+			Encoding::Utf16 => 0xFFFF,
+			Encoding::Utf32 | Encoding::Wide => 0xFFFF_FFFF,
+		}
+	}
+
+	/// Appends the code units for ch.
+	pub fn encode(self, ch: char, units: &mut Vec<u32>)
+	{
+		match self
+		{
+			Encoding::Plain | Encoding::Utf8 => units.extend(ch.encode_utf8(&mut [0; 4]).bytes().map(u32::from)),
+			Encoding::Utf16 => units.extend(ch.encode_utf16(&mut [0; 2]).iter().map(|&unit| u32::from(unit))),
+			Encoding::Utf32 | Encoding::Wide => units.push(ch as u32),
+		}
+	}
+}
+
+/// The value of a character constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CharConstant {pub encoding: Encoding, pub value: i64}
+
+/// The value of a string literal (without the terminating null).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StringLiteral {pub encoding: Encoding, pub units: Vec<u32>}
+
+impl StringLiteral
+{
+	/// Decodes the units. Invalid sequences are replaced with U+FFFD.
+	pub fn to_string_lossy(&self) -> String
+	{
+		match self.encoding
+		{
+			Encoding::Plain | Encoding::Utf8 =>
+			{
+				let bytes: Vec<u8> = self.units.iter().map(|&unit| unit as u8).collect();
+				String::from_utf8_lossy(&bytes).into_owned()
+			}
+			Encoding::Utf16 =>
+			{
+				let units: Vec<u16> = self.units.iter().map(|&unit| unit as u16).collect();
+				String::from_utf16_lossy(&units)
+			}
+			Encoding::Utf32 | Encoding::Wide =>
+			{
+				self.units.iter().map(|&unit| char::from_u32(unit).unwrap_or('\u{FFFD}')).collect()
+			}
+		}
+	}
+}
+
+/// c_char_constant := ('L' | 'u' | 'U' | 'u8')? '\\'' c_char+ '\\''
+///
+/// Plain constants follow GCC: char is signed and multi-character constants, e.g. `'ab'`,
+/// combine up to four chars into an int. Prefixed constants must be a single code unit.
+pub fn c_char_constant<'a>() -> Parser<'a, CharConstant>
+{
+	let parser = prefixed('\'', char_body);
+
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+		let (encoding, units) = pass.value;
+		let value = match (encoding, units.as_slice())
+		{
+			(_, []) => Err("empty character constant"),
+			(Encoding::Plain, [unit]) => Ok(*unit as u8 as i8 as i64),
+			(Encoding::Plain, units) if units.len() <= 4 => Ok(units.iter().fold(0, |value, unit| value << 8 | unit) as i32 as i64),
+			(Encoding::Plain, _) => Err("character constant is too long"),
+			(Encoding::Wide, [unit]) => Ok(*unit as i32 as i64),
+			(_, [unit]) => Ok(*unit as i64),
+			(_, _) => Err("character constant must be a single code unit"),
+		};
+
+		match value
+		{
+			Ok(value) => Ok(Succeeded {new_state: pass.new_state, value: CharConstant {encoding, value}}),
+			Err(mesg) => Err(Failed::message(input.clone(), input.advance(encoding.prefix().len() + 1), mesg.to_string())),
+		}
+	})
+}
+
+/// c_string_literal := (('L' | 'u' | 'U' | 'u8')? '\"' s_char* '\"')+
+///
+/// Adjacent literals (separated by white space) are concatenated. If one of them has a
+/// prefix the result uses that encoding (including the escapes in the other literals).
+/// Literals with different prefixes cannot be concatenated.
+pub fn c_string_literal<'a>() -> Parser<'a, StringLiteral>
+{
+	let scan_piece = prefixed('"', |_| string_body(Encoding::Utf32));
+	let bodies: Vec<_> = ENCODINGS.iter().map(|&encoding| string_body(encoding)).collect();
+	let space = match0(is_whitespace);
+
+	Rc::new(move |input: State<'a>|
+	{
+		// Find the pieces and the encoding of the result.
+		let mut pieces = Vec::new();
+		let mut encoding = Encoding::Plain;
+		let mut next = input.clone();
+		loop
+		{
+			let pass = match scan_piece(next.clone())
+			{
+				Ok(pass) => pass,
+				Err(failure) if pieces.is_empty() || failure.is_incomplete() || failure.err_state.index > next.index => return Err(Failed {old_state: input, ..failure}),
+				Err(_) => break,
+			};
+
+			let piece = pass.value.0;
+			if piece != Encoding::Plain && encoding != Encoding::Plain && piece != encoding
+			{
+				let mesg = format!("can't concatenate {} and {} string literals", encoding.prefix(), piece.prefix());
+				return Err(Failed::message(input, next, mesg));
+			}
+			if piece != Encoding::Plain
+			{
+				encoding = piece;
+			}
+			pieces.push((next.advance(piece.prefix().len()), pass.new_state.clone()));
+			next = space(pass.new_state)?.new_state;
+		}
+
+		// Decode the pieces using that encoding.
+		let body = &bodies[ENCODINGS.iter().position(|&e| e == encoding).unwrap()];
+		let mut units = Vec::new();
+		for (start, _) in pieces.iter()
+		{
+			let pass = body(start.clone()).map_err(|failure| Failed {old_state: input.clone(), ..failure})?;
+			units.extend(pass.value);
+		}
+		Ok(Succeeded {new_state: pieces.last().unwrap().1.clone(), value: StringLiteral {encoding, units}})
+	})
 }
 
 /// comment := '/*' ([^*] | '*' [^/])* '*/'
-/// 
+///
 /// Note that these do not nest.
-pub fn comment() -> Parser<@~str>
+pub fn comment<'a>() -> Parser<'a, &'a str>
 {
-	fn comment_body(chars: @[char], index: uint) -> uint
+	fn comment_body(text: &str, index: usize) -> usize
 	{
+		let bytes = text.as_bytes();
 		let mut i = index;
 		loop
 		{
-			if chars[i] == EOT
+			if i == bytes.len()
 			{
 				return 0;
 			}
-			else if chars[i] == '*' && chars[i+1] == '/'
+			else if bytes[i] == b'*' && bytes.get(i+1) == Some(&b'/')
 			{
 				return i - index;
 			}
@@ -160,20 +446,21 @@ pub fn comment() -> Parser<@~str>
 			}
 		}
 	}
-	
+
 	let body = scan(comment_body);
 	seq3_ret1("/*".lit(), body, "*/".lit())
 }
 
 /// line_comment := '//' [^\r\n]*
-pub fn line_comment() -> Parser<@~str>
+pub fn line_comment<'a>() -> Parser<'a, &'a str>
 {
-	fn comment_body(chars: @[char], index: uint) -> uint
+	fn comment_body(text: &str, index: usize) -> usize
 	{
+		let bytes = text.as_bytes();
 		let mut i = index;
 		loop
 		{
-			if chars[i] == '\r' || chars[i] == '\n' || chars[i] == EOT
+			if i == bytes.len() || bytes[i] == b'\r' || bytes[i] == b'\n'
 			{
 				return i - index;
 			}
@@ -183,93 +470,487 @@ pub fn line_comment() -> Parser<@~str>
 			}
 		}
 	}
-	
+
 	let body = scan(comment_body);
 	seq2_ret1("//".lit(), body)
 }
 
+/// The kinds of tokens returned by c99_token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind
+{
+	Keyword,
+	Identifier,
+
+	/// Integer, floating, and character constants.
+	Constant,
+	StringLiteral,
+	Punctuator,
+
+	/// Block and line comments. These are trivia: they're kept so that tools can work
+	/// with the original text but compilers would normally ignore them.
+	Comment,
+}
+
+/// A C99 token. Text is the original text of the token (including quotes, digraphs, etc).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {pub kind: TokenKind, pub text: &'a str, pub span: Span}
+
+impl Token<'_>
+{
+	/// Returns true for tokens that don't affect the meaning of the program.
+	pub fn is_trivia(&self) -> bool
+	{
+		self.kind == TokenKind::Comment
+	}
+}
+
+/// keyword := identifier
+///
+/// Succeeds if the identifier is one of the C99 keywords.
+pub fn keyword<'a>() -> Parser<'a, &'a str>
+{
+	let parser = identifier();
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+		if KEYWORDS.contains(&pass.value)
+		{
+			Ok(pass)
+		}
+		else
+		{
+			Err(Failed::new(input.clone(), input, "keyword".to_string()))
+		}
+	})
+}
+
+/// punctuator := '[' | ']' | '(' | ')' | '{' | '}' | '.' | '->' | …
+///
+/// The longest matching punctuator is used, e.g. `>>=` instead of `>>` and `=`. Digraphs,
+/// e.g. `<:` and `%:%:`, are returned as is.
+pub fn punctuator<'a>() -> Parser<'a, &'a str>
+{
+	fn longest(text: &str, index: usize) -> usize
+	{
+		let rest = &text[index..];
+		PUNCTUATORS.iter().filter(|p| rest.starts_with(**p)).map(|p| p.len()).max().unwrap_or(0)
+	}
+
+	let parser = scan(longest);
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+		if pass.value.is_empty()
+		{
+			Err(Failed::new(input.clone(), input, "punctuator".to_string()))
+		}
+		else
+		{
+			Ok(pass)
+		}
+	})
+}
+
+/// constant := floating_constant | integer_constant | c_char_constant
+///
+/// Returns the text of the constant. Note that constants cannot be directly followed
+/// by letters or digits, e.g. `12ab` is an error instead of `12` followed by `ab`.
+pub fn constant<'a>() -> Parser<'a, &'a str>
+{
+	let number = ret_str(floating_constant()).or(ret_str(integer_constant())).err("constant");
+	let character = ret_str(c_char_constant());
+	number.or(character)
+}
+
+/// string_token := ('L' | 'u' | 'U' | 'u8')? '\"' s_char* '\"'
+///
+/// Returns the text of a single string literal. Use c_string_literal to get the value.
+pub fn string_token<'a>() -> Parser<'a, &'a str>
+{
+	ret_str(prefixed('"', string_body))
+}
+
+/// c99_token := comment | line_comment | constant | string_token | keyword | identifier | punctuator
+///
+/// Adjacent string literals are separate tokens (use c_string_literal to concatenate them).
+pub fn c99_token<'a>() -> Parser<'a, Token<'a>>
+{
+	let comment = make_token(ret_str(comment()).or(ret_str(line_comment())), TokenKind::Comment);
+	let parser = alt!(
+		make_token(constant(), TokenKind::Constant),
+		make_token(string_token(), TokenKind::StringLiteral),
+		make_token(keyword(), TokenKind::Keyword),
+		make_token(identifier(), TokenKind::Identifier),
+		make_token(punctuator(), TokenKind::Punctuator)
+	).err("token");
+
+	Rc::new(move |input: State<'a>|
+	{
+		// In C these always start a comment, e.g. an unterminated comment isn't a '/' punctuator.
+		let rest = input.rest();
+		if rest.starts_with("/*") || rest.starts_with("//")
+		{
+			comment(input)
+		}
+		else
+		{
+			parser(input)
+		}
+	})
+}
+
+/// c99_tokens := space (c99_token space)* EOT
+///
+/// space := ([ \t\r\n\v\f] | '\\' newline)*
+pub fn c99_tokens<'a>() -> Parser<'a, Vec<Token<'a>>>
+{
+	fn space(text: &str, index: usize) -> usize
+	{
+		let bytes = text.as_bytes();
+		let mut i = index;
+		loop
+		{
+			match bytes.get(i..).unwrap_or(&[])
+			{
+				[b' ' | b'\t' | b'\r' | b'\n' | b'\x0B' | b'\x0C', ..] => i += 1,
+				[b'\\', b'\r', b'\n', ..] => i += 3,
+				[b'\\', b'\n', ..] => i += 2,
+				_ => return i - index,
+			}
+		}
+	}
+
+	// many_till instead of r0 so that errors within tokens are reported.
+	seq!(skip scan(space), seq!(c99_token(), skip scan(space)).many_till(eot()))
+}
+
 // ---- Helpers ---------------------------------------------------------------
-pure fn is_identifier_prefix(ch: char) -> bool
+const KEYWORDS: [&str; 37] = [
+	"auto", "break", "case", "char", "const", "continue", "default", "do", "double",
+	"else", "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long",
+	"register", "restrict", "return", "short", "signed", "sizeof", "static", "struct",
+	"switch", "typedef", "union", "unsigned", "void", "volatile", "while", "_Bool",
+	"_Complex", "_Imaginary",
+];
+
+const PUNCTUATORS: [&str; 54] = [
+	"[", "]", "(", ")", "{", "}", ".", "->", "++", "--", "&", "*", "+", "-", "~", "!",
+	"/", "%", "<<", ">>", "<", ">", "<=", ">=", "==", "!=", "^", "|", "&&", "||", "?",
+	":", ";", "...", "=", "*=", "/=", "%=", "+=", "-=", "<<=", ">>=", "&=", "^=", "|=",
+	",", "#", "##", "<:", ":>", "<%", "%>", "%:", "%:%:",
+];
+
+fn is_identifier_prefix(ch: char) -> bool
 {
 	return is_alpha(ch) || ch == '_';
 }
 
-pure fn is_identifier_suffix(ch: char) -> bool
+fn is_identifier_suffix(ch: char) -> bool
 {
 	return is_identifier_prefix(ch) || is_digit(ch);
 }
 
-pure fn is_octal(ch: char) -> bool
+// Returns whether the suffix is unsigned and the number of longs.
+fn integer_suffix(suffix: &str) -> Option<(bool, usize)>
 {
-	return ch >= '0' && ch <= '7';
+	let (unsigned, longs) = match suffix.strip_prefix(['u', 'U']).or_else(|| suffix.strip_suffix(['u', 'U']))
+	{
+		Some(longs) => (true, longs),
+		None => (false, suffix),
+	};
+	match longs
+	{
+		"" => Some((unsigned, 0)),
+		"l" | "L" => Some((unsigned, 1)),
+		"ll" | "LL" => Some((unsigned, 2)),
+		_ => None,
+	}
 }
 
-pure fn is_hex(ch: char) -> bool
+fn integer_value(text: &str, suffix: &str) -> Result<Constant, String>
 {
-	return (ch >= '0' && ch <= '9') || (ch >= 'a' && ch <= 'f') || (ch >= 'A' && ch <= 'F');
+	use IntegerType::*;
+
+	let (unsigned, longs) = integer_suffix(suffix).ok_or_else(|| format!("invalid suffix '{}' on integer constant", suffix))?;
+	let (digits, radix) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"))
+	{
+		Some(digits) => (digits, 16),
+		None if text.len() > 1 && text.starts_with('0') => (&text[1..], 8),
+		None => (text, 10),
+	};
+	if let Some(ch) = digits.chars().find(|ch| ch.to_digit(radix).is_none())
+	{
+		return Err(format!("invalid digit '{}' in octal constant", ch));
+	}
+
+	let too_large = || format!("'{}' is too large", text);
+	let value = u64::from_str_radix(digits, radix).map_err(|_| too_large())?;
+	let types: &[IntegerType] = match (unsigned, longs, radix == 10)
+	{
+		(false, 0, true) => &[Int, Long, LongLong],
+		(false, 0, false) => &[Int, UnsignedInt, Long, UnsignedLong, LongLong, UnsignedLongLong],
+		(true, 0, _) => &[UnsignedInt, UnsignedLong, UnsignedLongLong],
+		(false, 1, true) => &[Long, LongLong],
+		(false, 1, false) => &[Long, UnsignedLong, LongLong, UnsignedLongLong],
+		(true, 1, _) => &[UnsignedLong, UnsignedLongLong],
+		(false, _, true) => &[LongLong],
+		(false, _, false) => &[LongLong, UnsignedLongLong],
+		(true, _, _) => &[UnsignedLongLong],
+	};
+	types.iter().find(|ty| value <= ty.max_value()).map(|&ty| Constant::Integer {value, ty}).ok_or_else(too_large)
 }
 
-fn from_base_8(text: &str) -> result::Result<int, ~str>
+fn float_value(text: &str, ty: FloatType) -> f64
 {
-	let mut power = 1;
-	let mut result = 0;
-	
-	let mut i = str::len(text);
-	while i > 0u
+	let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+	match (hex, ty)
 	{
-		i -= 1u;
-		
-		let delta = (text[i] - ('0' as u8)) as int;
-		if power*delta < int::max_value - result
+		(Some(hex), FloatType::Float) => hex_float_value(hex, 24) as f32 as f64,
+		(Some(hex), _) => hex_float_value(hex, 53),
+		(None, FloatType::Float) => text.parse::<f32>().map_or(f64::INFINITY, |value| value as f64),
+		(None, _) => text.parse::<f64>().unwrap_or(f64::INFINITY),
+	}
+}
+
+// Text is the part of a hex float after 0x. The result is rounded to precision bits
+// (which is exact for normal numbers).
+fn hex_float_value(text: &str, precision: u32) -> f64
+{
+	let (mantissa, exponent) = text.split_once(['p', 'P']).unwrap();
+	let mut exponent = exponent.parse::<i64>().unwrap_or(if exponent.starts_with('-') {-100_000} else {100_000}).clamp(-100_000, 100_000);
+
+	// Keep up to 124 bits of the mantissa, the rest only matter for rounding.
+	let mut bits: u128 = 0;
+	let mut sticky = false;
+	let mut fraction = false;
+	for ch in mantissa.chars()
+	{
+		match ch.to_digit(16)
 		{
-			result += power*delta;
+			None => fraction = true,
+			Some(digit) if bits >> 124 == 0 =>
+			{
+				bits = bits << 4 | digit as u128;
+				if fraction
+				{
+					exponent -= 4;
+				}
+			}
+			Some(digit) =>
+			{
+				sticky |= digit != 0;
+				if !fraction
+				{
+					exponent += 4;
+				}
+			}
 		}
-		else
+	}
+	if bits == 0
+	{
+		return 0.0;
+	}
+
+	// Round bits to precision (to nearest, ties to even) so that scaling is exact.
+	let width = 128 - bits.leading_zeros();
+	if width > precision
+	{
+		let shift = width - precision;
+		let half = 1u128 << (shift - 1);
+		let rest = bits & ((1u128 << shift) - 1);
+		bits >>= shift;
+		exponent += shift as i64;
+		if rest > half || (rest == half && (sticky || bits & 1 == 1))
 		{
-			return result::Err(~"Octal number is too large");
+			bits += 1;
 		}
-		power *= 8;
 	}
-	
-	return result::Ok(result);
+
+	let mut value = bits as f64;
+	while exponent > 0
+	{
+		let step = exponent.min(1000);
+		value *= 2f64.powi(step as i32);
+		exponent -= step;
+	}
+	while exponent < 0
+	{
+		let step = exponent.max(-1000);
+		value *= 2f64.powi(step as i32);
+		exponent -= step;
+	}
+	value
 }
 
-fn from_base_16(text: &str) -> result::Result<int, ~str>
+fn make_token<'a>(parser: Parser<'a, &'a str>, kind: TokenKind) -> Parser<'a, Token<'a>>
 {
-	let mut power = 1;
-	let mut result = 0;
-	
-	let mut i = str::len(text);
-	while i > 0u
+	let parser = parser.spanned();
+	Rc::new(move |input: State<'a>|
 	{
-		i -= 1u;
-		
-		let ch = text[i] as char;
-		let delta = 
-			if ch >= '0' && ch <= '9'
+		parser(input).map(|pass| Succeeded {new_state: pass.new_state, value: Token {kind, text: pass.value.0, span: pass.value.1}})
+	})
+}
+
+fn is_hex(ch: char) -> bool
+{
+	return (ch >= '0' && ch <= '9') || (ch >= 'a' && ch <= 'f') || (ch >= 'A' && ch <= 'F');
+}
+
+const ENCODINGS: [Encoding; 5] = [Encoding::Utf8, Encoding::Utf16, Encoding::Utf32, Encoding::Wide, Encoding::Plain];
+
+// A character or numeric escape within a literal.
+enum LiteralChar
+{
+	Char(char),
+	Unit(u64),
+}
+
+// c_char := [^quote \\ \n\r] | escape_sequence
+//
+// Returns the code units for the character. If encoding is None the units are code points.
+// Errors in escapes are reported just after the backslash (so that they aren't merged with
+// the error for the closing quote).
+fn literal_char<'a>(quote: char, encoding: Option<Encoding>) -> Parser<'a, Vec<u32>>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let rest = input.rest();
+		let (ch, count) = match rest.chars().next()
+		{
+			None if input.partial => return incomplete(input),
+			None | Some('\r' | '\n') => return Err(Failed::new(input.clone(), input, String::new())),
+			Some(ch) if ch == quote => return Err(Failed::new(input.clone(), input, String::new())),
+			Some('\\') =>
 			{
-				(text[i] - ('0' as u8)) as int
+				let result = escape_sequence(rest);
+				let count = match &result {Ok((_, count)) => *count, Err(_) => rest.len()};
+				if input.partial && count == rest.len()
+				{
+					return incomplete(input);		// the escape might continue
+				}
+				match result
+				{
+					Ok(result) => result,
+					Err(mesg) => return Err(Failed::message(input.clone(), input.advance(1), mesg)),
+				}
 			}
-			else if ch >= 'a' && ch <= 'f'
+			Some(ch) => (LiteralChar::Char(ch), ch.len_utf8()),
+		};
+
+		let mut units = Vec::new();
+		match (ch, encoding)
+		{
+			(LiteralChar::Char(ch), Some(encoding)) => encoding.encode(ch, &mut units),
+			(LiteralChar::Char(ch), None) => units.push(ch as u32),
+			(LiteralChar::Unit(unit), Some(encoding)) if unit <= encoding.max_unit() as u64 => units.push(unit as u32),
+			(LiteralChar::Unit(unit), None) if char::from_u32(unit.min(u32::MAX as u64) as u32).is_some() => units.push(unit as u32),
+			(LiteralChar::Unit(_), _) => return Err(Failed::message(input.clone(), input.advance(1), format!("'{}' is out of range", &rest[..count]))),
+		}
+		Ok(Succeeded {new_state: input.advance(count), value: units})
+	})
+}
+
+// escape_sequence := '\\' ['"?abfnrtv\\]
+// escape_sequence := '\\' octal_digit{1,3}
+// escape_sequence := '\\x' hex_digit+
+// escape_sequence := '\\u' hex_digit{4}
+// escape_sequence := '\\U' hex_digit{8}
+//
+// Text starts with the backslash. Returns the escape and its length. On errors text may
+// have ended too soon (literal_char checks for that).
+fn escape_sequence(text: &str) -> Result<(LiteralChar, usize), String>
+{
+	let digits = |start: usize, radix: u32, max: usize| text[start..].bytes().take(max).take_while(|&b| (b as char).is_digit(radix)).count();
+	match text[1..].chars().next()
+	{
+		Some(ch @ ('\'' | '"' | '?' | '\\' | 'a' | 'b' | 'f' | 'n' | 'r' | 't' | 'v')) =>
+		{
+			Ok((LiteralChar::Char(escape_to_char(ch)), 2))
+		}
+		Some('0'..='7') =>
+		{
+			let count = digits(1, 8, 3);
+			let unit = u64::from_str_radix(&text[1..1 + count], 8).unwrap();
+			Ok((LiteralChar::Unit(unit), 1 + count))
+		}
+		Some('x') =>
+		{
+			let count = digits(2, 16, usize::MAX);
+			if count == 0
 			{
-				10 + (text[i] - ('a' as u8)) as int
+				return Err("\\x used with no following hex digits".to_string());
 			}
-			else
+			let unit = u64::from_str_radix(&text[2..2 + count], 16).unwrap_or(u64::MAX);
+			Ok((LiteralChar::Unit(unit), 2 + count))
+		}
+		Some(prefix @ ('u' | 'U')) =>
+		{
+			let len = if prefix == 'u' {4} else {8};
+			if digits(2, 16, len) < len
 			{
-				10 + (text[i] - ('A' as u8)) as int
-			};
-		if power*delta < int::max_value - result
+				return Err(format!("\\{} needs {} hex digits", prefix, len));
+			}
+
+			// C99 6.4.3: universal characters can't be surrogates or in the basic character set.
+			let value = u32::from_str_radix(&text[2..2 + len], 16).unwrap();
+			match char::from_u32(value)
+			{
+				Some(ch) if value >= 0xA0 || ch == '$' || ch == '@' || ch == '`' => Ok((LiteralChar::Char(ch), 2 + len)),
+				_ => Err(format!("'{}' is not a valid universal character name", &text[..2 + len])),
+			}
+		}
+		Some(ch) =>
 		{
-			result += power*delta;
+			Err(format!("invalid escape sequence '\\{}'", ch))
 		}
-		else
+		None =>
 		{
-			return result::Err(~"Hex number is too large");
+			Err("expected escape sequence".to_string())
 		}
-		power *= 16;
 	}
-	
-	return result::Ok(result);
+}
+
+// literal := prefix? body where body returns the parser for the encoding.
+fn prefixed<'a, T: 'a>(quote: char, body: impl Fn(Encoding) -> Parser<'a, T>) -> Parser<'a, (Encoding, T)>
+{
+	let bodies: Vec<_> = ENCODINGS.iter().map(|&encoding| (encoding, body(encoding))).collect();
+
+	Rc::new(move |input: State<'a>|
+	{
+		let rest = input.rest();
+		let starts = |encoding: Encoding| rest.strip_prefix(encoding.prefix()).is_some_and(|r| r.starts_with(quote));
+		match bodies.iter().find(|(encoding, _)| starts(*encoding))
+		{
+			Some((encoding, body)) =>
+			{
+				let pass = body(input.advance(encoding.prefix().len())).map_err(|failure| Failed {old_state: input.clone(), ..failure})?;
+				Ok(Succeeded {new_state: pass.new_state, value: (*encoding, pass.value)})
+			}
+			None if input.partial && ENCODINGS.iter().any(|e| format!("{}{}", e.prefix(), quote).starts_with(rest)) =>
+			{
+				incomplete(input)
+			}
+			None =>
+			{
+				Err(Failed::new(input.clone(), input, format!("'{}'", quote)))
+			}
+		}
+	})
+}
+
+// char_body := '\'' c_char* '\''
+fn char_body<'a>(encoding: Encoding) -> Parser<'a, Vec<u32>>
+{
+	let body = literal_char('\'', Some(encoding)).many_till("'".lit());
+	seq!(skip "'".lit(), body).thene(|chars| ret(chars.concat()))
+}
+
+// string_body := '"' s_char* '"'
+fn string_body<'a>(encoding: Encoding) -> Parser<'a, Vec<u32>>
+{
+	let body = literal_char('"', Some(encoding)).many_till("\"".lit());
+	seq!(skip "\"".lit(), body).thene(|chars| ret(chars.concat()))
 }
 
 fn escape_to_char(ch: char) -> char
@@ -282,7 +963,7 @@ fn escape_to_char(ch: char) -> char
 		}
 		'b' =>
 		{
-			'\x7F'
+			'\x08'
 		}
 		'f' =>
 		{
@@ -310,64 +991,3 @@ fn escape_to_char(ch: char) -> char
 		}
 	}
 }
-
-fn octal_digits() -> Parser<int>
-{
-	do match1(is_octal).thene()
-		    |text| {
-			match from_base_8(*text)
-			{
-				result::Ok(value) =>
-				{
-					ret(value)
-				}
-				result::Err(copy mesg) =>
-				{
-					fails(mesg)
-				}
-			}
-		}
-}
-
-fn hex_digits() -> Parser<int>
-{
-	do match1(is_hex).thene()
-		|text| {
-			match from_base_16(*text)
-			{
-				result::Ok(value) =>
-				{
-					ret(value)
-				}
-				result::Err(copy mesg) =>
-				{
-					fails(mesg)
-				}
-			}
-		}
-}
-
-// escape-sequence := '\\' ['"?abfnrtv\\]
-// escape-sequence := '\\' octal-digit{1, 3}
-// escape-sequence := '\\x' hex-digit{1, 2}
-// escape-sequence := universal-character-name
-fn escape_sequence() -> Parser<char>
-{
-	let escape = do "'\"?abfnrtv\\".anyc().thene()
-		|ch| {ret(escape_to_char(ch))};
-	
-	let case1 = seq2_ret1("\\".lit(), escape);
-	let case2 = seq2_ret1("\\".lit(), octal_digits().thene(|n| ret(n as char) ));
-	let case3 = seq2_ret1("\\x".lit(), hex_digits().thene(|n| ret(n as char) ));
-	let case4 = universal_character_name();
-	or_v(@~[case1, case2, case3, case4]).err("")
-}
-
-// universal-character-name := '\\u' hex-digit{4}
-// universal-character-name := '\\U' hex-digit{8}
-fn universal_character_name() -> Parser<char>
-{
-	seq3_ret2("\\".lit(), "uU".anyc(), hex_digits()).thene(|n| ret(n as char) )
-}
-
-
@@ -0,0 +1,538 @@
+//! Parsers for C99 preprocessing tokens and directives (see section 6.10 of the standard), e.g.
+//!
+//! ```
+//! # use rparse::*;
+//! let text = "#ifndef MAX_H\n#define MAX(a, b) ((a) > (b) ? (a) : (b))\nint max;\n#endif\n";
+//! let file = pp_file().parse("max.h", text).unwrap();
+//!
+//! let GroupPart::Conditional(ref conditional) = file[0] else {panic!()};
+//! assert_eq!(conditional.branches[0].0, Condition::Ifndef("MAX_H".into()));
+//!
+//! let GroupPart::Directive(Directive::Define {ref name, ref params, ref body, ..}, _) = conditional.branches[0].1[0] else {panic!()};
+//! assert_eq!((name.as_ref(), params.as_ref().unwrap().len(), body.len()), ("MAX", 2, 17));
+//! ```
+//!
+//! Comments are treated as white space and a backslash followed by a newline splices lines
+//! together, e.g. `#define X \` continues the directive onto the next line. Splices may also
+//! appear within tokens: token text is the original text but names are spelled without the
+//! splices (see PpToken::spelling). Macros are not expanded and conditions are not evaluated:
+//! all of the groups in a conditional are parsed.
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::c99_parsers::*;
+use crate::misc::*;
+use crate::parsers::*;
+use crate::types::*;
+use crate::{alt, seq};
+
+/// The kinds of preprocessing tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PpTokenKind
+{
+	Identifier,
+
+	/// Preprocessing numbers include integer and floating constants, but also things
+	/// like `1.2.3` and `0xe+1`.
+	Number,
+	CharConstant,
+	StringLiteral,
+	Punctuator,
+
+	/// A character that doesn't start any other token, e.g. `@` or an unmatched quote.
+	Other,
+}
+
+/// A preprocessing token. Text is the original text of the token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PpToken<'a> {pub kind: PpTokenKind, pub text: &'a str, pub span: Span}
+
+impl<'a> PpToken<'a>
+{
+	/// Returns the text of the token with any line splices removed.
+	pub fn spelling(&self) -> Cow<'a, str>
+	{
+		if self.text.contains(['\r', '\n'])
+		{
+			Cow::Owned(splice(self.text).0)
+		}
+		else
+		{
+			Cow::Borrowed(self.text)
+		}
+	}
+}
+
+/// Directives other than the conditional directives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Directive<'a>
+{
+	/// `#include <path>` or `#include "path"`.
+	Include {path: &'a str, system: bool},
+
+	/// `#include` followed by macros that expand into a header name.
+	IncludeMacro(Vec<PpToken<'a>>),
+
+	/// Params is None for object-like macros. Variadic macros end with `...`.
+	Define {name: Cow<'a, str>, params: Option<Vec<Cow<'a, str>>>, variadic: bool, body: Vec<PpToken<'a>>},
+
+	Undef(Cow<'a, str>),
+
+	/// `#line 10 "foo.c"`
+	Line {line: u64, file: Option<String>},
+
+	Error(Vec<PpToken<'a>>),
+
+	Pragma(Vec<PpToken<'a>>),
+
+	/// A `#` on its own line.
+	Null,
+
+	/// Directives that aren't part of C99, e.g. `#warning`.
+	Unknown {name: Cow<'a, str>, tokens: Vec<PpToken<'a>>},
+}
+
+/// The conditions of the branches in a Conditional.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition<'a>
+{
+	/// `#if` and `#elif`.
+	If(Vec<PpToken<'a>>),
+	Ifdef(Cow<'a, str>),
+	Ifndef(Cow<'a, str>),
+}
+
+/// `#if`, `#ifdef`, or `#ifndef` with the optional `#elif` and `#else` groups.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conditional<'a> {pub branches: Vec<(Condition<'a>, Vec<GroupPart<'a>>)>, pub else_group: Option<Vec<GroupPart<'a>>>}
+
+/// The lines of a file (or of a group within a Conditional).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GroupPart<'a>
+{
+	/// A line of tokens that isn't a directive. Blank lines are skipped.
+	Text(Vec<PpToken<'a>>),
+
+	/// Span covers the directive's line(s).
+	Directive(Directive<'a>, Span),
+
+	Conditional(Conditional<'a>),
+}
+
+/// pp_token := pp_number | c_char_constant | string_token | identifier | punctuator | other
+///
+/// pp_number := '.'? [0-9] ([0-9a-zA-Z_.] | \[eEpP\] [+-])*
+///
+/// Tokens may contain line splices, e.g. `a\` followed by a newline and `b` is one identifier.
+pub fn pp_token<'a>() -> Parser<'a, PpToken<'a>>
+{
+	let parser = unspliced_token();
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+
+		// A token which runs into a splice (or a quote which might be the start of a literal
+		// containing one) has to be lexed again without the splices.
+		let rest = &input.bytes[pass.new_state.index..];
+		let quote = pass.value.kind == PpTokenKind::Other && matches!(pass.value.text, "'" | "\"");
+		if !quote && !matches!(rest, [b'\\', b'\r' | b'\n', ..])
+		{
+			return Ok(pass);
+		}
+
+		let (text, offsets) = splice(input.rest());
+		let Ok(spliced) = unspliced_token()(State::new(input.file, &text)) else {return Ok(pass)};
+
+		// The token ends just after its last byte (not after any splice that follows it).
+		let len = offsets[spliced.new_state.index - 1] + 1;
+		if len <= pass.value.text.len()
+		{
+			return Ok(pass);
+		}
+		let new_state = input.advance(len);
+		let span = Span {start: input.position(), end: new_state.position()};
+		Ok(Succeeded {new_state, value: PpToken {kind: spliced.value.kind, text: &input.rest()[..len], span}})
+	})
+}
+
+/// pp_file := group EOT
+///
+/// group := (conditional | directive | text_line)*
+pub fn pp_file<'a>() -> Parser<'a, Vec<GroupPart<'a>>>
+{
+	let group = group();
+
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = group(input.clone())?;
+		if pass.new_state.at_end()
+		{
+			return Ok(pass);
+		}
+
+		// The group stopped at an #elif, #else, or #endif.
+		let name = directive_name()(pass.new_state.clone())?;
+		Err(Failed::message(input, pass.new_state, format!("#{} without #if", name.value)))
+	})
+}
+
+// ---- Helpers ---------------------------------------------------------------
+const DIRECTIVES: [&str; 12] = ["define", "elif", "else", "endif", "error", "if", "ifdef", "ifndef", "include", "line", "pragma", "undef"];
+
+fn unspliced_token<'a>() -> Parser<'a, PpToken<'a>>
+{
+	alt!(
+		make_pp_token(pp_number(), PpTokenKind::Number),
+		make_pp_token(ret_str(c_char_constant()), PpTokenKind::CharConstant),
+		make_pp_token(string_token(), PpTokenKind::StringLiteral),
+		make_pp_token(identifier(), PpTokenKind::Identifier),
+		make_pp_token(punctuator(), PpTokenKind::Punctuator),
+		make_pp_token(ret_str(anycp(|ch| !is_whitespace(ch))), PpTokenKind::Other)
+	).err("token")
+}
+
+// Returns the text up to the end of the (spliced) line with the splices removed along with
+// the offset within text of each of its bytes (plus the offset of the end).
+fn splice(text: &str) -> (String, Vec<usize>)
+{
+	let bytes = text.as_bytes();
+	let mut spliced = Vec::new();
+	let mut offsets = Vec::new();
+	let mut i = 0;
+	loop
+	{
+		match bytes[i..]
+		{
+			[b'\\', b'\r', b'\n', ..] => i += 3,
+			[b'\\', b'\r' | b'\n', ..] => i += 2,
+			[b'\r' | b'\n', ..] | [] => break,
+			[b, ..] =>
+			{
+				spliced.push(b);
+				offsets.push(i);
+				i += 1;
+			}
+		}
+	}
+	offsets.push(i);
+
+	// Only ASCII was removed so spliced is still UTF-8.
+	(String::from_utf8(spliced).unwrap(), offsets)
+}
+
+// Returns the offset of the end of the (spliced) line.
+fn line_end(bytes: &[u8]) -> usize
+{
+	let mut i = 0;
+	loop
+	{
+		match bytes[i..]
+		{
+			[b'\\', b'\r', b'\n', ..] => i += 3,
+			[b'\\', b'\r' | b'\n', ..] => i += 2,
+			[b'\r' | b'\n', ..] | [] => return i,
+			_ => i += 1,
+		}
+	}
+}
+
+// Matches a token of the given kind and converts its spelling (returning an error if convert
+// returns None).
+fn spelled<'a, T: 'a>(kind: PpTokenKind, expected: &'static str, convert: impl Fn(Cow<'a, str>) -> Option<T> + 'a) -> Parser<'a, T>
+{
+	let token = pp_token();
+	Rc::new(move |input: State<'a>|
+	{
+		match token(input.clone())
+		{
+			Ok(pass) if pass.value.kind == kind => match convert(pass.value.spelling())
+			{
+				Some(value) => Ok(Succeeded {new_state: pass.new_state, value}),
+				None => Err(Failed::new(input.clone(), input, expected.to_string())),
+			},
+			_ => Err(Failed::new(input.clone(), input, expected.to_string())),
+		}
+	})
+}
+
+fn pp_identifier<'a>() -> Parser<'a, Cow<'a, str>>
+{
+	spelled(PpTokenKind::Identifier, "identifier", Some)
+}
+
+fn make_pp_token<'a>(parser: Parser<'a, &'a str>, kind: PpTokenKind) -> Parser<'a, PpToken<'a>>
+{
+	let parser = parser.spanned();
+	Rc::new(move |input: State<'a>|
+	{
+		parser(input).map(|pass| Succeeded {new_state: pass.new_state, value: PpToken {kind, text: pass.value.0, span: pass.value.1}})
+	})
+}
+
+fn pp_number<'a>() -> Parser<'a, &'a str>
+{
+	fn number(text: &str, index: usize) -> usize
+	{
+		let bytes = &text.as_bytes()[index..];
+		let start = if bytes.first() == Some(&b'.') {1} else {0};
+		if !bytes.get(start).is_some_and(u8::is_ascii_digit)
+		{
+			return 0;
+		}
+
+		let mut i = start + 1;
+		loop
+		{
+			match bytes[i..]
+			{
+				[b'e' | b'E' | b'p' | b'P', b'+' | b'-', ..] => i += 2,
+				[b, ..] if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' => i += 1,
+				_ => return i,
+			}
+		}
+	}
+
+	let parser = scan(number);
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(input.clone())?;
+		if pass.value.is_empty()
+		{
+			Err(Failed::new(input.clone(), input, String::new()))
+		}
+		else
+		{
+			Ok(pass)
+		}
+	})
+}
+
+// hspace := ([ \t\v\f] | comment | line_comment | '\\' newline)*
+//
+// White space within a line.
+fn hspace<'a>() -> Parser<'a, ()>
+{
+	Rc::new(|input: State<'a>|
+	{
+		let bytes = input.rest().as_bytes();
+		let mut i = 0;
+		loop
+		{
+			match bytes[i..]
+			{
+				[b' ' | b'\t' | b'\x0B' | b'\x0C', ..] => i += 1,
+				[b'\\', b'\r', b'\n', ..] => i += 3,
+				[b'\\', b'\r' | b'\n', ..] => i += 2,
+				[b'/', b'/', ..] => i += line_end(&bytes[i..]),
+				[b'/', b'*', ..] => match input.rest()[i + 2..].find("*/")
+				{
+					Some(offset) => i += offset + 4,
+					None if input.partial => return incomplete(input),
+					None => return Err(Failed::message(input.clone(), input.advance(i), "unterminated comment".to_string())),
+				},
+				[] if input.partial => return incomplete(input),
+				_ => return Ok(Succeeded {new_state: input.advance(i), value: ()}),
+			}
+		}
+	})
+}
+
+// end_of_line := hspace ('\r'? '\n' | '\r' | EOT)
+fn end_of_line<'a>() -> Parser<'a, ()>
+{
+	let newline = alt!("\n".lit(), "\r\n".lit(), "\r".lit()).thene(|_| ret(()));
+	seq!(skip hspace(), newline.or(eot()).err("end of line"))
+}
+
+// line_tokens := hspace (pp_token hspace)* end_of_line
+fn line_tokens<'a>() -> Parser<'a, Vec<PpToken<'a>>>
+{
+	let token = seq!(pp_token(), skip hspace());
+	seq!(skip hspace(), token.many_till(end_of_line()))
+}
+
+// directive_name := hspace ('#' | '%:') hspace identifier
+fn directive_name<'a>() -> Parser<'a, Cow<'a, str>>
+{
+	let hash = "#".lit().or("%:".lit());
+	seq!(skip hspace(), skip hash, skip hspace(), pp_identifier())
+}
+
+// Matches the start of the named directive (without consuming anything if the name is different).
+fn directive<'a>(name: &'static str) -> Parser<'a, ()>
+{
+	let parser = directive_name();
+	Rc::new(move |input: State<'a>|
+	{
+		match parser(input.clone())
+		{
+			Ok(pass) if pass.value == name => Ok(Succeeded {new_state: pass.new_state, value: ()}),
+			_ => Err(Failed::new(input.clone(), input, format!("#{}", name))),
+		}
+	})
+}
+
+// Succeeds without consuming anything at the end of the text or an #elif, #else, or #endif.
+fn at_group_end<'a>() -> Parser<'a, ()>
+{
+	let parser = directive_name();
+	Rc::new(move |input: State<'a>|
+	{
+		if input.at_end() && !input.partial
+		{
+			return Ok(Succeeded {new_state: input, value: ()});
+		}
+		match parser(input.clone())
+		{
+			Ok(pass) if matches!(pass.value.as_ref(), "elif" | "else" | "endif") => Ok(Succeeded {new_state: input, value: ()}),
+			_ => Err(Failed::new(input.clone(), input, String::new())),
+		}
+	})
+}
+
+// group := (conditional | control_line | text_line)*
+fn group<'a>() -> Parser<'a, Vec<GroupPart<'a>>>
+{
+	let group_ptr = Rc::new(RefCell::new(fails("")));
+	let part = alt!(conditional(forward_ref(group_ptr.clone())), control_line(), text_line());
+	let group = part.many_till(at_group_end()).thene(|parts| ret(parts.into_iter().filter(|part| part != &GroupPart::Text(Vec::new())).collect::<Vec<_>>()));
+	*group_ptr.borrow_mut() = group.clone();
+	group
+}
+
+// text_line := hspace !('#' | '%:') line_tokens
+fn text_line<'a>() -> Parser<'a, GroupPart<'a>>
+{
+	let tokens = line_tokens();
+	let space = hspace();
+	Rc::new(move |input: State<'a>|
+	{
+		let start = space(input.clone())?.new_state;
+		let rest = start.rest();
+		if rest.starts_with('#') || rest.starts_with("%:")
+		{
+			// Also reported when the directive couldn't be parsed.
+			return Err(Failed::new(input, start, "directive".to_string()));
+		}
+		tokens(input).map(|pass| Succeeded {new_state: pass.new_state, value: GroupPart::Text(pass.value)})
+	})
+}
+
+// conditional := if_line group ('#elif' line_tokens group)* ('#else' end_of_line group)? '#endif' end_of_line
+// if_line := '#if' line_tokens | '#ifdef' hspace identifier end_of_line | '#ifndef' hspace identifier end_of_line
+fn conditional<'a>(group: Parser<'a, Vec<GroupPart<'a>>>) -> Parser<'a, GroupPart<'a>>
+{
+	let name = || seq!(skip hspace(), pp_identifier().err("macro name"), skip end_of_line());
+	let if_line = alt!(
+		seq!(skip directive("if"), line_tokens()).thene(|tokens| ret(Condition::If(tokens))),
+		seq!(skip directive("ifdef"), name()).thene(|name| ret(Condition::Ifdef(name))),
+		seq!(skip directive("ifndef"), name()).thene(|name| ret(Condition::Ifndef(name)))
+	);
+	let elif = seq!(skip directive("elif"), line_tokens(), group.clone()).thene(|(tokens, group)| ret((Condition::If(tokens), group)));
+	let else_group = seq!(skip directive("else"), skip end_of_line(), group.clone());
+	let endif = seq!(skip directive("endif"), skip end_of_line());
+
+	seq!(if_line, group, elif.r0(), else_group.optional(), skip endif).thene(|(condition, group, elifs, else_group)|
+	{
+		let mut branches = vec![(condition, group)];
+		branches.extend(elifs);
+		ret(GroupPart::Conditional(Conditional {branches, else_group}))
+	})
+}
+
+// control_line := include | define | undef | line | error | pragma | null | unknown
+fn control_line<'a>() -> Parser<'a, GroupPart<'a>>
+{
+	let name = || seq!(skip hspace(), pp_identifier().err("macro name"));
+
+	// include := '#include' hspace (('<' [^>\n]+ '>' | '"' [^"\n]+ '"') end_of_line | line_tokens)
+	let system = seq!(skip "<".lit(), match1(|ch| ch != '>' && ch != '\n'), skip ">".lit()).thene(|path| ret((path, true)));
+	let local = seq!(skip "\"".lit(), match1(|ch| ch != '"' && ch != '\n'), skip "\"".lit()).thene(|path| ret((path, false)));
+	let header = seq!(system.or(local), skip end_of_line()).thene(|(path, system)| ret(Directive::Include {path, system}));
+	let include = seq!(skip directive("include"), skip hspace(), header.or(line_tokens().thene(|tokens| ret(Directive::IncludeMacro(tokens)))));
+
+	// define := '#define' hspace identifier params? line_tokens
+	let define = seq!(skip directive("define"), name(), params(), line_tokens()).thene(|(name, params, body)|
+	{
+		let variadic = params.as_ref().is_some_and(|(_, variadic)| *variadic);
+		ret(Directive::Define {name, params: params.map(|(names, _)| names), variadic, body})
+	});
+
+	let undef = seq!(skip directive("undef"), name(), skip end_of_line()).thene(|name| ret(Directive::Undef(name)));
+
+	// line := '#line' hspace [0-9]+ (hspace string_literal)? end_of_line
+	let line_number = spelled(PpTokenKind::Number, "line number", |text| text.parse::<u64>().ok());
+	let path = spelled(PpTokenKind::StringLiteral, "file name", |text| string_literal()(State::new("", &text)).ok().map(|pass| pass.value));
+	let file = seq!(skip hspace(), path).optional();
+	let line = seq!(skip directive("line"), skip hspace(), line_number, file, skip end_of_line()).thene(|(line, file)| ret(Directive::Line {line, file}));
+
+	let error = seq!(skip directive("error"), line_tokens()).thene(|tokens| ret(Directive::Error(tokens)));
+	let pragma = seq!(skip directive("pragma"), line_tokens()).thene(|tokens| ret(Directive::Pragma(tokens)));
+	let null = seq!(skip hspace(), skip "#".lit().or("%:".lit()), skip end_of_line()).thene(|_| ret(Directive::Null));
+	let unknown = seq!(unknown_name(), line_tokens()).thene(|(name, tokens)| ret(Directive::Unknown {name, tokens}));
+
+	let directive = alt!(include, define, undef, line, error, pragma, null, unknown);
+	directive.spanned().thene(|(directive, span)| ret(GroupPart::Directive(directive, span)))
+}
+
+// params := '(' hspace ((identifier hspace ',' hspace)* (identifier | '...') hspace)? ')'
+//
+// The '(' must immediately follow the macro name. Returns the names and whether the macro is
+// variadic or None for object-like macros.
+fn params<'a>() -> Parser<'a, Option<(Vec<Cow<'a, str>>, bool)>>
+{
+	let space = hspace();
+	let name = seq!(pp_identifier(), skip hspace());
+	let ellipsis = seq!(skip "...".lit(), skip hspace());
+	let comma = seq!(skip ",".lit(), skip hspace());
+	let close = ")".lit();
+	Rc::new(move |input: State<'a>|
+	{
+		if !input.rest().starts_with('(')
+		{
+			return Ok(Succeeded {new_state: input, value: None});
+		}
+		let done = |state: State<'a>, names, variadic| match close(state)
+		{
+			Ok(pass) => Ok(Succeeded {new_state: pass.new_state, value: Some((names, variadic))}),
+			Err(failure) => Err(Failed {old_state: input.clone(), ..failure}),
+		};
+
+		let mut state = space(input.advance(1))?.new_state;
+		let mut names = Vec::new();
+		if state.rest().starts_with(')')
+		{
+			return done(state, names, false);
+		}
+		loop
+		{
+			if let Ok(pass) = ellipsis(state.clone())
+			{
+				return done(pass.new_state, names, true);
+			}
+			let pass = name(state.clone()).map_err(|_| Failed::new(input.clone(), state.clone(), "parameter name".to_string()))?;
+			names.push(pass.value);
+			state = pass.new_state;
+			match comma(state.clone())
+			{
+				Ok(pass) => state = pass.new_state,
+				Err(_) => return done(state, names, false),
+			}
+		}
+	})
+}
+
+// Returns the name of a directive that isn't part of C99.
+fn unknown_name<'a>() -> Parser<'a, Cow<'a, str>>
+{
+	let parser = directive_name();
+	Rc::new(move |input: State<'a>|
+	{
+		match parser(input.clone())
+		{
+			Ok(pass) if !DIRECTIVES.contains(&pass.value.as_ref()) => Ok(pass),
+			_ => Err(Failed::new(input.clone(), input, String::new())),
+		}
+	})
+}
@@ -0,0 +1,267 @@
+//! Lossless concrete syntax trees. Parsers that return Cst values can be built up
+//! from ordinary parsers using token and node, e.g.
+//!
+//! ```
+//! # use rparse::*;
+//! // call := identifier '(' identifier? ')'
+//! let name = identifier().token("name");
+//! let call = seq!(name.clone().s0(), "(".lit().token("lparen").s0(), name.optional(), ")".lit().token("rparen")).node("call");
+//!
+//! let tree = call.parse("test", "f ( x)").unwrap();
+//! assert_eq!(tree.children().len(), 6);       // 4 tokens and 2 trivia
+//! assert_eq!(tree.to_string(), "f ( x)");
+//! ```
+//!
+//! Text within a node that isn't covered by one of its children (e.g. white space skipped
+//! by s0 or the values dropped by skip and sep_by) is added to the node as trivia. So, as
+//! long as the root node matches all of the input (e.g. `p.everything(space).node("file")`),
+//! printing the tree reproduces the input exactly.
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::parsers::*;
+use crate::types::*;
+
+/// A node in a concrete syntax tree. Offsets are byte offsets into the parsed text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Cst<'a>
+{
+	/// A rule along with its children (in order).
+	Node {kind: String, span: Range<usize>, children: Vec<Cst<'a>>},
+
+	/// Text matched by a parser that was marked as a token.
+	Token {kind: String, offset: usize, text: &'a str},
+
+	/// Text that was matched but isn't part of a token or node, e.g. white space and comments.
+	Trivia {offset: usize, text: &'a str},
+}
+
+impl<'a> Cst<'a>
+{
+	/// Returns the kind of a node or token. For trivia this is empty.
+	pub fn kind(&self) -> &str
+	{
+		match self
+		{
+			Cst::Node {kind, ..} | Cst::Token {kind, ..} => kind,
+			Cst::Trivia {..} => "",
+		}
+	}
+
+	/// Returns the range of text covered by self.
+	pub fn span(&self) -> Range<usize>
+	{
+		match self
+		{
+			Cst::Node {span, ..} => span.clone(),
+			Cst::Token {offset, text, ..} | Cst::Trivia {offset, text} => *offset..*offset + text.len(),
+		}
+	}
+
+	/// Returns the children of a node. Tokens and trivia don't have children.
+	pub fn children(&self) -> &[Cst<'a>]
+	{
+		match self
+		{
+			Cst::Node {children, ..} => children,
+			_ => &[],
+		}
+	}
+
+	/// Returns the tokens within self (including those in child nodes).
+	pub fn tokens(&self) -> Vec<&Cst<'a>>
+	{
+		let mut result = Vec::new();
+		self.visit(&mut |cst| if let Cst::Token {..} = cst {result.push(cst)});
+		result
+	}
+
+	fn visit<'b>(&'b self, fun: &mut impl FnMut(&'b Cst<'a>))
+	{
+		fun(self);
+		for child in self.children()
+		{
+			child.visit(fun);
+		}
+	}
+}
+
+/// Prints the text that the tree covers.
+impl fmt::Display for Cst<'_>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		match self
+		{
+			Cst::Node {children, ..} =>
+			{
+				for child in children
+				{
+					write!(f, "{}", child)?;
+				}
+				Ok(())
+			}
+			Cst::Token {text, ..} | Cst::Trivia {text, ..} =>
+			{
+				write!(f, "{}", text)
+			}
+		}
+	}
+}
+
+/// Values that can be used as the children of a node. This is implemented for Cst,
+/// Option, Vec, and tuples so that parsers like seq!, r0, optional, and sep_by0 can
+/// be used to build nodes.
+pub trait CstChildren<'a>
+{
+	/// Appends self to children.
+	fn append_to(self, children: &mut Vec<Cst<'a>>);
+}
+
+impl<'a> CstChildren<'a> for Cst<'a>
+{
+	fn append_to(self, children: &mut Vec<Cst<'a>>)
+	{
+		children.push(self);
+	}
+}
+
+impl<'a> CstChildren<'a> for ()
+{
+	fn append_to(self, _children: &mut Vec<Cst<'a>>)
+	{
+	}
+}
+
+impl<'a, T: CstChildren<'a>> CstChildren<'a> for Option<T>
+{
+	fn append_to(self, children: &mut Vec<Cst<'a>>)
+	{
+		if let Some(value) = self
+		{
+			value.append_to(children);
+		}
+	}
+}
+
+impl<'a, T: CstChildren<'a>> CstChildren<'a> for Vec<T>
+{
+	fn append_to(self, children: &mut Vec<Cst<'a>>)
+	{
+		for value in self
+		{
+			value.append_to(children);
+		}
+	}
+}
+
+macro_rules! tuple_children
+{
+	($($name:ident)+) =>
+	{
+		impl<'a, $($name: CstChildren<'a>),+> CstChildren<'a> for ($($name,)+)
+		{
+			#[allow(non_snake_case)]
+			fn append_to(self, children: &mut Vec<Cst<'a>>)
+			{
+				let ($($name,)+) = self;
+				$($name.append_to(children);)+
+			}
+		}
+	};
+}
+
+tuple_children!(A);
+tuple_children!(A B);
+tuple_children!(A B C);
+tuple_children!(A B C D);
+tuple_children!(A B C D E);
+tuple_children!(A B C D E F);
+tuple_children!(A B C D E F G);
+tuple_children!(A B C D E F G H);
+tuple_children!(A B C D E F G H I);
+
+/// Methods used to build concrete syntax trees.
+pub trait CstParsers<'a, T>
+{
+	/// Returns a token with the text parser matched (the value is ignored).
+	fn token(&self, kind: &str) -> Parser<'a, Cst<'a>>;
+
+	/// Returns trivia with the text parser matched. This is only needed for trivia
+	/// that isn't within a node (e.g. leading white space).
+	fn trivia(&self) -> Parser<'a, Cst<'a>>;
+
+	/// Returns a node whose children are the Cst values returned by parser along
+	/// with trivia for the text between them.
+	fn node(&self, kind: &str) -> Parser<'a, Cst<'a>> where T: CstChildren<'a>;
+}
+
+impl<'a, T: 'a> CstParsers<'a, T> for Parser<'a, T>
+{
+	fn token(&self, kind: &str) -> Parser<'a, Cst<'a>>
+	{
+		let kind = kind.to_string();
+		let parser = ret_str(self.clone());
+
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input.clone()).map(|pass| Succeeded {new_state: pass.new_state, value: Cst::Token {kind: kind.clone(), offset: input.index, text: pass.value}})
+		})
+	}
+
+	fn trivia(&self) -> Parser<'a, Cst<'a>>
+	{
+		let parser = ret_str(self.clone());
+
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input.clone()).map(|pass| Succeeded {new_state: pass.new_state, value: Cst::Trivia {offset: input.index, text: pass.value}})
+		})
+	}
+
+	fn node(&self, kind: &str) -> Parser<'a, Cst<'a>> where T: CstChildren<'a>
+	{
+		let kind = kind.to_string();
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			if input.is_binary()
+			{
+				return Err(Failed::message(input.clone(), input, "text input".to_string()));		// fill_gaps slices text
+			}
+			parser(input.clone()).map(|pass|
+			{
+				let mut values = Vec::new();
+				pass.value.append_to(&mut values);
+
+				let span = input.index..pass.new_state.index;
+				let children = fill_gaps(input.text, span.clone(), values);
+				Succeeded {new_state: pass.new_state, value: Cst::Node {kind: kind.clone(), span, children}}
+			})
+		})
+	}
+}
+
+// Adds trivia for the parts of span that values don't cover.
+fn fill_gaps<'a>(text: &'a str, span: Range<usize>, values: Vec<Cst<'a>>) -> Vec<Cst<'a>>
+{
+	let mut children = Vec::with_capacity(values.len());
+	let mut offset = span.start;
+	for value in values
+	{
+		let range = value.span();
+		if range.start > offset
+		{
+			children.push(Cst::Trivia {offset, text: &text[offset..range.start]});
+		}
+		offset = offset.max(range.end);
+		children.push(value);
+	}
+	if span.end > offset
+	{
+		children.push(Cst::Trivia {offset, text: &text[offset..span.end]});
+	}
+	children
+}
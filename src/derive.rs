@@ -0,0 +1,83 @@
+//! Types which can parse themselves. Parse is normally implemented with the derive
+//! macro, e.g.
+//!
+//! ```
+//! # use rparse::*;
+//! #[derive(Debug, PartialEq, Parse)]
+//! #[rparse("name '=' value ';'")]
+//! struct Setting<'a>
+//! {
+//!     #[rparse(parser = "identifier()")]
+//!     name: &'a str,
+//!
+//!     #[rparse(parser = "decimal_number()")]
+//!     value: i64,
+//! }
+//!
+//! #[derive(Debug, PartialEq, Parse)]
+//! #[rparse("'[' name ']' settings")]
+//! struct Section<'a>
+//! {
+//!     #[rparse(parser = "identifier()")]
+//!     name: &'a str,
+//!     settings: Vec<Setting<'a>>,
+//! }
+//!
+//! let section = Section::parser().parse("test", "[server] port = 80; threads=4;").unwrap();
+//! assert_eq!(section.settings[1], Setting {name: "threads", value: 4});
+//! ```
+//!
+//! The grammar attribute is a list of quoted tokens and field names (or indexes for tuple
+//! fields). Every field must appear once. If the grammar is omitted the fields are parsed
+//! in order. Tokens and the parser attributes of fields are followed by s0 so that white
+//! space may appear after each item.
+//!
+//! Fields without a parser attribute use the Parse implementation of their type. Vec,
+//! Option, and Box fields use e*, e?, and e. Vec fields can also have a sep attribute
+//! in which case the elements are parsed with sep_by0.
+//!
+//! Enums are parsed by trying each variant in order (each variant may have its own
+//! grammar). The label attribute, e.g. `#[rparse(label = "setting")]`, sets the err label
+//! for the type. By default the label is the type name split into lower case words.
+use std::rc::Rc;
+
+use crate::parsers::*;
+use crate::types::*;
+
+/// Implemented by types that can be parsed (see the module docs).
+pub trait Parse<'a>: Sized + 'a
+{
+	/// Returns a new parser for the type.
+	fn parser() -> Parser<'a, Self>;
+}
+
+/// e*
+impl<'a, T: Parse<'a>> Parse<'a> for Vec<T>
+{
+	fn parser() -> Parser<'a, Self>
+	{
+		T::parser().r0()
+	}
+}
+
+/// e?
+impl<'a, T: Parse<'a>> Parse<'a> for Option<T>
+{
+	fn parser() -> Parser<'a, Self>
+	{
+		T::parser().optional()
+	}
+}
+
+/// e
+impl<'a, T: Parse<'a>> Parse<'a> for Box<T>
+{
+	fn parser() -> Parser<'a, Self>
+	{
+		let parser = T::parser();
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input).map(|pass| Succeeded {new_state: pass.new_state, value: Box::new(pass.value)})
+		})
+	}
+}
@@ -0,0 +1,758 @@
+//! Builds parsers at runtime from PEG style grammars, e.g.
+//!
+//! ```
+//! # use rparse::*;
+//! let grammar = Grammar::new("list", r#"
+//!     list   := '[' _ (number (',' _ number)*)? ']'
+//!     number := digits:[0-9]+ _
+//!     _      := [ \t\r\n]*       // rules starting with _ don't create nodes
+//! "#).unwrap();
+//!
+//! let tree = grammar.parser("list").parse("test", "[1, 23]").unwrap();
+//! assert_eq!(tree.children.len(), 2);
+//! assert_eq!(tree.children[1].capture("digits"), Some("23"));
+//! ```
+//!
+//! The syntax is:
+//!
+//! ```text
+//! grammar   := rule+
+//! rule      := name ':=' choice
+//! choice    := sequence ('|' sequence)*       ordered, i.e. the first match wins
+//! sequence  := prefixed+
+//! prefixed  := ('!' | '&')? item             not and and lookaheads (these don't consume input)
+//! item      := (name ':')? suffixed           captures the text suffixed matched
+//! suffixed  := primary ('*' | '+' | '?')?
+//! primary   := literal | class | '.' | '(' choice ')' | name
+//! literal   := "'" char* "'" | '"' char* '"'
+//! class     := '[' '^'? (char ('-' char)?)* ']'
+//! ```
+//!
+//! Literals and classes support the \n, \r, \t, \0, and \\ escapes (a backslash before
+//! any other character matches that character). White space and // comments may
+//! appear between tokens.
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::c99_parsers::*;
+use crate::line_index::*;
+use crate::parsers::*;
+use crate::types::*;
+use crate::{alt, seq};
+
+/// A grammar which can be used to create parsers. Also see the module docs.
+#[derive(Clone, Debug)]
+pub struct Grammar
+{
+	rules: Vec<Rule>,
+}
+
+/// The value a rule passes to its action. Children are the values of the rules
+/// that were matched while matching this rule (in order) and captures are the
+/// named captures. Rules whose names start with an underscore are hidden: they
+/// don't have actions and their children and captures are added to their parent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node<'a, T>
+{
+	pub rule: String,
+	pub text: &'a str,
+	pub span: Span,
+	pub children: Vec<T>,
+	pub captures: Vec<(String, &'a str)>,
+}
+
+/// The parse tree returned by Grammar::parser.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tree<'a>
+{
+	pub rule: String,
+	pub text: &'a str,
+	pub span: Span,
+	pub children: Vec<Tree<'a>>,
+	pub captures: Vec<(String, &'a str)>,
+}
+
+/// Converts nodes into values. An error message fails the parse.
+pub type Action<'a, T> = Rc<dyn Fn(Node<'a, T>) -> Result<T, String> + 'a>;
+
+/// The actions to use for each rule.
+pub struct Actions<'a, T>
+{
+	default: Action<'a, T>,
+	actions: HashMap<String, Action<'a, T>>,
+}
+
+impl<'a, T> Node<'a, T>
+{
+	/// Returns the text of the first capture with name.
+	pub fn capture(&self, name: &str) -> Option<&'a str>
+	{
+		self.captures.iter().find(|c| c.0 == name).map(|c| c.1)
+	}
+}
+
+impl<'a> Tree<'a>
+{
+	/// Returns the text of the first capture with name.
+	pub fn capture(&self, name: &str) -> Option<&'a str>
+	{
+		self.captures.iter().find(|c| c.0 == name).map(|c| c.1)
+	}
+}
+
+impl<'a, T> Actions<'a, T>
+{
+	/// Default is used for rules that don't have their own action.
+	pub fn new(default: impl Fn(Node<'a, T>) -> Result<T, String> + 'a) -> Actions<'a, T>
+	{
+		Actions {default: Rc::new(default), actions: HashMap::new()}
+	}
+
+	/// Sets the action for the rule with name.
+	pub fn rule(mut self, name: &str, action: impl Fn(Node<'a, T>) -> Result<T, String> + 'a) -> Self
+	{
+		self.actions.insert(name.to_string(), Rc::new(action));
+		self
+	}
+}
+
+impl Grammar
+{
+	/// Parses a grammar. Besides syntax errors this fails if a rule is defined more
+	/// than once, if a rule refers to a rule which isn't defined, or if a rule is left
+	/// recursive (i.e. it can call itself without consuming any input).
+	pub fn new(file: &str, text: &str) -> ParseStatus<Grammar>
+	{
+		let rules = grammar_parser().parse(file, text)?;
+
+		let mut names: HashMap<&str, &Rule> = HashMap::new();
+		for rule in rules.iter()
+		{
+			if names.insert(&rule.name, rule).is_some()
+			{
+				return Err(grammar_error(file, text, rule.pos, rule.name.len(), format!("rule {} is defined more than once", rule.name)));
+			}
+		}
+
+		for rule in rules.iter()
+		{
+			let mut result = Ok(());
+			rule.expr.visit(&mut |expr|
+			{
+				if let Expr::Rule(name, pos) = expr
+				{
+					if result.is_ok() && !names.contains_key(name.as_str())
+					{
+						result = Err(grammar_error(file, text, *pos, name.len(), format!("rule {} is not defined", name)));
+					}
+				}
+			});
+			result?;
+		}
+
+		if let Some(rule) = left_recursive(&rules)
+		{
+			return Err(grammar_error(file, text, rule.pos, rule.name.len(), format!("rule {} is left recursive", rule.name)));
+		}
+
+		Ok(Grammar {rules})
+	}
+
+	/// Returns true if the grammar defines a rule with name.
+	pub fn has_rule(&self, name: &str) -> bool
+	{
+		self.rules.iter().any(|rule| rule.name == name)
+	}
+
+	/// Returns a parser for the start rule which returns the parse tree.
+	pub fn parser<'a>(&self, start: &str) -> Parser<'a, Tree<'a>>
+	{
+		self.parser_with(start, Actions::new(|node: Node<'a, Tree<'a>>|
+		{
+			Ok(Tree {rule: node.rule, text: node.text, span: node.span, children: node.children, captures: node.captures})
+		}))
+	}
+
+	/// Returns a parser for the start rule which uses actions to compute the value of
+	/// each rule. Start must not be a hidden rule.
+	pub fn parser_with<'a, T: 'a>(&self, start: &str, actions: Actions<'a, T>) -> Parser<'a, T>
+	{
+		assert!(self.has_rule(start), "grammar has no {} rule", start);
+		assert!(!is_hidden(start), "start rule {} is hidden", start);
+
+		let refs: HashMap<String, Rc<RefCell<Parser<'a, Collected<'a, T>>>>> = self.rules.iter()
+			.map(|rule| (rule.name.clone(), Rc::new(RefCell::new(fails("")))))
+			.collect();
+		for rule in self.rules.iter()
+		{
+			let body = compile(&rule.expr, &refs);
+			let parser = if is_hidden(&rule.name)
+			{
+				body
+			}
+			else
+			{
+				let action = actions.actions.get(&rule.name).unwrap_or(&actions.default).clone();
+				rule_parser(&rule.name, body, action)
+			};
+			*refs[&rule.name].borrow_mut() = parser;
+		}
+
+		let parser = forward_ref(refs[start].clone());
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input).map(|pass| Succeeded {new_state: pass.new_state, value: pass.value.0.into_iter().next().unwrap()})
+		})
+	}
+}
+
+// ---- compiler ----------------------------------------------------------------------------------
+// The values and captures of the rules matched so far.
+type Collected<'a, T> = (Vec<T>, Vec<(String, &'a str)>);
+
+fn is_hidden(name: &str) -> bool
+{
+	name.starts_with('_')
+}
+
+fn compile<'a, T: 'a>(expr: &Expr, refs: &HashMap<String, Rc<RefCell<Parser<'a, Collected<'a, T>>>>>) -> Parser<'a, Collected<'a, T>>
+{
+	match expr
+	{
+		Expr::Literal(text) =>
+		{
+			discard(text.lit())
+		}
+		Expr::Class(class) =>
+		{
+			discard(class_parser(class.clone()))
+		}
+		Expr::Rule(name, _) =>
+		{
+			forward_ref(refs[name].clone())
+		}
+		Expr::Seq(exprs) =>
+		{
+			let parsers: Vec<_> = exprs.iter().map(|e| compile(e, refs)).collect();
+			Rc::new(move |input: State<'a>|
+			{
+				let mut state = input.clone();
+				let mut collected = (Vec::new(), Vec::new());
+				for parser in parsers.iter()
+				{
+					let pass = parser(state).map_err(|failure| Failed {old_state: input.clone(), ..failure})?;
+					append(&mut collected, pass.value);
+					state = pass.new_state;
+				}
+				Ok(Succeeded {new_state: state, value: collected})
+			})
+		}
+		Expr::Choice(exprs) =>
+		{
+			or_v(exprs.iter().map(|e| compile(e, refs)).collect())
+		}
+		Expr::Repeat(expr, min, max) =>
+		{
+			repeat(compile(expr, refs), *min, *max)
+		}
+		Expr::Not(expr) =>
+		{
+			let parser = compile(expr, refs);
+			Rc::new(move |input: State<'a>|
+			{
+				match parser(input.clone())
+				{
+					Ok(_) => Err(Failed::new(input.clone(), input, String::new())),
+					Err(failure) if failure.is_incomplete() => Err(Failed {old_state: input, ..failure}),
+					Err(_) => Ok(Succeeded {new_state: input, value: (Vec::new(), Vec::new())}),
+				}
+			})
+		}
+		Expr::And(expr) =>
+		{
+			let parser = compile(expr, refs);
+			Rc::new(move |input: State<'a>|
+			{
+				parser(input.clone()).map(|_| Succeeded {new_state: input, value: (Vec::new(), Vec::new())})
+			})
+		}
+		Expr::Capture(name, expr) =>
+		{
+			let name = name.clone();
+			let parser = compile(expr, refs);
+			Rc::new(move |input: State<'a>|
+			{
+				if input.is_binary()
+				{
+					return Err(binary_failure(input));
+				}
+				parser(input.clone()).map(|pass|
+				{
+					let mut value = pass.value;
+					value.1.push((name.clone(), &input.text[input.index..pass.new_state.index]));
+					Succeeded {new_state: pass.new_state, value}
+				})
+			})
+		}
+	}
+}
+
+// Nodes and captures are slices of text so they can't be used with binary input.
+fn binary_failure(input: State<'_>) -> Failed<'_>
+{
+	Failed::message(input.clone(), input, "text input".to_string())
+}
+
+// Wraps a visible rule's body so that the action is called with a node.
+fn rule_parser<'a, T: 'a>(name: &str, body: Parser<'a, Collected<'a, T>>, action: Action<'a, T>) -> Parser<'a, Collected<'a, T>>
+{
+	let label = name.replace('_', " ");
+	let name = name.to_string();
+	let parser: Parser<'a, Collected<'a, T>> = Rc::new(move |input: State<'a>|
+	{
+		if input.is_binary()
+		{
+			return Err(binary_failure(input));
+		}
+		let pass = body(input.clone())?;
+		let (children, captures) = pass.value;
+		let span = Span {start: input.position(), end: pass.new_state.position()};
+		let node = Node {rule: name.clone(), text: &input.text[input.index..pass.new_state.index], span, children, captures};
+		match action(node)
+		{
+			Ok(value) => Ok(Succeeded {new_state: pass.new_state, value: (vec![value], Vec::new())}),
+			Err(mesg) => Err(Failed::message(input, pass.new_state, mesg)),
+		}
+	});
+	parser.err(&label)
+}
+
+// e{min,max} which, unlike r, stops matching once max is reached or e stops
+// making progress (so things like ('a'?)* don't loop forever).
+fn repeat<'a, T: 'a>(parser: Parser<'a, Collected<'a, T>>, min: usize, max: usize) -> Parser<'a, Collected<'a, T>>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let mut state = input.clone();
+		let mut collected = (Vec::new(), Vec::new());
+		let mut count = 0;
+		while count < max
+		{
+			match parser(state.clone())
+			{
+				Ok(pass) =>
+				{
+					append(&mut collected, pass.value);
+					count += 1;
+					if pass.new_state.index == state.index
+					{
+						break;
+					}
+					state = pass.new_state;
+				}
+				Err(failure) if count < min || failure.is_incomplete() =>
+				{
+					return Err(Failed {old_state: input, ..failure});
+				}
+				Err(_) =>
+				{
+					break;
+				}
+			}
+		}
+		Ok(Succeeded {new_state: state, value: collected})
+	})
+}
+
+fn discard<'a, T: 'a, U: 'a>(parser: Parser<'a, U>) -> Parser<'a, Collected<'a, T>>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		parser(input).map(|pass| Succeeded {new_state: pass.new_state, value: (Vec::new(), Vec::new())})
+	})
+}
+
+fn append<'a, T>(collected: &mut Collected<'a, T>, more: Collected<'a, T>)
+{
+	collected.0.extend(more.0);
+	collected.1.extend(more.1);
+}
+
+fn class_parser<'a>(class: Class) -> Parser<'a, char>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		match input.peek()
+		{
+			Some(ch) if class.matches(ch) => Ok(Succeeded {new_state: input.advance(ch.len_utf8()), value: ch}),
+			None if input.partial => incomplete(input),
+			_ => Err(Failed::new(input.clone(), input, class.label.clone())),
+		}
+	})
+}
+
+// ---- grammar parser ----------------------------------------------------------------------------
+#[derive(Clone, Debug)]
+struct Rule
+{
+	name: String,
+	pos: Position,
+	expr: Expr,
+}
+
+#[derive(Clone, Debug)]
+enum Expr
+{
+	Literal(String),
+	Class(Class),
+	Rule(String, Position),
+	Seq(Vec<Expr>),
+	Choice(Vec<Expr>),
+	Repeat(Box<Expr>, usize, usize),
+	Not(Box<Expr>),
+	And(Box<Expr>),
+	Capture(String, Box<Expr>),
+}
+
+// Also used for '.' (which is an empty negated class).
+#[derive(Clone, Debug)]
+struct Class
+{
+	ranges: Vec<(char, char)>,
+	negated: bool,
+	label: String,
+}
+
+impl Expr
+{
+	fn visit(&self, fun: &mut impl FnMut(&Expr))
+	{
+		fun(self);
+		match self
+		{
+			Expr::Seq(exprs) | Expr::Choice(exprs) => exprs.iter().for_each(|e| e.visit(fun)),
+			Expr::Repeat(expr, _, _) | Expr::Not(expr) | Expr::And(expr) | Expr::Capture(_, expr) => expr.visit(fun),
+			_ => (),
+		}
+	}
+}
+
+impl Class
+{
+	fn matches(&self, ch: char) -> bool
+	{
+		self.ranges.iter().any(|&(lo, hi)| lo <= ch && ch <= hi) != self.negated
+	}
+}
+
+// Returns the first rule which can call itself without consuming any input.
+fn left_recursive(rules: &[Rule]) -> Option<&Rule>
+{
+	let exprs: HashMap<&str, &Expr> = rules.iter().map(|rule| (rule.name.as_str(), &rule.expr)).collect();
+
+	// Find the rules which can succeed without consuming input (repeating until nothing changes).
+	let mut nullable = HashSet::new();
+	loop
+	{
+		let count = nullable.len();
+		for rule in rules.iter()
+		{
+			if is_nullable(&rule.expr, &nullable)
+			{
+				nullable.insert(rule.name.as_str());
+			}
+		}
+		if nullable.len() == count
+		{
+			break;
+		}
+	}
+
+	rules.iter().find(|rule|
+	{
+		let mut seen = HashSet::new();
+		let mut pending = Vec::new();
+		first_calls(&rule.expr, &nullable, &mut pending);
+		while let Some(name) = pending.pop()
+		{
+			if name == rule.name
+			{
+				return true;
+			}
+			if seen.insert(name)
+			{
+				first_calls(exprs[name], &nullable, &mut pending);
+			}
+		}
+		false
+	})
+}
+
+fn is_nullable(expr: &Expr, nullable: &HashSet<&str>) -> bool
+{
+	match expr
+	{
+		Expr::Literal(text) => text.is_empty(),
+		Expr::Class(_) => false,
+		Expr::Rule(name, _) => nullable.contains(name.as_str()),
+		Expr::Seq(exprs) => exprs.iter().all(|e| is_nullable(e, nullable)),
+		Expr::Choice(exprs) => exprs.iter().any(|e| is_nullable(e, nullable)),
+		Expr::Repeat(expr, min, _) => *min == 0 || is_nullable(expr, nullable),
+		Expr::Not(_) | Expr::And(_) => true,
+		Expr::Capture(_, expr) => is_nullable(expr, nullable),
+	}
+}
+
+// Adds the rules which expr may call before it consumes any input.
+fn first_calls<'e>(expr: &'e Expr, nullable: &HashSet<&str>, calls: &mut Vec<&'e str>)
+{
+	match expr
+	{
+		Expr::Literal(_) | Expr::Class(_) => (),
+		Expr::Rule(name, _) => calls.push(name),
+		Expr::Seq(exprs) =>
+		{
+			for e in exprs.iter()
+			{
+				first_calls(e, nullable, calls);
+				if !is_nullable(e, nullable)
+				{
+					break;
+				}
+			}
+		}
+		Expr::Choice(exprs) => exprs.iter().for_each(|e| first_calls(e, nullable, calls)),
+		Expr::Repeat(expr, _, _) | Expr::Not(expr) | Expr::And(expr) | Expr::Capture(_, expr) => first_calls(expr, nullable, calls),
+	}
+}
+
+fn grammar_error(file: &str, text: &str, pos: Position, len: usize, mesg: String) -> ParseFailed
+{
+	let index = LineIndex::new(text);
+	let unit_cols = [ColUnit::Utf8, ColUnit::Utf16, ColUnit::Chars].map(|unit| index.position(pos.offset, unit).map_or(pos.col, |p| p.col));
+	ParseFailed {file: file.to_string(), line: pos.line, col: pos.col, mesg, expected: Vec::new(), found: None, span: pos.offset..pos.offset + len, context: Vec::new(), tab_width: 1, unit_cols}
+}
+
+// grammar := rule+ EOT
+fn grammar_parser<'a>() -> Parser<'a, Vec<Rule>>
+{
+	let choice_ref = Rc::new(RefCell::new(fails("")));
+
+	// primary := literal | class | '.' | '(' choice ')' | name
+	let primary = alt!(
+		seq2(literal(), space(), |text, _| Ok(Expr::Literal(text))),
+		seq2(class(), space(), |class, _| Ok(Expr::Class(class))),
+		seq2(".".lit(), space(), |_, _| Ok(Expr::Class(Class {ranges: Vec::new(), negated: true, label: "any character".to_string()}))),
+		seq!(skip token("("), forward_ref(choice_ref.clone()), skip token(")")),
+		seq2(identifier().with_pos(), space(), |(name, pos), _| Ok(Expr::Rule(name.to_string(), pos)))
+	).err("expression");
+
+	// suffixed := primary ('*' | '+' | '?')?
+	let suffix = seq!("*+?".anyc(), skip space());
+	let suffixed = seq2(primary, suffix.optional(), |expr, suffix|
+	{
+		match suffix
+		{
+			Some('*') => Ok(Expr::Repeat(Box::new(expr), 0, usize::MAX)),
+			Some('+') => Ok(Expr::Repeat(Box::new(expr), 1, usize::MAX)),
+			Some(_) => Ok(Expr::Repeat(Box::new(expr), 0, 1)),
+			None => Ok(expr),
+		}
+	});
+
+	// item := (name ':' !'=')? suffixed
+	let capture = seq!(identifier(), skip ":".lit(), skip not_followed_by("=".lit()), skip space());
+	let item = seq2(capture.optional(), suffixed, |name, expr|
+	{
+		match name
+		{
+			Some(name) => Ok(Expr::Capture(name.to_string(), Box::new(expr))),
+			None => Ok(expr),
+		}
+	});
+
+	// prefixed := ('!' | '&')? item
+	let prefix = seq!("!&".anyc(), skip space());
+	let prefixed = seq2(prefix.optional(), item, |prefix, expr|
+	{
+		match prefix
+		{
+			Some('!') => Ok(Expr::Not(Box::new(expr))),
+			Some(_) => Ok(Expr::And(Box::new(expr))),
+			None => Ok(expr),
+		}
+	});
+
+	// sequence := prefixed+
+	// choice := sequence ('|' sequence)*
+	// These end at the tokens which can follow them (so that errors within an item are
+	// reported instead of silently ending the list).
+	let rule_start = ret_str(seq!(identifier(), skip space(), skip ":=".lit()));
+	let end = alt!(rule_start, ")".lit(), ret_str(eot()));
+	let sequence = seq2(prefixed.clone(), prefixed.many_till(followed_by(alt!(end.clone(), "|".lit()))), |first, mut rest|
+	{
+		if rest.is_empty() {Ok(first)} else {rest.insert(0, first); Ok(Expr::Seq(rest))}
+	});
+	*choice_ref.borrow_mut() = seq2(sequence.clone(), seq!(skip token("|"), sequence).many_till(followed_by(end)), |first, mut rest|
+	{
+		if rest.is_empty() {Ok(first)} else {rest.insert(0, first); Ok(Expr::Choice(rest))}
+	});
+
+	// rule := name ':=' choice
+	let rule = seq3(identifier().with_pos(), seq!(skip space(), skip token(":=")), forward_ref(choice_ref.clone()), |(name, pos), _, expr|
+	{
+		Ok(Rule {name: name.to_string(), pos, expr})
+	});
+
+	seq2(seq!(skip space(), rule.clone()), rule.many_till(eot()), |first, mut rest|
+	{
+		rest.insert(0, first);
+		Ok(rest)
+	})
+}
+
+// space := ([ \t\r\n] | '//' [^\n]*)*
+fn space<'a>() -> Parser<'a, &'a str>
+{
+	scan(|text, index|
+	{
+		let bytes = text.as_bytes();
+		let mut i = index;
+		while i < bytes.len()
+		{
+			if bytes[i] == b' ' || bytes[i] == b'\t' || bytes[i] == b'\r' || bytes[i] == b'\n'
+			{
+				i += 1;
+			}
+			else if text[i..].starts_with("//")
+			{
+				i += text[i..].find('\n').unwrap_or(text.len() - i);
+			}
+			else
+			{
+				break;
+			}
+		}
+		i - index
+	})
+}
+
+fn token<'a>(s: &str) -> Parser<'a, &'a str>
+{
+	seq!(s.lit(), skip space())
+}
+
+// Succeeds without consuming anything if parser succeeds.
+fn followed_by<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, ()>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		parser(input.clone()).map(|_| Succeeded {new_state: input, value: ()})
+	})
+}
+
+// Succeeds without consuming anything if parser fails.
+fn not_followed_by<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, ()>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		match parser(input.clone())
+		{
+			Ok(_) => Err(Failed::new(input.clone(), input, String::new())),
+			Err(_) => Ok(Succeeded {new_state: input, value: ()}),
+		}
+	})
+}
+
+// literal := "'" char* "'" | '"' char* '"'
+fn literal<'a>() -> Parser<'a, String>
+{
+	Rc::new(|input: State<'a>|
+	{
+		let quote = match input.peek()
+		{
+			Some(ch) if ch == '\'' || ch == '"' => ch,
+			_ => return Err(Failed::new(input.clone(), input, String::new())),
+		};
+
+		let mut value = String::new();
+		let mut chars = input.rest().char_indices().skip(1);
+		while let Some((i, ch)) = chars.next()
+		{
+			if ch == quote
+			{
+				return Ok(Succeeded {new_state: input.advance(i + 1), value});
+			}
+			else if ch == '\\'
+			{
+				match chars.next()
+				{
+					Some((_, ch)) => value.push(unescape(ch)),
+					None => break,
+				}
+			}
+			else
+			{
+				value.push(ch);
+			}
+		}
+		Err(Failed::new(input.clone(), input.advance(input.rest().len()), format!("closing {}", quote)))
+	})
+}
+
+// class := '[' '^'? (char ('-' char)?)* ']'
+fn class<'a>() -> Parser<'a, Class>
+{
+	Rc::new(|input: State<'a>|
+	{
+		let rest = input.rest();
+		if !rest.starts_with('[')
+		{
+			return Err(Failed::new(input.clone(), input, String::new()));
+		}
+
+		let negated = rest[1..].starts_with('^');
+		let mut chars = rest.char_indices().skip(if negated {2} else {1}).peekable();
+		let mut ranges = Vec::new();
+		while let Some((i, ch)) = chars.next()
+		{
+			let lo = match ch
+			{
+				']' => return Ok(Succeeded {new_state: input.advance(i + 1), value: Class {ranges, negated, label: rest[..i + 1].to_string()}}),
+				'\\' => match chars.next() {Some((_, ch)) => unescape(ch), None => break},
+				_ => ch,
+			};
+
+			let mut hi = lo;
+			if chars.peek().map(|c| c.1) == Some('-') && chars.clone().nth(1).is_some_and(|c| c.1 != ']')
+			{
+				chars.next();
+				hi = match chars.next()
+				{
+					Some((_, '\\')) => match chars.next() {Some((_, ch)) => unescape(ch), None => break},
+					Some((_, ch)) => ch,
+					None => break,
+				};
+				if hi < lo
+				{
+					return Err(Failed::message(input.clone(), input.advance(i), format!("bad range {}-{}", lo, hi)));
+				}
+			}
+			ranges.push((lo, hi));
+		}
+		Err(Failed::new(input.clone(), input.advance(rest.len()), "']'".to_string()))
+	})
+}
+
+fn unescape(ch: char) -> char
+{
+	match ch
+	{
+		'n' => '\n',
+		'r' => '\r',
+		't' => '\t',
+		'0' => '\0',
+		_ => ch,
+	}
+}
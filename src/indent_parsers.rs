@@ -0,0 +1,161 @@
+//! Parsers for languages where indentation is significant, e.g.
+//!
+//! ```
+//! # use std::cell::RefCell;
+//! # use std::rc::Rc;
+//! # use rparse::*;
+//! // entry := name ':' (number | block)
+//! // block := indented_block(entry)
+//! let entry_ptr = Rc::new(RefCell::new(fails("")));
+//! let block = indented_block(forward_ref(entry_ptr.clone())).thene(|entries| ret(entries.len() as i64));
+//! let entry = seq!(identifier(), skip ":".s0(), decimal_number().s0().or(block));
+//! *entry_ptr.borrow_mut() = entry.clone();
+//!
+//! let text = "server:\n  port: 80\n  limits:\n    max: 10\n  threads: 4\n";
+//! assert_eq!(entry.parse("test", text), Ok(("server", 3)));
+//! ```
+//!
+//! State.indent is the column of the innermost block (or line fold). Within a block s0
+//! and s1 won't skip onto a line that starts at or before that column. So the parsers
+//! for an item stop at the end of the item, but items can continue onto lines that are
+//! indented past the start of the item.
+use std::rc::Rc;
+
+use crate::parsers::*;
+use crate::types::*;
+
+/// block := item+ space
+///
+/// Each item must start on its own line at the same column and that column must be past
+/// the column of the enclosing block. The block ends at the end of the input or at a line
+/// which is indented less than the items.
+pub fn indented_block<'a, T: 'a>(item: Parser<'a, T>) -> Parser<'a, Vec<T>>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let mut start = next_token(input.clone());
+		if input.partial && start.at_end()
+		{
+			return incomplete(input);
+		}
+		if start.at_end() || !starts_line(start.clone()) || start.col <= input.indent
+		{
+			return Err(Failed::new(input, start, "indented block".to_string()));
+		}
+
+		let col = start.col;
+		let mut values = Vec::new();
+		loop
+		{
+			let pass = item(State {indent: col, ..start}).map_err(|failure| Failed {old_state: input.clone(), ..failure})?;
+			values.push(pass.value);
+
+			let end = State {indent: input.indent, ..pass.new_state};
+			let next = next_token(end.clone());
+			if input.partial && next.at_end()
+			{
+				return incomplete(input);
+			}
+			if next.at_end() || !starts_line(next.clone()) || next.line == start.line || next.col < col
+			{
+				return Ok(Succeeded {new_state: end.skip_space(), value: values});
+			}
+			if next.col > col
+			{
+				return Err(Failed::message(input, next, "unexpected indentation".to_string()));
+			}
+			start = next;
+		}
+	})
+}
+
+/// same_indent := space e
+///
+/// Succeeds if parser matches text which starts a line at the column of the current
+/// block (or column 1 if there is no block).
+pub fn same_indent<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, T>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let start = next_token(input.clone());
+		if starts_line(start.clone()) && !start.at_end() && start.col == input.indent.max(1)
+		{
+			parser(start).map_err(|failure| Failed {old_state: input.clone(), ..failure})
+		}
+		else
+		{
+			Err(Failed::new(input, start, "same indentation".to_string()))
+		}
+	})
+}
+
+/// indent := space
+///
+/// Succeeds if the next token starts a line at a column past the column of the current
+/// block. Returns the column.
+pub fn indent<'a>() -> Parser<'a, usize>
+{
+	Rc::new(|input: State<'a>|
+	{
+		let start = next_token(input.clone());
+		if starts_line(start.clone()) && !start.at_end() && start.col > input.indent.max(1)
+		{
+			Ok(Succeeded {new_state: start.clone(), value: start.col})
+		}
+		else
+		{
+			Err(Failed::new(input, start, "indentation".to_string()))
+		}
+	})
+}
+
+/// dedent := space
+///
+/// Succeeds if the rest of the input is white space or the next token starts a line
+/// at a column before the column of the current block.
+pub fn dedent<'a>() -> Parser<'a, ()>
+{
+	Rc::new(|input: State<'a>|
+	{
+		let start = next_token(input.clone());
+		if input.partial && start.at_end()
+		{
+			incomplete(input)
+		}
+		else if start.at_end() || (starts_line(start.clone()) && start.col < input.indent)
+		{
+			Ok(Succeeded {new_state: start, value: ()})
+		}
+		else
+		{
+			Err(Failed::new(input, start, "dedent".to_string()))
+		}
+	})
+}
+
+/// line_fold := e space
+///
+/// Parser may continue onto lines that are indented past the column where it started.
+pub fn line_fold<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, T>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let pass = parser(State {indent: input.col, ..input.clone()}).map_err(|failure| Failed {old_state: input.clone(), ..failure})?;
+		let new_state = State {indent: input.indent, ..pass.new_state}.skip_space();
+		Ok(Succeeded {new_state, value: pass.value})
+	})
+}
+
+// Returns the state at the next token ignoring the current block.
+fn next_token(input: State) -> State
+{
+	State {indent: input.indent, ..(input.skipper.0)(input.clone())}
+}
+
+// Returns true if only spaces and tabs precede input on its line.
+fn starts_line(input: State) -> bool
+{
+	let before = &input.text[..input.index.min(input.text.len())];
+	let line = before.rfind(['\r', '\n']).map_or(before, |i| &before[i + 1..]);
+	line.bytes().all(|b| b == b' ' || b == b'\t')
+}
@@ -0,0 +1,70 @@
+//! General purpose parser combinator library.
+// If the version is changed also change the versions in Cargo.toml and rparse_derive/Cargo.toml.
+#![forbid(unused_imports)]
+#![allow(clippy::needless_return)]				// early returns read better in the helper functions
+#![allow(clippy::manual_range_contains)]		// we prefer ch >= 'a' && ch <= 'z' to ('a'..='z').contains(&ch)
+#![allow(clippy::result_large_err)]				// boxing Failed would add an allocation to every failed parse
+
+#[macro_use]
+extern crate log;
+
+// Lets the code generated by derive(Parse) refer to ::rparse within this crate too.
+extern crate self as rparse;
+
+pub use crate::byte_parsers::*;
+pub use crate::c99_parsers::*;
+pub use crate::c99_preprocessor::*;
+pub use crate::cst::*;
+pub use crate::derive::*;
+pub use crate::grammar::*;
+pub use crate::indent_parsers::*;
+pub use crate::line_index::*;
+pub use crate::misc::*;
+pub use crate::number_parsers::*;
+pub use crate::operator_parsers::*;
+pub use crate::parsers::*;
+pub use crate::render::*;
+pub use crate::types::*;
+pub use rparse_derive::Parse;
+
+pub mod byte_parsers;
+pub mod c99_parsers;
+pub mod c99_preprocessor;
+pub mod cst;
+pub mod derive;
+pub mod grammar;
+pub mod indent_parsers;
+pub mod line_index;
+pub mod macros;
+pub mod misc;
+pub mod number_parsers;
+pub mod operator_parsers;
+pub mod parsers;
+pub mod render;
+pub mod types;
+
+#[cfg(test)]
+mod tests
+{
+	mod byte_tests;
+	mod c99_tests;
+	mod char_tests;
+	mod cst_tests;
+	mod derive_tests;
+	mod error_tests;
+	mod generic_tests;
+	mod grammar_tests;
+	mod indent_tests;
+	mod line_index_tests;
+	mod number_tests;
+	mod operator_tests;
+	mod preprocessor_tests;
+	mod recover_tests;
+	mod render_tests;
+	mod stream_tests;
+	mod span_tests;
+	mod string_tests;
+	mod test_helpers;
+	mod test_expr;
+	mod test_xml;
+}
@@ -0,0 +1,179 @@
+//! Converts between byte offsets and line/column positions.
+//!
+//! Parsers track the line and column as they go, but tools like editors need to
+//! map arbitrary offsets (and columns measured in UTF-16 code units) back and forth.
+//! The parse functions use a LineIndex to compute the col of errors in each unit (see
+//! ParseFailed::position). Use parse_indexed to share one index between parsing, error
+//! rendering, and converting spans.
+use std::ops::Range;
+
+use crate::types::*;
+
+/// How columns are measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColUnit
+{
+	/// Bytes, i.e. UTF-8 code units.
+	Utf8,
+
+	/// UTF-16 code units (this is what the Language Server Protocol uses by default).
+	Utf16,
+
+	/// Unicode scalar values (this is what State uses by default).
+	Chars,
+}
+
+/// Table of line start offsets for a text. Lines and columns are 1-based and "\r\n",
+/// "\r", and "\n" each end a line.
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a>
+{
+	text: &'a str,
+	starts: Vec<usize>,		// byte offset of the start of each line
+	ascii: Vec<bool>,		// true if the line has only ASCII characters
+}
+
+impl<'a> LineIndex<'a>
+{
+	/// Builds the table in one pass over text.
+	pub fn new(text: &'a str) -> LineIndex<'a>
+	{
+		let bytes = text.as_bytes();
+		let mut starts = vec![0];
+		let mut ascii = vec![true];
+		let mut i = 0;
+		while i < bytes.len()
+		{
+			match bytes[i]
+			{
+				b'\r' if bytes.get(i + 1) == Some(&b'\n') =>
+				{
+					i += 1;
+					starts.push(i + 1);
+					ascii.push(true);
+				}
+				b'\r' | b'\n' =>
+				{
+					starts.push(i + 1);
+					ascii.push(true);
+				}
+				b if b >= 0x80 =>
+				{
+					*ascii.last_mut().unwrap() = false;
+				}
+				_ =>
+				{
+				}
+			}
+			i += 1;
+		}
+		LineIndex {text, starts, ascii}
+	}
+
+	/// Returns the text the table was built from.
+	pub fn text(&self) -> &'a str
+	{
+		self.text
+	}
+
+	/// Returns the number of lines. This is always at least one.
+	pub fn line_count(&self) -> usize
+	{
+		self.starts.len()
+	}
+
+	/// Returns the text of line without the line terminator.
+	pub fn line(&self, line: usize) -> Option<&'a str>
+	{
+		if line == 0 || line > self.starts.len()
+		{
+			return None;
+		}
+
+		let start = self.starts[line - 1];
+		let end = self.line_end(line - 1);
+		Some(&self.text[start..end])
+	}
+
+	/// Returns the position of offset or None if offset is past the end of the text
+	/// or not on a character boundary.
+	pub fn position(&self, offset: usize, unit: ColUnit) -> Option<Position>
+	{
+		if offset > self.text.len() || !self.text.is_char_boundary(offset)
+		{
+			return None;
+		}
+
+		// Offsets within a line terminator are treated as the end of the line.
+		let i = self.starts.partition_point(|&start| start <= offset) - 1;
+		let start = self.starts[i];
+		let offset = offset.min(self.line_end(i));
+		let col = if self.ascii[i]
+		{
+			offset - start + 1
+		}
+		else
+		{
+			let prefix = &self.text[start..offset];
+			match unit
+			{
+				ColUnit::Utf8 => prefix.len() + 1,
+				ColUnit::Utf16 => prefix.encode_utf16().count() + 1,
+				ColUnit::Chars => prefix.chars().count() + 1,
+			}
+		};
+		Some(Position {offset, line: i + 1, col})
+	}
+
+	/// Returns the byte offset of line and col or None if they are not within the text.
+	/// Cols just past the end of a line are allowed. Also see position.
+	pub fn offset(&self, line: usize, col: usize, unit: ColUnit) -> Option<usize>
+	{
+		let text = self.line(line)?;
+		let start = self.starts[line - 1];
+		if col == 0
+		{
+			return None;
+		}
+		if self.ascii[line - 1]
+		{
+			return if col - 1 <= text.len() {Some(start + col - 1)} else {None};
+		}
+
+		let mut count = 0;
+		for (i, ch) in text.char_indices().chain(std::iter::once((text.len(), ' ')))
+		{
+			if count == col - 1
+			{
+				return Some(start + i);
+			}
+			else if count > col - 1
+			{
+				return None;		// in the middle of a character
+			}
+			count += match unit
+			{
+				ColUnit::Utf8 => ch.len_utf8(),
+				ColUnit::Utf16 => ch.len_utf16(),
+				ColUnit::Chars => 1,
+			};
+		}
+		None
+	}
+
+	/// Returns the span of the bytes within range.
+	pub fn span(&self, range: Range<usize>, unit: ColUnit) -> Option<Span>
+	{
+		Some(Span {start: self.position(range.start, unit)?, end: self.position(range.end, unit)?})
+	}
+
+	fn line_end(&self, i: usize) -> usize
+	{
+		match self.starts.get(i + 1)
+		{
+			Some(&next) if self.text[..next].ends_with("\r\n") => next - 2,
+			Some(&next) => next - 1,
+			None => self.text.len(),
+		}
+	}
+}
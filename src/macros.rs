@@ -0,0 +1,87 @@
+//! Macros for sequences and alternatives with any number of parsers.
+//!
+//! ```
+//! # use rparse::*;
+//! // call := identifier '(' identifier ')'
+//! let call = seq!(identifier().s0(), skip "(".s0(), identifier().s0(), skip ")".s0());
+//! assert_eq!(call.parse("test", "f (x)"), Ok(("f", "x")));
+//!
+//! let keyword = alt!("if".lit(), "else".lit(), "while".lit());
+//! assert_eq!(keyword.parse("test", "else"), Ok("else"));
+//! ```
+
+/// seq := e0 e1 ... eN
+///
+/// Returns a tuple with the values of each parser. Parsers prefixed with skip
+/// are matched, but their values are not returned. If only one value is returned
+/// it is not wrapped in a tuple and if none are returned the value is ().
+#[macro_export]
+macro_rules! seq
+{
+	($($elements:tt)+) => {$crate::__seq!(@parse [] $($elements)+)};
+}
+
+/// seq_str := e0 e1 ... eN
+///
+/// Like seq except that the matched text is returned.
+#[macro_export]
+macro_rules! seq_str
+{
+	($($elements:tt)+) => {$crate::parsers::ret_str($crate::__seq!(@parse [] $($elements)+))};
+}
+
+/// alt := e0 | e1 | ... | eN
+///
+/// Returns the value of the first parser which succeeds. This is the same as or_v.
+#[macro_export]
+macro_rules! alt
+{
+	($($parsers:expr),+ $(,)?) => {$crate::parsers::or_v(vec![$($parsers),+])};
+}
+
+// Parses the elements into a list of (keep|skip parser) and then binds each parser
+// to a (hygienic) variable which is captured by a closure that calls them in order.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __seq
+{
+	(@parse [$($acc:tt)*] skip $e:expr, $($rest:tt)*) => {$crate::__seq!(@parse [$($acc)* (skip $e)] $($rest)*)};
+	(@parse [$($acc:tt)*] skip $e:expr) => {$crate::__seq!(@bind [] [$($acc)* (skip $e)])};
+	(@parse [$($acc:tt)*] $e:expr, $($rest:tt)*) => {$crate::__seq!(@parse [$($acc)* (keep $e)] $($rest)*)};
+	(@parse [$($acc:tt)*] $e:expr) => {$crate::__seq!(@bind [] [$($acc)* (keep $e)])};
+	(@parse [$($acc:tt)*]) => {$crate::__seq!(@bind [] [$($acc)*])};
+
+	(@bind [$($bound:tt)*] [($kind:ident $e:expr) $($rest:tt)*]) =>
+	{{
+		let parser = $e;
+		$crate::__seq!(@bind [$($bound)* ($kind parser)] [$($rest)*])
+	}};
+	(@bind [$($bound:tt)*] []) =>
+	{
+		$crate::parsers::to_parser(move |input| $crate::__seq!(@run input, input.clone(), [] $($bound)*))
+	};
+
+	(@run $input:ident, $state:expr, [$($values:ident)*] (keep $parser:ident) $($rest:tt)*) =>
+	{{
+		let pass = $parser($state).map_err(|failure| $crate::types::Failed {old_state: $input.clone(), ..failure})?;
+		let value = pass.value;
+		$crate::__seq!(@run $input, pass.new_state, [$($values)* value] $($rest)*)
+	}};
+	(@run $input:ident, $state:expr, [$($values:ident)*] (skip $parser:ident) $($rest:tt)*) =>
+	{{
+		let pass = $parser($state).map_err(|failure| $crate::types::Failed {old_state: $input.clone(), ..failure})?;
+		$crate::__seq!(@run $input, pass.new_state, [$($values)*] $($rest)*)
+	}};
+	(@run $input:ident, $state:expr, []) =>
+	{
+		Ok($crate::types::Succeeded {new_state: $state, value: ()})
+	};
+	(@run $input:ident, $state:expr, [$value:ident]) =>
+	{
+		Ok($crate::types::Succeeded {new_state: $state, value: $value})
+	};
+	(@run $input:ident, $state:expr, [$($values:ident)+]) =>
+	{
+		Ok($crate::types::Succeeded {new_state: $state, value: ($($values),+)})
+	};
+}
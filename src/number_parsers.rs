@@ -0,0 +1,197 @@
+//! Parsers for integers of any width in bases 2 through 36, e.g.
+//!
+//! ```
+//! # use rparse::*;
+//! let p = integer::<u16>(NumberFormat::new(16).with_separators());
+//! assert_eq!(p.parse("test", "ff_ff"), Ok(0xFFFF));
+//!
+//! let err = p.parse("test", "1_0000").unwrap_err();
+//! assert_eq!(err.to_string(), "test:1:6: '1_0000' is out of range for u16");
+//! ```
+//!
+//! Overflow is detected exactly: parsing succeeds whenever the value fits in the type
+//! (including MIN for signed types) and otherwise the error points at the first digit that
+//! didn't fit. With the bigint feature BigInt and BigUint can also be used.
+use std::any::type_name;
+use std::rc::Rc;
+
+use crate::parsers::*;
+use crate::types::*;
+
+/// Types that integer can return.
+pub trait Integer: Clone + 'static
+{
+	/// Returns zero.
+	fn zero() -> Self;
+
+	/// Returns self * base + digit or None if the result doesn't fit.
+	fn append_digit(self, base: u32, digit: u32) -> Option<Self>;
+
+	/// Returns self * base - digit or None if the result doesn't fit. Negative numbers
+	/// are built up this way so that MIN can be parsed.
+	fn append_negative_digit(self, base: u32, digit: u32) -> Option<Self>;
+}
+
+macro_rules! primitive_integer
+{
+	($($name:ty)+) =>
+	{
+		$(
+			impl Integer for $name
+			{
+				fn zero() -> Self
+				{
+					0
+				}
+
+				fn append_digit(self, base: u32, digit: u32) -> Option<Self>
+				{
+					self.checked_mul(<$name>::try_from(base).ok()?)?.checked_add(<$name>::try_from(digit).ok()?)
+				}
+
+				fn append_negative_digit(self, base: u32, digit: u32) -> Option<Self>
+				{
+					self.checked_mul(<$name>::try_from(base).ok()?)?.checked_sub(<$name>::try_from(digit).ok()?)
+				}
+			}
+		)+
+	};
+}
+
+primitive_integer!(i8 i16 i32 i64 i128 isize);
+primitive_integer!(u8 u16 u32 u64 u128 usize);
+
+#[cfg(feature = "bigint")]
+impl Integer for num_bigint::BigInt
+{
+	fn zero() -> Self
+	{
+		num_bigint::BigInt::from(0)
+	}
+
+	fn append_digit(self, base: u32, digit: u32) -> Option<Self>
+	{
+		Some(self * base + digit)
+	}
+
+	fn append_negative_digit(self, base: u32, digit: u32) -> Option<Self>
+	{
+		Some(self * base - digit)
+	}
+}
+
+#[cfg(feature = "bigint")]
+impl Integer for num_bigint::BigUint
+{
+	fn zero() -> Self
+	{
+		num_bigint::BigUint::from(0u32)
+	}
+
+	fn append_digit(self, base: u32, digit: u32) -> Option<Self>
+	{
+		Some(self * base + digit)
+	}
+
+	fn append_negative_digit(self, _base: u32, digit: u32) -> Option<Self>
+	{
+		// Only -0 fits.
+		if digit == 0 && self == Self::zero() {Some(self)} else {None}
+	}
+}
+
+/// The syntax used by integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberFormat {pub base: u32, pub sign: bool, pub separators: bool}
+
+impl NumberFormat
+{
+	/// Digits in base without a sign or separators. Panics if base isn't within 2..=36.
+	pub fn new(base: u32) -> NumberFormat
+	{
+		assert!(base >= 2 && base <= 36, "base {} is not within 2..=36", base);
+		NumberFormat {base, sign: false, separators: false}
+	}
+
+	/// Allows a leading + or -.
+	pub fn with_sign(self) -> NumberFormat
+	{
+		NumberFormat {sign: true, ..self}
+	}
+
+	/// Allows underscores between digits, e.g. `1_000_000`.
+	pub fn with_separators(self) -> NumberFormat
+	{
+		NumberFormat {separators: true, ..self}
+	}
+}
+
+/// integer := [+-]? digit ('_'? digit)*
+///
+/// The sign and separators are only allowed if format enables them. Digits after 9 are
+/// letters of either case. Parsing stops at the first character that isn't a digit in
+/// the base, e.g. in base 8 `129` parses as 12.
+pub fn integer<'a, T: Integer>(format: NumberFormat) -> Parser<'a, T>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let bytes = input.rest().as_bytes();
+		let digit = |i: usize| bytes.get(i).and_then(|&b| (b as char).to_digit(format.base));
+
+		let signed = format.sign && matches!(bytes.first(), Some(b'+' | b'-'));
+		let negative = signed && bytes[0] == b'-';
+		let start = if signed {1} else {0};
+
+		let mut value = Some(T::zero());
+		let mut overflow = 0;
+		let mut i = start;
+		loop
+		{
+			if input.partial && i == bytes.len()
+			{
+				return incomplete(input);
+			}
+			if let Some(d) = digit(i)
+			{
+				if let Some(v) = value.take()
+				{
+					value = if negative {v.append_negative_digit(format.base, d)} else {v.append_digit(format.base, d)};
+					overflow = i;
+				}
+				i += 1;
+			}
+			else if format.separators && bytes.get(i) == Some(&b'_') && i > start
+			{
+				if input.partial && i + 1 == bytes.len()
+				{
+					return incomplete(input);
+				}
+				if digit(i + 1).is_none()
+				{
+					return Err(Failed::message(input.clone(), input.advance(i), "'_' must be between digits".to_string()));
+				}
+				i += 1;
+			}
+			else
+			{
+				break;
+			}
+		}
+
+		if i == start
+		{
+			// Like match1 there's no label unless a sign was parsed (callers will normally use err).
+			let expected = if signed {"digit"} else {""};
+			return Err(Failed::new(input.clone(), input.advance(start), expected.to_string()));
+		}
+		match value
+		{
+			Some(value) => Ok(Succeeded {new_state: input.advance(i), value}),
+			None =>
+			{
+				let mesg = format!("'{}' is out of range for {}", &input.rest()[..i], type_name::<T>());
+				Err(Failed::message(input.clone(), input.advance(overflow), mesg))
+			}
+		}
+	})
+}
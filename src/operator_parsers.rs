@@ -0,0 +1,367 @@
+//! Builds expression parsers from a table of operators.
+//!
+//! This is a precedence climbing (aka Pratt) parser. Operators have a precedence
+//! where larger values bind more tightly, e.g.
+//!
+//! ```
+//! # use rparse::*;
+//! let expr = OperatorTable::new()
+//!     .infix("+".s0(), 1, Assoc::Left, |lhs, rhs| lhs + rhs)
+//!     .infix("*".s0(), 2, Assoc::Left, |lhs, rhs| lhs * rhs)
+//!     .prefix("-".s0(), 3, |value: i64| -value)
+//!     .build(decimal_number().s0());
+//!
+//! assert_eq!(expr.parse("test", "2 + -3*4"), Ok(-10));
+//! ```
+//!
+//! If an operator matches, but the operand after it does not, then the other
+//! operators are tried. If none of them work the operand's error is returned (merged
+//! like [`or`](crate::parsers::Combinators::or) does) so that it points at the bad
+//! operand instead of at the operator.
+use std::rc::Rc;
+
+use crate::parsers::*;
+use crate::types::*;
+
+/// Associativity of an infix operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Assoc
+{
+	/// a - b - c is (a - b) - c
+	Left,
+
+	/// a ^ b ^ c is a ^ (b ^ c)
+	Right,
+
+	/// a < b < c is an error
+	None,
+}
+
+/// Operators used to build an expression parser. See the module docs.
+pub struct OperatorTable<'a, T>
+{
+	operators: Vec<Operator<'a, T>>,
+}
+
+impl<'a, T: 'a> Default for OperatorTable<'a, T>
+{
+	fn default() -> Self
+	{
+		Self::new()
+	}
+}
+
+impl<'a, T: 'a> OperatorTable<'a, T>
+{
+	/// Returns an empty table. Note that operators are tried in the order in which they
+	/// are added so, for example, "**" should be added before "*".
+	pub fn new() -> OperatorTable<'a, T>
+	{
+		OperatorTable {operators: Vec::new()}
+	}
+
+	/// prefix := op e
+	pub fn prefix<U: 'a>(mut self, op: Parser<'a, U>, prec: u32, eval: impl Fn(T) -> T + 'a) -> Self
+	{
+		self.operators.push(Operator {op: unit(op), prec, kind: Kind::Prefix(Rc::new(eval))});
+		self
+	}
+
+	/// infix := e op e
+	pub fn infix<U: 'a>(mut self, op: Parser<'a, U>, prec: u32, assoc: Assoc, eval: impl Fn(T, T) -> T + 'a) -> Self
+	{
+		self.operators.push(Operator {op: unit(op), prec, kind: Kind::Infix(assoc, Rc::new(eval))});
+		self
+	}
+
+	/// postfix := e op
+	pub fn postfix<U: 'a>(mut self, op: Parser<'a, U>, prec: u32, eval: impl Fn(T) -> T + 'a) -> Self
+	{
+		self.operators.push(Operator {op: unit(op), prec, kind: Kind::Postfix(Rc::new(eval))});
+		self
+	}
+
+	/// call := e open (expr (sep expr)*)? close
+	///
+	/// Function calls, e.g. f(1, 2). Eval is passed the callee and the arguments.
+	pub fn call<U: 'a, V: 'a, W: 'a>(mut self, open: Parser<'a, U>, sep: Parser<'a, V>, close: Parser<'a, W>, prec: u32, eval: impl Fn(T, Vec<T>) -> T + 'a) -> Self
+	{
+		self.operators.push(Operator {op: unit(open), prec, kind: Kind::Call(unit(sep), unit(close), Rc::new(eval))});
+		self
+	}
+
+	/// index := e open expr close
+	///
+	/// Indexing, e.g. `a[i]`. Eval is passed the indexed value and the index.
+	pub fn index<U: 'a, V: 'a>(mut self, open: Parser<'a, U>, close: Parser<'a, V>, prec: u32, eval: impl Fn(T, T) -> T + 'a) -> Self
+	{
+		self.operators.push(Operator {op: unit(open), prec, kind: Kind::Index(unit(close), Rc::new(eval))});
+		self
+	}
+
+	/// ternary := e question expr colon e
+	///
+	/// Conditional operators, e.g. c ? a : b. These are right associative.
+	pub fn ternary<U: 'a, V: 'a>(mut self, question: Parser<'a, U>, colon: Parser<'a, V>, prec: u32, eval: impl Fn(T, T, T) -> T + 'a) -> Self
+	{
+		self.operators.push(Operator {op: unit(question), prec, kind: Kind::Ternary(unit(colon), Rc::new(eval))});
+		self
+	}
+
+	/// Returns a parser for expressions using the table's operators where the
+	/// operands are parsed using primary.
+	///
+	/// Primary will often use a forward_ref to the returned parser in order to
+	/// support parenthesized expressions.
+	pub fn build(self, primary: Parser<'a, T>) -> Parser<'a, T>
+	{
+		let table = Rc::new(self);
+		Rc::new(move |input: State<'a>| parse_expr(&table, &primary, input, 0))
+	}
+}
+
+// ---- private items -----------------------------------------------------------------------------
+type Eval1<'a, T> = Rc<dyn Fn(T) -> T + 'a>;
+type Eval2<'a, T> = Rc<dyn Fn(T, T) -> T + 'a>;
+
+enum Kind<'a, T>
+{
+	Prefix(Eval1<'a, T>),
+	Infix(Assoc, Eval2<'a, T>),
+	Postfix(Eval1<'a, T>),
+	Call(Parser<'a, ()>, Parser<'a, ()>, Rc<dyn Fn(T, Vec<T>) -> T + 'a>),
+	Index(Parser<'a, ()>, Eval2<'a, T>),
+	Ternary(Parser<'a, ()>, Rc<dyn Fn(T, T, T) -> T + 'a>),
+}
+
+struct Operator<'a, T>
+{
+	op: Parser<'a, ()>,
+	prec: u32,
+	kind: Kind<'a, T>,
+}
+
+fn unit<'a, U: 'a>(parser: Parser<'a, U>) -> Parser<'a, ()>
+{
+	parser.then(ret(()))
+}
+
+// Operators which fail are normally ignored, but we can't ignore partial input.
+fn incomplete_op<'a, T>(input: State<'a>, status: &Status<'a, ()>) -> Option<Status<'a, T>>
+{
+	match status
+	{
+		Err(failure) if failure.is_incomplete() => Some(Err(Failed {old_state: input, ..failure.clone()})),
+		_ => None,
+	}
+}
+
+// expr := prefix* primary (postfix | infix e | ternary)*
+// where only operators with a precedence at least min_prec are used. Min_prec is a u64
+// so that it can be one more than any precedence.
+fn parse_expr<'a, T: 'a>(table: &OperatorTable<'a, T>, primary: &Parser<'a, T>, input: State<'a>, min_prec: u64) -> Status<'a, T>
+{
+	let (mut lhs, mut state) = match parse_prefix(table, primary, input.clone())
+	{
+		Ok(pass) => (pass.value, pass.new_state),
+		Err(failure) => return Err(failure),
+	};
+
+	let mut non_assoc = None;
+	'outer: loop
+	{
+		let mut failed = None;
+		for operator in table.operators.iter().filter(|o| u64::from(o.prec) >= min_prec && !matches!(o.kind, Kind::Prefix(_)))
+		{
+			let status = (operator.op)(state.clone());
+			if let Some(status) = incomplete_op(input.clone(), &status)
+			{
+				return status;
+			}
+			let after = match status
+			{
+				Ok(pass) => pass.new_state,
+				Err(_) => continue,
+			};
+
+			match &operator.kind
+			{
+				Kind::Prefix(_) =>
+				{
+					unreachable!();
+				}
+				Kind::Infix(assoc, eval) =>
+				{
+					if *assoc == Assoc::None && non_assoc == Some(operator.prec)
+					{
+						return Err(Failed::message(input, state, "non-associative operator".to_string()));
+					}
+
+					let next_prec = u64::from(operator.prec) + if *assoc == Assoc::Right {0} else {1};
+					match parse_expr(table, primary, after, next_prec)
+					{
+						Ok(pass) =>
+						{
+							lhs = eval(lhs, pass.value);
+							state = pass.new_state;
+							non_assoc = if *assoc == Assoc::None {Some(operator.prec)} else {None};
+							continue 'outer;
+						}
+						Err(failure) if failure.is_incomplete() => return Err(Failed {old_state: input, ..failure}),
+						Err(failure) =>
+						{
+							failed = merge_operand(failed, failure);
+							continue;				// try the other operators
+						}
+					}
+				}
+				Kind::Postfix(eval) =>
+				{
+					lhs = eval(lhs);
+					state = after;
+					continue 'outer;
+				}
+				Kind::Call(sep, close, eval) =>
+				{
+					match parse_args(table, primary, sep, close, after)
+					{
+						Ok(pass) =>
+						{
+							lhs = eval(lhs, pass.value);
+							state = pass.new_state;
+							continue 'outer;
+						}
+						Err(failure) if failure.is_incomplete() => return Err(Failed {old_state: input, ..failure}),
+						Err(failure) =>
+						{
+							failed = merge_operand(failed, failure);
+							continue;				// try the other operators
+						}
+					}
+				}
+				Kind::Index(close, eval) =>
+				{
+					match parse_expr(table, primary, after, 0).and_then(|pass| close(pass.new_state).map(|pass2| Succeeded {new_state: pass2.new_state, value: pass.value}))
+					{
+						Ok(pass) =>
+						{
+							lhs = eval(lhs, pass.value);
+							state = pass.new_state;
+							continue 'outer;
+						}
+						Err(failure) if failure.is_incomplete() => return Err(Failed {old_state: input, ..failure}),
+						Err(failure) =>
+						{
+							failed = merge_operand(failed, failure);
+							continue;				// try the other operators
+						}
+					}
+				}
+				Kind::Ternary(colon, eval) =>
+				{
+					let then = parse_expr(table, primary, after, 0);
+					let result = then.and_then(|pass| colon(pass.new_state).map(|pass2| Succeeded {new_state: pass2.new_state, value: pass.value}))
+						.and_then(|pass| parse_expr(table, primary, pass.new_state, u64::from(operator.prec)).map(|pass2| Succeeded {new_state: pass2.new_state, value: (pass.value, pass2.value)}));
+					match result
+					{
+						Ok(pass) =>
+						{
+							let (when_true, when_false) = pass.value;
+							lhs = eval(lhs, when_true, when_false);
+							state = pass.new_state;
+							continue 'outer;
+						}
+						Err(failure) if failure.is_incomplete() => return Err(Failed {old_state: input, ..failure}),
+						Err(failure) =>
+						{
+							failed = merge_operand(failed, failure);
+							continue;				// try the other operators
+						}
+					}
+				}
+			}
+		}
+		if let Some(failure) = failed
+		{
+			return Err(Failed {old_state: input, ..failure});
+		}
+		break;
+	}
+
+	Ok(Succeeded {new_state: state, value: lhs})
+}
+
+fn merge_operand<'a>(failed: Option<Failed<'a>>, failure: Failed<'a>) -> Option<Failed<'a>>
+{
+	match failed
+	{
+		Some(previous) => Some(merge_failures(previous, failure)),
+		None => Some(failure),
+	}
+}
+
+// prefix := op prefix | primary
+fn parse_prefix<'a, T: 'a>(table: &OperatorTable<'a, T>, primary: &Parser<'a, T>, input: State<'a>) -> Status<'a, T>
+{
+	for operator in table.operators.iter()
+	{
+		if let Kind::Prefix(eval) = &operator.kind
+		{
+			let status = (operator.op)(input.clone());
+			if let Some(status) = incomplete_op(input.clone(), &status)
+			{
+				return status;
+			}
+			if let Ok(pass) = status
+			{
+				// The operand includes operators which bind at least as tightly as we do.
+				return match parse_expr(table, primary, pass.new_state, u64::from(operator.prec))
+				{
+					Ok(pass2) => Ok(Succeeded {new_state: pass2.new_state, value: eval(pass2.value)}),
+					Err(failure) => Err(Failed {old_state: input, ..failure}),
+				};
+			}
+		}
+	}
+
+	primary(input)
+}
+
+// args := (expr (sep expr)*)? close
+fn parse_args<'a, T: 'a>(table: &OperatorTable<'a, T>, primary: &Parser<'a, T>, sep: &Parser<'a, ()>, close: &Parser<'a, ()>, input: State<'a>) -> Status<'a, Vec<T>>
+{
+	if let Ok(pass) = close(input.clone())
+	{
+		return Ok(Succeeded {new_state: pass.new_state, value: Vec::new()});
+	}
+
+	let mut values = Vec::new();
+	let mut state = input.clone();
+	loop
+	{
+		match parse_expr(table, primary, state, 0)
+		{
+			Ok(pass) =>
+			{
+				values.push(pass.value);
+				state = pass.new_state;
+			}
+			Err(failure) =>
+			{
+				return Err(Failed {old_state: input, ..failure});
+			}
+		}
+
+		let close_failure = match close(state.clone())
+		{
+			Ok(pass) => return Ok(Succeeded {new_state: pass.new_state, value: values}),
+			Err(failure) => failure,
+		};
+
+		match sep(state)
+		{
+			Ok(pass) => state = pass.new_state,
+			Err(failure) => return Err(Failed {old_state: input, ..merge_failures(failure, close_failure)}),
+		}
+	}
+}
@@ -1,119 +1,370 @@
 //! Functions and methods that return functions that are able to parse strings.
 //!
 //! These can be divided into parsers that return chars, strings, and generic Ts.
-// TODO: probably should use individual modules for these, but the dependencies
-// are painful (see https://github.com/mozilla/rust/issues/3352).
-use core::str::CharRange;
+// TODO: probably should use individual modules for these.
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
 
-use misc::*;
-use types::*;
+use crate::line_index::*;
+use crate::misc::*;
+use crate::types::*;
 
 /// Return type of parse function.
-pub type ParseStatus<T: Copy Durable> = result::Result<T, ParseFailed>;
+pub type ParseStatus<T> = Result<T, ParseFailed>;
 
 /// Returned by parse function on error. Line and col are both 1-based.
-pub struct ParseFailed {file: @~str, line: uint, col: uint, mesg: @~str}
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseFailed
+{
+	pub file: String,
+	pub line: usize,
+	pub col: usize,
+	pub mesg: String,
+
+	/// What the parsers at the error location were looking for, e.g. "','" and "')'".
+	pub expected: Vec<String>,
+
+	/// The unexpected character (or byte) or None if at the end of the input.
+	pub found: Option<String>,
+
+	/// Byte range of the unexpected input.
+	pub span: Range<usize>,
+
+	/// Labels of the enclosing parsers, innermost first.
+	pub context: Vec<String>,
+
+	/// The State.tab_width used to compute col.
+	pub tab_width: usize,
+
+	/// The col of span.start measured in each ColUnit (see position).
+	pub unit_cols: [usize; 3],
+}
+
+impl ParseFailed
+{
+	/// Returns the location of the error with the col measured in unit. Note that unlike
+	/// col this is measured by a LineIndex so tabs are always one column.
+	pub fn position(&self, unit: ColUnit) -> Position
+	{
+		Position {offset: self.span.start, line: self.line, col: self.unit_cols[unit as usize]}
+	}
+
+	/// Returns the error without the location, e.g. "expected ',' or ')' while parsing
+	/// attribute list, found 'x'".
+	pub fn description(&self) -> String
+	{
+		let tokens = !self.expected.is_empty() && self.mesg == self.expected.join(" or ");
+		let mut text = if tokens && self.expected.len() > 1
+		{
+			let (last, first) = self.expected.split_last().unwrap();
+			format!("expected one of {} or {}", first.join(", "), last)
+		}
+		else if tokens
+		{
+			format!("expected {}", self.mesg)
+		}
+		else
+		{
+			self.mesg.clone()
+		};
+
+		if let Some(label) = self.context.first()
+		{
+			text.push_str(&format!(" while parsing {}", label));
+		}
+
+		if tokens
+		{
+			match &self.found
+			{
+				Some(found) if found.starts_with("0x") => text.push_str(&format!(", found {}", found)),
+				Some(found) => text.push_str(&format!(", found {:?}", found.chars().next().unwrap())),
+				None => text.push_str(", found end of input"),
+			}
+		}
+		text
+	}
+}
+
+impl fmt::Display for ParseFailed
+{
+	/// Formats the error as "file:line:col: description".
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "{}:{}:{}: {}", self.file, self.line, self.col, self.description())
+	}
+}
+
+/// Return type of the parse_partial functions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PartialStatus<T>
+{
+	/// The parser succeeded after consuming count bytes. Callers would normally
+	/// drop those bytes from their buffer before parsing the next item.
+	Complete(T, usize),
+
+	/// The parser reached the end of the input and needs more to decide. Callers
+	/// should append more input and parse the whole buffer again.
+	Incomplete,
+
+	/// The parser failed.
+	Failed(ParseFailed),
+}
+
+/// Return type of the parse_recover function. If value is None errors will not be empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recovered<T> {pub value: Option<T>, pub errors: Vec<ParseFailed>}
+
+/// Counts of how often memo parsers were able to use a cached result.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MemoStats {pub hits: usize, pub misses: usize}
+
+impl MemoStats
+{
+	/// Returns the fraction of memo calls which used a cached result.
+	pub fn hit_rate(&self) -> f64
+	{
+		let total = self.hits + self.misses;
+		if total > 0 {self.hits as f64 / total as f64} else {0.0}
+	}
+}
+
+thread_local!(static MEMO_STATS: Cell<MemoStats> = Cell::new(MemoStats::default()));
+
+/// Returns the memo statistics for the current thread.
+pub fn memo_stats() -> MemoStats
+{
+	MEMO_STATS.with(|stats| stats.get())
+}
+
+/// Zeros the memo statistics for the current thread.
+pub fn reset_memo_stats()
+{
+	MEMO_STATS.with(|stats| stats.set(MemoStats::default()));
+}
+
+fn record_memo(hit: bool)
+{
+	MEMO_STATS.with(|stats|
+	{
+		let mut value = stats.get();
+		if hit {value.hits += 1;} else {value.misses += 1;}
+		stats.set(value);
+	});
+}
+
+// The State fields (other than the skipper) which affect what a parser does.
+type MemoKey = (usize, usize, usize, ColMode, bool);
+
+fn memo_key(input: &State) -> MemoKey
+{
+	(input.index, input.indent, input.tab_width, input.col_mode, input.partial)
+}
+
+// Used by memo: (the skipper the parser was called with, result, errors recorded by
+// the recover parsers while the parser ran).
+type MemoEntry<'a, T> = (Skipper<'a>, Status<'a, T>, Vec<ParseFailed>);
+
+// Left recursive parsers return a seed while they are growing. Results which depend
+// upon a seed aren't final so they must not be cached (this is a simplification of the
+// involved sets used by Warth et al).
+thread_local!(static SEED_READS: Cell<usize> = const {Cell::new(0)});
+
+// The (parse_id, index) of each left_rec parser which is growing a seed, innermost
+// last. Used by forward_ref to tell whether a recursive call will stop at a seed.
+thread_local!(static GROWING: RefCell<Vec<(usize, usize)>> = const {RefCell::new(Vec::new())});
+
+// Used by left_rec: (result, still growing, number of times the seed was used).
+type SeedEntry<'a, T> = (Status<'a, T>, bool, usize);
+
+fn seed_reads() -> usize
+{
+	SEED_READS.with(|reads| reads.get())
+}
+
+fn read_seed()
+{
+	SEED_READS.with(|reads| reads.set(reads.get() + 1));
+}
+
+// Left recursion is a bug in the grammar so, unlike other errors, it's reported by
+// the parse functions even if parsing backtracked and then succeeded.
+thread_local!(static LEFT_RECURSION: RefCell<Option<ParseFailed>> = const {RefCell::new(None)});
+
+fn record_left_recursion(failure: &Failed)
+{
+	LEFT_RECURSION.with(|err|
+	{
+		err.borrow_mut().get_or_insert_with(|| to_parse_failed(failure.clone()));
+	});
+}
+
+// Returns the left recursion error recorded while fun ran (if any).
+fn check_left_recursion<R>(fun: impl FnOnce() -> R) -> (R, Option<ParseFailed>)
+{
+	let outer = LEFT_RECURSION.with(|err| err.borrow_mut().take());
+	let result = fun();
+	let err = LEFT_RECURSION.with(|err| err.replace(outer));
+	(result, err)
+}
+
+// Errors recorded by the recover parsers. The parse functions take the errors
+// recorded while they ran and parsers which backtrack discard the errors recorded
+// by alternatives which failed.
+thread_local!(static DIAGNOSTICS: RefCell<Vec<ParseFailed>> = const {RefCell::new(Vec::new())});
+
+fn diagnostics_len() -> usize
+{
+	DIAGNOSTICS.with(|errors| errors.borrow().len())
+}
+
+fn truncate_diagnostics(len: usize)
+{
+	DIAGNOSTICS.with(|errors| errors.borrow_mut().truncate(len));
+}
+
+fn take_diagnostics(len: usize) -> Vec<ParseFailed>
+{
+	DIAGNOSTICS.with(|errors| errors.borrow_mut().split_off(len))
+}
+
+// Returns the errors recorded since mark (without removing them).
+fn diagnostics_since(mark: usize) -> Vec<ParseFailed>
+{
+	DIAGNOSTICS.with(|errors| errors.borrow().get(mark..).unwrap_or_default().to_vec())
+}
+
+fn record_diagnostic(failure: Failed)
+{
+	replay_diagnostics(&[to_parse_failed(failure)]);
+}
+
+fn replay_diagnostics(new_errors: &[ParseFailed])
+{
+	DIAGNOSTICS.with(|errors|
+	{
+		// Memoized and backtracking parsers may hit the same error more than once.
+		let mut errors = errors.borrow_mut();
+		for err in new_errors
+		{
+			if !errors.contains(err)
+			{
+				errors.push(err.clone());
+			}
+		}
+	});
+}
 
 // ---- weird parsers -----------------------------------------------------------------------------
 // Returns a parser which matches the end of the input.
 // Clients should use everything instead of this.
 #[doc(hidden)]
-pub fn eot() -> Parser<()>
+pub fn eot<'a>() -> Parser<'a, ()>
 {
-	|input: State|
+	Rc::new(|input: State<'a>|
 	{
-		if input.text[input.index] == EOT
+		if input.at_end() && !input.partial
 		{
-			result::Ok(Succeeded {new_state: State {index: input.index + 1u, ..input}, value: ()})
+			Ok(Succeeded {new_state: input, value: ()})
 		}
 		else
 		{
-			result::Err(Failed {old_state: input, err_state: input, mesg: @~"EOT"})
+			// With partial input we can't know whether we're at the end so we fail at
+			// the end of the input which makes this an incomplete failure.
+			Err(Failed::new(input.clone(), input, "EOT".to_string()))
 		}
-	}
+	})
+}
+
+// Used by the seq macros to turn a closure into a Parser.
+#[doc(hidden)]
+pub fn to_parser<'a, T>(fun: impl Fn(State<'a>) -> Status<'a, T> + 'a) -> Parser<'a, T>
+{
+	Rc::new(fun)
+}
+
+// Returns an error indicating that the parser ran out of partial input.
+#[doc(hidden)]
+pub fn incomplete<'a, T>(input: State<'a>) -> Status<'a, T>
+{
+	Err(Failed::message(input.clone(), State {index: input.bytes.len(), ..input}, "more input".to_string()))
 }
 
 // ---- char parsers ------------------------------------------------------------------------------
 /// Consumes a character which must satisfy the predicate.
 /// Returns the matched character.
-pub fn anycp(predicate: fn@ (char) -> bool) -> Parser<char>
+pub fn anycp<'a>(predicate: impl Fn(char) -> bool + 'a) -> Parser<'a, char>
 {
-	|input: State| {
-		let mut i = input.index;
-		if input.text[i] != EOT && predicate(input.text[i])
-		{
-			i += 1u;
-		}
-		
-		if i > input.index
-		{
-			result::Ok(Succeeded {new_state: State {index: i, ..input}, value: input.text[input.index]})
-		}
-		else
+	Rc::new(move |input: State<'a>|
+	{
+		match input.peek()
 		{
-			result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @~""})
+			Some(ch) if predicate(ch) =>
+			{
+				Ok(Succeeded {new_state: input.advance(ch.len_utf8()), value: ch})
+			}
+			_ =>
+			{
+				Err(Failed::new(input.clone(), input, String::new()))
+			}
 		}
-	}
+	})
 }
 
 /// Parse methods which return a character.
 pub trait CharParsers
 {
 	/// Attempts to match any character in self. If matched the char is returned.
-	fn anyc(&self) -> Parser<char>;
-	
+	fn anyc<'a>(&self) -> Parser<'a, char>;
+
 	/// Attempts to match no character in self. If matched the char is returned.
-	fn noc(&self) -> Parser<char>;
+	fn noc<'a>(&self) -> Parser<'a, char>;
 }
 
-pub impl &str : CharParsers
+impl CharParsers for str
 {
-	fn anyc(&self) -> Parser<char>
+	fn anyc<'a>(&self) -> Parser<'a, char>
 	{
-		// Note that we're handing this string off to a closure so we can't get rid of this copy
-		// even if we make the impl on ~str.
-		let s = self.to_owned();
-		
-		|input: State|
+		// Note that we're handing this string off to a closure so we can't get rid of this copy.
+		let s = self.to_string();
+
+		Rc::new(move |input: State<'a>|
 		{
-			let mut i = input.index;
-			if str::find_char(s, input.text[i]).is_some()
-			{
-				i += 1u;
-			}
-			
-			if i > input.index
-			{
-				result::Ok(Succeeded {new_state: State {index: i, ..input}, value: input.text[input.index]})
-			}
-			else
+			match input.peek()
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @fmt!("[%s]", s)})
+				Some(ch) if s.contains(ch) =>
+				{
+					Ok(Succeeded {new_state: input.advance(ch.len_utf8()), value: ch})
+				}
+				_ =>
+				{
+					Err(Failed::new(input.clone(), input, format!("[{}]", s)))
+				}
 			}
-		}
+		})
 	}
-	
-	fn noc(&self) -> Parser<char>
+
+	fn noc<'a>(&self) -> Parser<'a, char>
 	{
-		let s = self.to_owned();
-		
-		|input: State|
+		let s = self.to_string();
+
+		Rc::new(move |input: State<'a>|
 		{
-			let mut i = input.index;
-			if input.text[i] != EOT && str::find_char(s, input.text[i]).is_none()
-			{
-				i += 1u;
-			}
-			
-			if i > input.index
+			match input.peek()
 			{
-				result::Ok(Succeeded {new_state: State {index: i, ..input}, value: input.text[input.index]})
-			}
-			else
-			{
-				result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @fmt!("[^%s]", s)})
+				Some(ch) if !s.contains(ch) =>
+				{
+					Ok(Succeeded {new_state: input.advance(ch.len_utf8()), value: ch})
+				}
+				_ =>
+				{
+					Err(Failed::new(input.clone(), input, format!("[^{}]", s)))
+				}
 			}
-		}
+		})
 	}
 }
 
@@ -127,98 +378,101 @@ pub impl &str : CharParsers
 // users would have to write something like:
 //    match(p).r0().str()
 // 2) Generating an array of characters and then converting them into a string
-// is much slower than updating a mutable string.
+// is much slower than slicing the input.
 // 3) Debugging a parser is simpler if users can use higher level building
 // blocks (TODO: though maybe we can somehow ignore or collapse low
 // level parsers when logging).
+//
+// Note that the string parsers return slices of the input so they don't allocate.
 
 /// Consumes zero or more characters matching the predicate.
-/// Returns the matched characters. 
-/// 
-/// Note that this does not increment line.
-pub fn match0(predicate: fn@ (char) -> bool) -> Parser<@~str>
+/// Returns the matched characters.
+pub fn match0<'a>(predicate: impl Fn(char) -> bool + 'a) -> Parser<'a, &'a str>
 {
-	|input: State|
+	Rc::new(move |input: State<'a>|
 	{
-		let mut i = input.index;
-		while input.text[i] != EOT && predicate(input.text[i])
+		let rest = input.rest();
+		let count = match rest.find(|ch| !predicate(ch))
 		{
-			i += 1u;
-		}
-		
-		let text = str::from_chars(vec::slice(input.text, input.index, i));
-		result::Ok(Succeeded {new_state: State {index: i, ..input}, value: @text})
-	}
+			Some(count) => count,
+			None if input.partial && input.index + rest.len() == input.bytes.len() => return incomplete(input),
+			None => rest.len(),
+		};
+
+		Ok(Succeeded {new_state: input.advance(count), value: &rest[..count]})
+	})
 }
 
 /// Consumes one or more characters matching the predicate.
-/// Returns the matched characters. 
-/// 
-/// Note that this does not increment line.
-pub fn match1(predicate: fn@ (char) -> bool) -> Parser<@~str>
+/// Returns the matched characters.
+pub fn match1<'a>(predicate: impl Fn(char) -> bool + 'a) -> Parser<'a, &'a str>
 {
-	|input: State|
+	Rc::new(move |input: State<'a>|
 	{
-		let mut i = input.index;
-		while input.text[i] != EOT && predicate(input.text[i])
+		let rest = input.rest();
+		let count = match rest.find(|ch| !predicate(ch))
 		{
-			i += 1u;
-		}
-		
-		if i > input.index
+			Some(count) => count,
+			None if input.partial && input.index + rest.len() == input.bytes.len() => return incomplete(input),
+			None => rest.len(),
+		};
+
+		if count > 0
 		{
-			let text = str::from_chars(vec::slice(input.text, input.index, i));
-			result::Ok(Succeeded {new_state: State {index: i, ..input}, value: @text})
+			Ok(Succeeded {new_state: input.advance(count), value: &rest[..count]})
 		}
 		else
 		{
-			result::Err(Failed {old_state: input, err_state: State {index: i, ..input}, mesg: @~""})
+			Err(Failed::new(input.clone(), input, String::new()))
 		}
-	}
+	})
 }
 
 /// match1_0 := prefix+ suffix*
-pub fn match1_0(prefix: fn@ (char) -> bool, suffix: fn@ (char) -> bool) -> Parser<@~str>
+pub fn match1_0<'a>(prefix: impl Fn(char) -> bool + 'a, suffix: impl Fn(char) -> bool + 'a) -> Parser<'a, &'a str>
 {
-	let prefix = match1(prefix);
-	let suffix = match0(suffix);
-	prefix.thene(|p| suffix.thene(|s| ret(@(*p + *s))))
+	seq2_ret_str(match1(prefix), match0(suffix))
 }
 
 /// optional_str := e?
 ///
 /// Returns an empty string on failure.
-pub fn optional_str(parser: Parser<@~str>) -> Parser<@~str>
+pub fn optional_str<'a>(parser: Parser<'a, &'a str>) -> Parser<'a, &'a str>
 {
-	|input: State|
+	Rc::new(move |input: State<'a>|
 	{
-		match parser(input)
+		match parser(input.clone())
 		{
-			result::Ok(ref pass)		=> result::Ok(Succeeded {new_state: pass.new_state, value: pass.value}),
-			result::Err(ref _failure)		=> result::Ok(Succeeded {new_state: input, value: @~""}),
+			Ok(pass)		=> Ok(pass),
+			Err(failure) if failure.is_incomplete() => Err(failure),
+			Err(_failure)	=> Ok(Succeeded {new_state: input, value: ""}),
 		}
-	}
+	})
 }
 
-/// Calls fun once and matches the number of characters returned by fun. 
-/// 
+/// Calls fun once and matches the number of bytes returned by fun.
+///
 /// This does increment line.  Note that this succeeds even if zero characters are matched.
+/// Fun is passed the complete text and the byte offset at which to start matching.
 ///
 /// # Fun's are typically written like this:
 ///
-/// ~~~
-/// fn to_new_line(chars: @[char], index: uint) -> uint
+/// ```
+/// fn to_new_line(text: &str, index: usize) -> usize
 /// {
+///     // Comparing bytes is safe because ASCII bytes never appear within
+///     // multi-byte UTF-8 characters.
+///     let bytes = text.as_bytes();
 ///     let mut i = index;
 ///     loop
 ///     {
-///         // Chars will always have an EOT character. If we hit it then
-///         // we failed to find a new-line character so match nothing. 
-///         if chars[i] == EOT
+///         // If we hit the end of the text then we failed to find a
+///         // new-line character so match nothing.
+///         if i == bytes.len()
 ///         {
 ///             return 0;
 ///         }
-///         else if chars[i] == '\r' || chars[i] == '\n'
+///         else if bytes[i] == b'\r' || bytes[i] == b'\n'
 ///         {
 ///             // Match all the characters up to, but not including, the first new line.
 ///             return i - index;
@@ -229,210 +483,164 @@ pub fn optional_str(parser: Parser<@~str>) -> Parser<@~str>
 ///         }
 ///     }
 /// }
-/// ~~~
-pub fn scan(fun: fn@ (@[char], uint) -> uint) -> Parser<@~str>
+/// ```
+pub fn scan<'a>(fun: impl Fn(&str, usize) -> usize + 'a) -> Parser<'a, &'a str>
 {
-	|input: State|
+	Rc::new(move |input: State<'a>|
 	{
-		let mut i = input.index;
-		let mut line = input.line;
-		
-		let count = fun(input.text, i);
-		if count > 0u && input.text[i] != EOT		// EOT check makes it easier to write funs that do stuff like matching chars that are not something
+		if input.is_binary()
 		{
-			for uint::range(0u, count)
-			|_k| {
-				if input.text[i] == '\r'
-				{
-					line += 1;
-				}
-				else if input.text[i] == '\n' && (i == 0u || input.text[i-1u] != '\r')
-				{
-					line += 1;
-				}
-				i += 1u;
-			}
-			let text = str::from_chars(vec::slice(input.text, input.index, i));
-			result::Ok(Succeeded {new_state: State {index: i, line: line, ..input}, value: @text})
+			return Ok(Succeeded {new_state: input, value: ""});		// binary input has no text
 		}
-		else
+
+		let count = fun(input.text, input.index);
+		if input.partial && input.index + count == input.text.len()
 		{
-			result::Ok(Succeeded {new_state: State {index: i, line: line, ..input}, value: @~""})
+			return incomplete(input);		// fun might have matched more
 		}
-	}
-}
 
+		Ok(Succeeded {new_state: input.advance(count), value: &input.text[input.index..input.index + count]})
+	})
+}
 
-/// If all the parsers are successful then the matched text is returned.
-pub fn seq2_ret_str<T0: Copy Durable, T1: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>) -> Parser<@~str>
+// Returns the text between the start of input and the end of pass.
+fn matched_text<'a, T>(input: State<'a>, status: Status<'a, T>) -> Status<'a, &'a str>
 {
-	|input: State|
+	match status
 	{
-		match p0.then(p1)(input)
+		Ok(pass) =>
 		{
-			result::Ok(ref pass) =>
-			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
-				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
-			}
-			result::Err(ref failure) =>
-			{
-				result::Err(Failed {old_state: input, ..*failure})
-			}
+			Ok(Succeeded {new_state: pass.new_state.clone(), value: input.text.get(input.index..pass.new_state.index).unwrap_or("")})
+		}
+		Err(failure) =>
+		{
+			Err(Failed {old_state: input, ..failure})
 		}
 	}
 }
 
+/// Returns the text matched by parser instead of its value. Also see the seq_str macro.
+pub fn ret_str<'a, T: 'a>(parser: Parser<'a, T>) -> Parser<'a, &'a str>
+{
+	Rc::new(move |input: State<'a>| matched_text(input.clone(), parser(input)))
+}
+
 /// If all the parsers are successful then the matched text is returned.
-pub fn seq3_ret_str<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<@~str>
+pub fn seq2_ret_str<'a, T0: 'a, T1: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>) -> Parser<'a, &'a str>
 {
-	|input: State|
-	{
-		match p0.then(p1). then(p2)(input)
-		{
-			result::Ok(ref pass) =>
-			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
-				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
-			}
-			result::Err(ref failure) =>
-			{
-				result::Err(Failed {old_state: input, ..*failure})
-			}
-		}
-	}
+	let parser = p0.then(p1);
+	Rc::new(move |input: State<'a>| matched_text(input.clone(), parser(input)))
 }
 
 /// If all the parsers are successful then the matched text is returned.
-pub fn seq4_ret_str<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<@~str>
+pub fn seq3_ret_str<'a, T0: 'a, T1: 'a, T2: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>) -> Parser<'a, &'a str>
 {
-	|input: State| {
-		match p0.then(p1). then(p2).then(p3)(input)
-		{
-			result::Ok(ref pass) =>
-			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
-				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
-			}
-			result::Err(ref failure) =>
-			{
-				result::Err(Failed {old_state: input, ..*failure})
-			}
-		}
-	}
+	let parser = p0.then(p1).then(p2);
+	Rc::new(move |input: State<'a>| matched_text(input.clone(), parser(input)))
 }
 
 /// If all the parsers are successful then the matched text is returned.
-pub fn seq5_ret_str<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>, p4: Parser<T4>) -> Parser<@~str>
+pub fn seq4_ret_str<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>, p3: Parser<'a, T3>) -> Parser<'a, &'a str>
 {
-	|input: State| {
-		match p0.then(p1). then(p2).then(p3).then(p4)(input)
-		{
-			result::Ok(ref pass) =>
-			{
-				let text = str::from_chars(vec::slice(input.text, input.index, pass.new_state.index));
-				result::Ok(Succeeded {new_state: pass.new_state, value: @text})
-			}
-			result::Err(ref failure) =>
-			{
-				result::Err(Failed {old_state: input, ..*failure})
-			}
-		}
-	}
+	let parser = p0.then(p1).then(p2).then(p3);
+	Rc::new(move |input: State<'a>| matched_text(input.clone(), parser(input)))
+}
+
+/// If all the parsers are successful then the matched text is returned.
+pub fn seq5_ret_str<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, T4: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>, p3: Parser<'a, T3>, p4: Parser<'a, T4>) -> Parser<'a, &'a str>
+{
+	let parser = p0.then(p1).then(p2).then(p3).then(p4);
+	Rc::new(move |input: State<'a>| matched_text(input.clone(), parser(input)))
 }
 
 /// Parse methods which return a string.
 pub trait StringParsers
 {
 	/// Returns the input that matches self. Also see liti and litv.
-	fn lit(&self) -> Parser<@~str>;
-	
+	fn lit<'a>(&self) -> Parser<'a, &'a str>;
+
 	/// Returns the input that matches lower-cased self. Also see lit and litv.
-	fn liti(&self) -> Parser<@~str>;
-	
-	/// s0 := e [ \t\r\n]*
-	fn s0(&self) -> Parser<@~str>;
-	
-	/// s1 := e [ \t\r\n]+
-	fn s1(&self) -> Parser<@~str>;
+	fn liti<'a>(&self) -> Parser<'a, &'a str>;
+
+	/// s0 := e space (see Combinators::s0)
+	fn s0<'a>(&self) -> Parser<'a, &'a str>;
+
+	/// s1 := e space (see Combinators::s1)
+	fn s1<'a>(&self) -> Parser<'a, &'a str>;
 }
 
-pub impl &str : StringParsers
+impl StringParsers for str
 {
-	fn lit(&self) -> Parser<@~str>
+	fn lit<'a>(&self) -> Parser<'a, &'a str>
 	{
-		let s = self.to_owned();
-		
-		|input: State|
+		let s = self.to_string();
+
+		Rc::new(move |input: State<'a>|
 		{
-			let mut i = 0u;
-			let mut j = input.index;
-			while i < str::len(s)
+			// Find the longest prefix of s that matches so that errors can point
+			// at the first character which didn't match.
+			let rest = input.rest();
+			let mut j = 0;
+			for (ch, actual) in s.chars().zip(rest.chars())
 			{
-				let CharRange {ch, next} = str::char_range_at(s, i);
-				if ch == input.text[j]
-				{
-					i = next;
-					j += 1u;
-				}
-				else
+				if ch != actual
 				{
 					break;
 				}
+				j += ch.len_utf8();
 			}
-			
-			if i == str::len(s)
+
+			if j == s.len()
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, j));
-				result::Ok(Succeeded {new_state: State {index: j, ..input}, value: @text})
+				Ok(Succeeded {new_state: input.advance(j), value: &rest[..j]})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: j, ..input}, mesg: @fmt!("'%s'", s)})
+				Err(Failed::new(input.clone(), input.advance(j), format!("'{}'", s)))
 			}
-		}
+		})
 	}
-	
-	fn liti(&self) -> Parser<@~str>
+
+	fn liti<'a>(&self) -> Parser<'a, &'a str>
 	{
-		let s = str::to_lower(self);
-		
-		|input: State|
+		// Both sides are folded with char::to_lowercase. This can change the number of
+		// chars (and bytes) so we track the input and the literal separately.
+		let s: Vec<char> = self.chars().flat_map(char::to_lowercase).collect();
+		let expected = format!("'{}'", s.iter().collect::<String>());
+
+		Rc::new(move |input: State<'a>|
 		{
-			let mut i = 0u;
-			let mut j = input.index;
-			while i < str::len(s)
+			let rest = input.rest();
+			let mut i = 0;
+			let mut j = 0;
+			for actual in rest.chars()
 			{
-				let CharRange {ch, next} = str::char_range_at(s, i);
-				if ch == lower_char(input.text[j])
-				{
-					i = next;
-					j += 1u;
-				}
-				else
+				let lower = actual.to_lowercase();
+				if i == s.len() || s.len() - i < lower.len() || !lower.zip(&s[i..]).all(|(a, &b)| a == b)
 				{
 					break;
 				}
+				i += actual.to_lowercase().len();
+				j += actual.len_utf8();
 			}
-			
-			if i == str::len(s)
+
+			if i == s.len()
 			{
-				let text = str::from_chars(vec::slice(input.text, input.index, j));
-				result::Ok(Succeeded {new_state: State {index: j, ..input}, value: @text})
+				Ok(Succeeded {new_state: input.advance(j), value: &rest[..j]})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: State {index: j, ..input}, mesg: @fmt!("'%s'", s)})
+				Err(Failed::new(input.clone(), input.advance(j), expected.clone()))
 			}
-		}
+		})
 	}
-	
-	fn s0(&self) -> Parser<@~str>
+
+	fn s0<'a>(&self) -> Parser<'a, &'a str>
 	{
 		self.lit().s0()
 	}
-	
-	fn s1(&self) -> Parser<@~str>
+
+	fn s1<'a>(&self) -> Parser<'a, &'a str>
 	{
 		self.lit().s1()
 	}
@@ -440,368 +648,659 @@ pub impl &str : StringParsers
 
 // ---- generic parsers ---------------------------------------------------------------------------
 /// Returns a parser which always fails.
-pub fn fails<T: Copy Durable>(mesg: &str) -> Parser<T>
+pub fn fails<'a, T: 'a>(mesg: &str) -> Parser<'a, T>
 {
-	let mesg = mesg.to_owned();
-	|input: State| result::Err(Failed {old_state: input, err_state: input, mesg: @copy mesg})
+	let mesg = mesg.to_string();
+	Rc::new(move |input: State<'a>| Err(Failed::message(input.clone(), input, mesg.clone())))
 }
 
 /// Parses with the aid of a pointer to a parser (useful for things like parenthesized expressions).
 ///
 /// # Usage is like this:
 ///
-/// ~~~
+/// ```
+/// # use std::cell::RefCell;
+/// # use std::rc::Rc;
+/// # use rparse::*;
 /// // create a pointer that we can initialize later with the real expr parser
-/// let expr_ptr = @mut ret(0i);
-/// let expr_ref = forward_ref(expr_ptr);
-/// 
+/// let expr_ptr = Rc::new(RefCell::new(ret(0)));
+/// let expr_ref = forward_ref(expr_ptr.clone());
+///
 /// // expr_ref can be used to parse expressions
-/// 
+/// # let expr = expr_ref.clone();
+///
 /// // initialize the expr_ptr with the real parser
-/// *expr_ptr = expr;
-/// ~~~
-pub fn forward_ref<T: Copy Durable>(parser: @mut Parser<T>) -> Parser<T>
+/// *expr_ptr.borrow_mut() = expr;
+/// ```
+///
+/// Left recursive grammars, e.g. `expr := expr '+' term | term`, require the use
+/// of left_rec. Without it parsing fails with a "left recursion" error when the
+/// recursion is detected (even if another alternative could have matched).
+pub fn forward_ref<'a, T: 'a>(parser: Rc<RefCell<Parser<'a, T>>>) -> Parser<'a, T>
+{
+	// Parsers are deterministic so if we're called again at the same index we'll recurse
+	// until the stack overflows unless the first call went through a left_rec which is
+	// growing a seed at that index (the second call will then stop at the seed). Active
+	// holds the parse_id, index, and GROWING length of each call.
+	let active: RefCell<Vec<(usize, usize, usize)>> = RefCell::new(Vec::new());
+
+	// Note that we clone the parser before calling it so that recursive parsers
+	// don't hold a borrow across the call.
+	Rc::new(move |input: State<'a>|
+	{
+		let key = (input.parse_id, input.index);
+		let outer = active.borrow().iter().rev().find(|e| (e.0, e.1) == key).map(|e| e.2);
+		if let Some(depth) = outer
+		{
+			if !GROWING.with(|growing| growing.borrow()[depth..].contains(&key))
+			{
+				let failure = Failed::message(input.clone(), input, "left recursion (use left_rec)".to_string());
+				record_left_recursion(&failure);
+				return Err(failure);
+			}
+		}
+
+		let parser = parser.borrow().clone();
+		let depth = GROWING.with(|growing| growing.borrow().len());
+		active.borrow_mut().push((input.parse_id, input.index, depth));
+		let status = parser(input);
+		active.borrow_mut().pop();
+		status
+	})
+}
+
+/// Creates the parser the first time it's used. This is mostly useful for recursive
+/// types that implement Parse, e.g. `lazy(Xml::parser)`.
+pub fn lazy<'a, T: 'a>(make: impl Fn() -> Parser<'a, T> + 'a) -> Parser<'a, T>
 {
-	|input: State| (*parser)(input)
+	let parser: OnceCell<Parser<'a, T>> = OnceCell::new();
+	Rc::new(move |input: State<'a>| parser.get_or_init(&make)(input))
 }
 
 /// or_v := e0 | e1 | …
-/// 
+///
 /// This is a version of or that is nicer to use when there are more than two alternatives.
-pub fn or_v<T: Copy Durable>(parsers: @~[Parser<T>]) -> Parser<T>
+pub fn or_v<'a, T: 'a>(parsers: Vec<Parser<'a, T>>) -> Parser<'a, T>
 {
 	// A recursive algorithm would be a lot simpler, but it's not clear how that could
 	// produce good error messages.
-	assert !vec::is_empty(*parsers);
-	
-	|input: State|
-	{
-		let mut result: Option<Status<T>> = None;
-		let mut errors = ~[];
-		let mut max_index = uint::max_value;
-		let mut i = 0u;
-		while i < vec::len(*parsers) && option::is_none(&result)
+	assert!(!parsers.is_empty());
+
+	Rc::new(move |input: State<'a>|
+	{
+		let mut best: Option<Failed<'a>> = None;
+		let mark = diagnostics_len();
+		for parser in parsers.iter()
 		{
-			match parsers[i](input)
+			truncate_diagnostics(mark);
+			match parser(input.clone())
 			{
-				result::Ok(ref pass) =>
+				Ok(pass) =>
 				{
-					result = option::Some(result::Ok(*pass));
+					return Ok(pass);
 				}
-				result::Err(ref failure) =>
+				Err(failure) if failure.is_incomplete() =>
 				{
-					if failure.err_state.index > max_index || max_index == uint::max_value
-					{
-						errors = ~[failure.mesg];
-						max_index = failure.err_state.index;
-					}
-					else if failure.err_state.index == max_index
+					// Later alternatives can't be tried until we know whether this one matches.
+					return Err(Failed {old_state: input, ..failure});
+				}
+				Err(failure) =>
+				{
+					best = Some(match best
 					{
-						vec::push(&mut errors, failure.mesg);
-					}
+						Some(prev) => merge_failures(prev, failure),
+						None => failure,
+					});
 				}
 			}
-			i += 1u;
 		}
-		
-		if option::is_some(&result)
-		{
-			option::get(result)
-		}
-		else
-		{
-			let errs = do vec::filter(errors) |s| {!str::is_empty(**s)};
-			let mesg = at_connect(errs, ~" or ");
-			result::Err(Failed {old_state: input, err_state: State {index: max_index, ..input}, mesg: @mesg})
-		}
-	}
+
+		let best = best.unwrap();
+		Err(Failed {old_state: input, ..best})
+	})
 }
 
-/// Returns a parser which always succeeds, but does not consume any input.
-#[allow(deprecated_mode)]		// TODO: probably need to use &T instead
-pub fn ret<T: Copy Durable>(value: T) -> Parser<T>
+/// Returns the current location without consuming any input.
+pub fn position<'a>() -> Parser<'a, Position>
 {
-	|input: State| result::Ok(Succeeded {new_state: input, value: value})
+	Rc::new(|input: State<'a>| Ok(Succeeded {new_state: input.clone(), value: input.position()}))
 }
 
-/// seq2 := e0 e1
-pub fn seq2<T0: Copy Durable, T1: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, eval: fn@ (T0, T1) -> result::Result<R, @~str>) -> Parser<R>
+/// Returns a parser which always succeeds, but does not consume any input.
+pub fn ret<'a, T: Clone + 'a>(value: T) -> Parser<'a, T>
+{
+	Rc::new(move |input: State<'a>| Ok(Succeeded {new_state: input, value: value.clone()}))
+}
+
+// Used by the seqN functions when a parser after the first one fails: the next
+// parser must start over at the original input.
+fn restart<'a, T>(input: State<'a>, status: Status<'a, T>) -> Status<'a, T>
+{
+	status.map_err(|failure| Failed {old_state: input.clone(), ..failure})
+}
+
+// Used by s0 and s1 to skip the white space after pass.
+fn skip_space<'a, T>(input: State<'a>, pass: Succeeded<'a, T>) -> Status<'a, T>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-		match eval(a0, a1)
+	let new_state = pass.new_state.skip_space();
+	if input.partial && new_state.at_end()
+	{
+		return incomplete(input);		// more white space might follow
+	}
+	Ok(Succeeded {new_state, value: pass.value})
+}
+
+// Used by the seqN functions to convert the result of their eval function into a status.
+fn evaluated<'a, R>(input: State<'a>, new_state: State<'a>, value: Result<R, String>) -> Status<'a, R>
+{
+	match value
+	{
+		Ok(value) =>
 		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
+			Ok(Succeeded {new_state, value})
+		}
+		Err(mesg) =>
+		{
+			Err(Failed::message(input, new_state, mesg))
 		}
-	}}
+	}
+}
+
+/// seq2 := e0 e1
+///
+/// The seqN functions pass the values to eval which can fail the parse by returning an
+/// error message. The seq! macro can be used with any number of parsers.
+pub fn seq2<'a, T0: 'a, T1: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, eval: impl Fn(T0, T1) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		evaluated(input, a1.new_state, eval(a0.value, a1.value))
+	})
 }
 
 /// seq3 := e0 e1 e2
-pub fn seq3<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, eval: fn@ (T0, T1, T2) -> result::Result<R, @~str>) -> Parser<R>
+pub fn seq3<'a, T0: 'a, T1: 'a, T2: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, eval: impl Fn(T0, T1, T2) -> Result<R, String> + 'a) -> Parser<'a, R>
 {
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-		match eval(a0, a1, a2)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		evaluated(input, a2.new_state, eval(a0.value, a1.value, a2.value))
+	})
 }
 
 /// seq4 := e0 e1 e2 e3
-pub fn seq4<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, eval: fn@ (T0, T1, T2, T3) -> result::Result<R, @~str>) -> Parser<R>
-{
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-		match eval(a0, a1, a2, a3)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}
+pub fn seq4<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, parser3: Parser<'a, T3>, eval: impl Fn(T0, T1, T2, T3) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		let a3 = restart(input.clone(), parser3(a2.new_state))?;
+		evaluated(input, a3.new_state, eval(a0.value, a1.value, a2.value, a3.value))
+	})
 }
 
 /// seq5 := e0 e1 e2 e3 e4
-pub fn seq5<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, eval: fn@ (T0, T1, T2, T3, T4) -> result::Result<R, @~str>) -> Parser<R>
-{
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-		match eval(a0, a1, a2, a3, a4)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}
+pub fn seq5<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, T4: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, parser3: Parser<'a, T3>, parser4: Parser<'a, T4>, eval: impl Fn(T0, T1, T2, T3, T4) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		let a3 = restart(input.clone(), parser3(a2.new_state))?;
+		let a4 = restart(input.clone(), parser4(a3.new_state))?;
+		evaluated(input, a4.new_state, eval(a0.value, a1.value, a2.value, a3.value, a4.value))
+	})
 }
 
 /// seq6 := e0 e1 e2 e3 e4 e5
-pub fn seq6<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, eval: fn@ (T0, T1, T2, T3, T4, T5) -> result::Result<R, @~str>) -> Parser<R>
-{
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-		match eval(a0, a1, a2, a3, a4, a5)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}
+pub fn seq6<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, T4: 'a, T5: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, parser3: Parser<'a, T3>, parser4: Parser<'a, T4>, parser5: Parser<'a, T5>, eval: impl Fn(T0, T1, T2, T3, T4, T5) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		let a3 = restart(input.clone(), parser3(a2.new_state))?;
+		let a4 = restart(input.clone(), parser4(a3.new_state))?;
+		let a5 = restart(input.clone(), parser5(a4.new_state))?;
+		evaluated(input, a5.new_state, eval(a0.value, a1.value, a2.value, a3.value, a4.value, a5.value))
+	})
 }
 
 /// seq7 := e0 e1 e2 e3 e4 e5 e6
-pub fn seq7<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, T6: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, parser6: Parser<T6>, eval: fn@ (T0, T1, T2, T3, T4, T5, T6) -> result::Result<R, @~str>) -> Parser<R>
-{
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-	do parser6.thene() |a6| {
-		match eval(a0, a1, a2, a3, a4, a5, a6)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}}
+#[allow(clippy::too_many_arguments)]
+pub fn seq7<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, T4: 'a, T5: 'a, T6: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, parser3: Parser<'a, T3>, parser4: Parser<'a, T4>, parser5: Parser<'a, T5>, parser6: Parser<'a, T6>, eval: impl Fn(T0, T1, T2, T3, T4, T5, T6) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		let a3 = restart(input.clone(), parser3(a2.new_state))?;
+		let a4 = restart(input.clone(), parser4(a3.new_state))?;
+		let a5 = restart(input.clone(), parser5(a4.new_state))?;
+		let a6 = restart(input.clone(), parser6(a5.new_state))?;
+		evaluated(input, a6.new_state, eval(a0.value, a1.value, a2.value, a3.value, a4.value, a5.value, a6.value))
+	})
 }
 
 /// seq8 := e0 e1 e2 e3 e4 e5 e6 e7
-pub fn seq8<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, T6: Copy Durable, T7: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, parser6: Parser<T6>, parser7: Parser<T7>, eval: fn@ (T0, T1, T2, T3, T4, T5, T6, T7) -> result::Result<R, @~str>) -> Parser<R>
-{
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-	do parser6.thene() |a6| {
-	do parser7.thene() |a7| {
-		match eval(a0, a1, a2, a3, a4, a5, a6, a7)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}}}
+#[allow(clippy::too_many_arguments)]
+pub fn seq8<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, T4: 'a, T5: 'a, T6: 'a, T7: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, parser3: Parser<'a, T3>, parser4: Parser<'a, T4>, parser5: Parser<'a, T5>, parser6: Parser<'a, T6>, parser7: Parser<'a, T7>, eval: impl Fn(T0, T1, T2, T3, T4, T5, T6, T7) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		let a3 = restart(input.clone(), parser3(a2.new_state))?;
+		let a4 = restart(input.clone(), parser4(a3.new_state))?;
+		let a5 = restart(input.clone(), parser5(a4.new_state))?;
+		let a6 = restart(input.clone(), parser6(a5.new_state))?;
+		let a7 = restart(input.clone(), parser7(a6.new_state))?;
+		evaluated(input, a7.new_state, eval(a0.value, a1.value, a2.value, a3.value, a4.value, a5.value, a6.value, a7.value))
+	})
 }
 
 /// seq9 := e0 e1 e2 e3 e4 e5 e6 e7 e8
-pub fn seq9<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable, T4: Copy Durable, T5: Copy Durable, T6: Copy Durable, T7: Copy Durable, T8: Copy Durable, R: Copy Durable>
-	(parser0: Parser<T0>, parser1: Parser<T1>, parser2: Parser<T2>, parser3: Parser<T3>, parser4: Parser<T4>, parser5: Parser<T5>, parser6: Parser<T6>, parser7: Parser<T7>, parser8: Parser<T8>, eval: fn@ (T0, T1, T2, T3, T4, T5, T6, T7, T8) -> result::Result<R, @~str>) -> Parser<R>
-{
-	do parser0.thene() |a0| {
-	do parser1.thene() |a1| {
-	do parser2.thene() |a2| {
-	do parser3.thene() |a3| {
-	do parser4.thene() |a4| {
-	do parser5.thene() |a5| {
-	do parser6.thene() |a6| {
-	do parser7.thene() |a7| {
-	do parser8.thene() |a8| {
-		match eval(a0, a1, a2, a3, a4, a5, a6, a7, a8)
-		{
-			result::Ok(ref value) =>
-			{
-				ret(*value)
-			}
-			result::Err(mesg) =>
-			{
-				fails(*mesg)
-			}
-		}
-	}}}}}}}}}
+#[allow(clippy::too_many_arguments)]
+pub fn seq9<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a, T4: 'a, T5: 'a, T6: 'a, T7: 'a, T8: 'a, R: 'a>
+	(parser0: Parser<'a, T0>, parser1: Parser<'a, T1>, parser2: Parser<'a, T2>, parser3: Parser<'a, T3>, parser4: Parser<'a, T4>, parser5: Parser<'a, T5>, parser6: Parser<'a, T6>, parser7: Parser<'a, T7>, parser8: Parser<'a, T8>, eval: impl Fn(T0, T1, T2, T3, T4, T5, T6, T7, T8) -> Result<R, String> + 'a) -> Parser<'a, R>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let a0 = parser0(input.clone())?;
+		let a1 = restart(input.clone(), parser1(a0.new_state))?;
+		let a2 = restart(input.clone(), parser2(a1.new_state))?;
+		let a3 = restart(input.clone(), parser3(a2.new_state))?;
+		let a4 = restart(input.clone(), parser4(a3.new_state))?;
+		let a5 = restart(input.clone(), parser5(a4.new_state))?;
+		let a6 = restart(input.clone(), parser6(a5.new_state))?;
+		let a7 = restart(input.clone(), parser7(a6.new_state))?;
+		let a8 = restart(input.clone(), parser8(a7.new_state))?;
+		evaluated(input, a8.new_state, eval(a0.value, a1.value, a2.value, a3.value, a4.value, a5.value, a6.value, a7.value, a8.value))
+	})
 }
 
 /// seq2_ret0 := e0 e1
-pub fn seq2_ret0<T0: Copy Durable, T1: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>) -> Parser<T0>
+pub fn seq2_ret0<'a, T0: 'a, T1: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>) -> Parser<'a, T0>
 {
-	seq2(p0, p1, |a0, _a1| result::Ok(a0))
+	seq2(p0, p1, |a0, _a1| Ok(a0))
 }
 
 /// seq2_ret1 := e0 e1
-pub fn seq2_ret1<T0: Copy Durable, T1: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>) -> Parser<T1>
+pub fn seq2_ret1<'a, T0: 'a, T1: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>) -> Parser<'a, T1>
 {
-	seq2(p0, p1, |_a0, a1| result::Ok(a1))
+	seq2(p0, p1, |_a0, a1| Ok(a1))
 }
 
 /// seq3_ret0 := e0 e1 e2
-pub fn seq3_ret0<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T0>
+pub fn seq3_ret0<'a, T0: 'a, T1: 'a, T2: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>) -> Parser<'a, T0>
 {
-	seq3(p0, p1, p2, |a0, _a1, _a2| result::Ok(a0))
+	seq3(p0, p1, p2, |a0, _a1, _a2| Ok(a0))
 }
 
 /// seq3_ret1 := e0 e1 e2
-pub fn seq3_ret1<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T1>
+pub fn seq3_ret1<'a, T0: 'a, T1: 'a, T2: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>) -> Parser<'a, T1>
 {
-	seq3(p0, p1, p2, |_a0, a1, _a2| result::Ok(a1))
+	seq3(p0, p1, p2, |_a0, a1, _a2| Ok(a1))
 }
 
 /// seq3_ret2 := e0 e1 e2
-pub fn seq3_ret2<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>) -> Parser<T2>
+pub fn seq3_ret2<'a, T0: 'a, T1: 'a, T2: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>) -> Parser<'a, T2>
 {
-	seq3(p0, p1, p2, |_a0, _a1, a2| result::Ok(a2))
+	seq3(p0, p1, p2, |_a0, _a1, a2| Ok(a2))
 }
 
 /// seq4_ret0 := e0 e1 e2 e3
-pub fn seq4_ret0<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T0>
+pub fn seq4_ret0<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>, p3: Parser<'a, T3>) -> Parser<'a, T0>
 {
-	seq4(p0, p1, p2, p3, |a0, _a1, _a2, _a3| result::Ok(a0))
+	seq4(p0, p1, p2, p3, |a0, _a1, _a2, _a3| Ok(a0))
 }
 
 /// seq4_ret1 := e0 e1 e2 e3
-pub fn seq4_ret1<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T1>
+pub fn seq4_ret1<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>, p3: Parser<'a, T3>) -> Parser<'a, T1>
 {
-	seq4(p0, p1, p2, p3, |_a0, a1, _a2, _a3| result::Ok(a1))
+	seq4(p0, p1, p2, p3, |_a0, a1, _a2, _a3| Ok(a1))
 }
 
 /// seq4_ret2 := e0 e1 e2 e3
-pub fn seq4_ret2<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T2>
+pub fn seq4_ret2<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>, p3: Parser<'a, T3>) -> Parser<'a, T2>
 {
-	seq4(p0, p1, p2, p3, |_a0, _a1, a2, _a3| result::Ok(a2))
+	seq4(p0, p1, p2, p3, |_a0, _a1, a2, _a3| Ok(a2))
 }
 
 /// seq4_ret3 := e0 e1 e2 e3
-pub fn seq4_ret3<T0: Copy Durable, T1: Copy Durable, T2: Copy Durable, T3: Copy Durable>(p0: Parser<T0>, p1: Parser<T1>, p2: Parser<T2>, p3: Parser<T3>) -> Parser<T3>
+pub fn seq4_ret3<'a, T0: 'a, T1: 'a, T2: 'a, T3: 'a>(p0: Parser<'a, T0>, p1: Parser<'a, T1>, p2: Parser<'a, T2>, p3: Parser<'a, T3>) -> Parser<'a, T3>
 {
-	seq4(p0, p1, p2, p3, |_a0, _a1, _a2, a3| result::Ok(a3))
+	seq4(p0, p1, p2, p3, |_a0, _a1, _a2, a3| Ok(a3))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SepKind
+{
+	Between,		// e (sep e)*
+	Trailing,		// e (sep e)* sep?
+	After,			// (e sep)+
+}
+
+// Used by the sep_by family. Separators and elements which are not followed by
+// what the kind requires are not consumed (like list always did).
+fn sep_list<'a, T: 'a, U: 'a>(parser: Parser<'a, T>, sep: Parser<'a, U>, min: usize, kind: SepKind) -> Parser<'a, Vec<T>>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		let mut output = input.clone();
+		let mut values = Vec::new();
+		let failure = loop
+		{
+			// Errors recovered from by an iteration which isn't consumed are discarded.
+			let mark = diagnostics_len();
+			let mut next = output.clone();
+			if kind == SepKind::Between && !values.is_empty()
+			{
+				match sep(next)
+				{
+					Ok(pass) => next = pass.new_state,
+					Err(failure) =>
+					{
+						truncate_diagnostics(mark);
+						break failure;
+					}
+				}
+			}
+
+			let value = match parser(next)
+			{
+				Ok(pass) =>
+				{
+					next = pass.new_state;
+					pass.value
+				}
+				Err(failure) =>
+				{
+					truncate_diagnostics(mark);
+					break failure;
+				}
+			};
+
+			if kind != SepKind::Between
+			{
+				let sep_mark = diagnostics_len();
+				match sep(next.clone())
+				{
+					Ok(pass) =>
+					{
+						next = pass.new_state;
+					}
+					Err(failure) if kind == SepKind::Trailing && !failure.is_incomplete() =>
+					{
+						truncate_diagnostics(sep_mark);
+						values.push(value);
+						output = next;
+						break failure;
+					}
+					Err(failure) =>
+					{
+						truncate_diagnostics(mark);
+						break failure;
+					}
+				}
+			}
+
+			values.push(value);
+			if next.index == output.index
+			{
+				// Parsing the same empty element and separator again would loop forever.
+				return Ok(Succeeded {new_state: next, value: values});
+			}
+			output = next;
+		};
+
+		if failure.is_incomplete() || values.len() < min
+		{
+			Err(Failed {old_state: input, ..failure})
+		}
+		else
+		{
+			Ok(Succeeded {new_state: output, value: values})
+		}
+	})
 }
 
 // chain_suffix := (op e)*
 #[doc(hidden)]
-pub fn chain_suffix<T: Copy Durable, U: Copy Durable>(parser: Parser<T>, op: Parser<U>) -> Parser<@~[(U, T)]>
+pub fn chain_suffix<'a, T: 'a, U: 'a>(parser: Parser<'a, T>, op: Parser<'a, U>) -> Parser<'a, Vec<(U, T)>>
+{
+	seq2(op, parser, |operator, value| Ok((operator, value))).r0()
+}
+
+// Index is a byte offset but we want the log arrows to line up with the
+// characters.
+fn chars_before(text: &str, index: usize) -> usize
+{
+	text[..index].chars().count()
+}
+
+// Binary data can't be printed so, for the log, we use byte offsets instead of arrows.
+fn note_bytes<'a, T>(parser: &Parser<'a, T>, input: State<'a>, mesg: &str) -> Status<'a, T>
+{
+	match parser(input.clone())
+	{
+		Ok(pass) =>
+		{
+			assert!(pass.new_state.index >= input.index);
+			info!("{} parsed bytes {}..{}", mesg, input.index, pass.new_state.index);
+			Ok(pass)
+		}
+		Err(failure) =>
+		{
+			assert!(failure.old_state.index == input.index);
+			assert!(failure.err_state.index >= input.index);
+			debug!("{} failed at byte {} (started at {})", mesg, failure.err_state.index, input.index);
+			Err(failure)
+		}
+	}
+}
+
+fn to_parse_failed(failure: Failed) -> ParseFailed
+{
+	let pos = failure.err_state.position();
+	let found = failure.found();
+	let span = failure.span();
+	ParseFailed {file: failure.old_state.file.to_string(), line: pos.line, col: pos.col, mesg: failure.mesg, expected: failure.expected, found, span, context: failure.context, tab_width: failure.err_state.tab_width, unit_cols: [pos.col; 3]}
+}
+
+// Sets the unit_cols of errors. The LineIndex is only built if there are errors.
+fn index_errors(input: &State, errors: &mut [ParseFailed], index: Option<&LineIndex>)
+{
+	if errors.is_empty() || input.is_binary()
+	{
+		return;			// binary cols are byte offsets in every unit
+	}
+
+	let built;
+	let index = match index
+	{
+		Some(index) => index,
+		None =>
+		{
+			built = LineIndex::new(input.text);
+			&built
+		}
+	};
+	for err in errors.iter_mut()
+	{
+		for unit in [ColUnit::Utf8, ColUnit::Utf16, ColUnit::Chars]
+		{
+			if let Some(pos) = index.position(err.span.start, unit)
+			{
+				err.unit_cols[unit as usize] = pos.col;
+			}
+		}
+	}
+}
+
+// Returns the errors recorded while parser ran followed by the error it failed with (if any).
+fn recover_status<'a, T>(parser: &Parser<'a, T>, input: State<'a>, index: Option<&LineIndex>) -> Recovered<T>
+{
+	let mark = diagnostics_len();
+	let (status, left_recursion) = check_left_recursion(|| parser(input.clone()));
+	let mut errors = take_diagnostics(mark);
+	let value = match (status, left_recursion)
+	{
+		(_, Some(err)) =>
+		{
+			errors.insert(0, err);
+			None
+		}
+		(Ok(pass), None) =>
+		{
+			Some(pass.value)
+		}
+		(Err(failure), None) =>
+		{
+			errors.push(to_parse_failed(failure));
+			None
+		}
+	};
+	index_errors(&input, &mut errors, index);
+	Recovered {value, errors}
+}
+
+fn first_error<T>(result: Recovered<T>) -> ParseStatus<T>
+{
+	match result.value
+	{
+		Some(value) if result.errors.is_empty() => Ok(value),
+		_ => Err(result.errors.into_iter().next().unwrap()),
+	}
+}
+
+fn partial_status<'a, T>(parser: &Parser<'a, T>, input: State<'a>) -> PartialStatus<T>
+{
+	let mark = diagnostics_len();
+	let (status, left_recursion) = check_left_recursion(|| parser(input.clone()));
+	let errors = take_diagnostics(mark);
+	let err = match (status, left_recursion)
+	{
+		(_, Some(err)) =>
+		{
+			err
+		}
+		(Err(failure), None) if failure.is_incomplete() =>
+		{
+			// Errors may have been recovered from only because the input was cut short.
+			return PartialStatus::Incomplete;
+		}
+		(Ok(pass), None) if errors.is_empty() =>
+		{
+			return PartialStatus::Complete(pass.value, pass.new_state.index);
+		}
+		(Ok(_), None) =>
+		{
+			errors.into_iter().next().unwrap()
+		}
+		(Err(failure), None) =>
+		{
+			errors.into_iter().next().unwrap_or_else(|| to_parse_failed(failure))
+		}
+	};
+	let mut errors = [err];
+	index_errors(&input, &mut errors, None);
+	let [err] = errors;
+	PartialStatus::Failed(err)
+}
+
+// Returns the state after the next character (or byte for binary input) or None
+// if the state is at the end of the input.
+fn skip_one(state: State) -> Option<State>
+{
+	if state.is_binary()
+	{
+		return if state.at_end() {None} else {Some(state.advance(1))};
+	}
+
+	state.peek().map(|ch| state.advance(ch.len_utf8()))
+}
+
+// Errors which happen after a labeled parser has made progress happened within
+// that parser so the label is recorded as context.
+fn in_context<'a>(mut failure: Failed<'a>, input: State<'a>, label: &str) -> Failed<'a>
+{
+	if !label.is_empty() && failure.err_state.index > input.index
+	{
+		failure.context.push(label.to_string());
+	}
+	failure
+}
+
+// Returns the failure which got further or, if they failed at the same spot, a
+// failure which expects what either of them expected.
+#[doc(hidden)]
+pub fn merge_failures<'a>(failure1: Failed<'a>, failure2: Failed<'a>) -> Failed<'a>
 {
-	let q = op.thene(
-	|operator|
+	if failure1.err_state.index > failure2.err_state.index
+	{
+		failure1
+	}
+	else if failure1.err_state.index < failure2.err_state.index
+	{
+		failure2
+	}
+	else
 	{
-		parser.thene(
-		|value|
+		let tokens1 = failure1.mesg == failure1.expected.join(" or ");
+		let tokens2 = failure2.mesg == failure2.expected.join(" or ");
+
+		let mut expected = failure1.expected;
+		for token in failure2.expected.iter()
 		{
-			ret((operator, value))
-		})
-	});
-	q.r0()
+			if !expected.contains(token)
+			{
+				expected.push(token.clone());
+			}
+		}
+
+		let mesg = if tokens1 && tokens2 {expected.join(" or ")} else {or_mesg(&failure1.mesg, &failure2.mesg)};
+		Failed {mesg, expected, ..failure2}
+	}
 }
 
 // When using tag it can be useful to use empty messages for interior parsers
 // so we need to handle that case.
 #[doc(hidden)]
-pub fn or_mesg(mesg1: @~str, mesg2: @~str) -> @~str
+pub fn or_mesg(mesg1: &str, mesg2: &str) -> String
 {
-	if !str::is_empty(*mesg1) && !str::is_empty(*mesg2)
+	if !mesg1.is_empty() && !mesg2.is_empty()
 	{
-		@(*mesg1 + " or " + *mesg2)
+		format!("{} or {}", mesg1, mesg2)
 	}
-	else if !str::is_empty(*mesg1)
+	else if !mesg1.is_empty()
 	{
-		mesg1
+		mesg1.to_string()
 	}
-	else if !str::is_empty(*mesg2)
+	else if !mesg2.is_empty()
 	{
-		mesg2
+		mesg2.to_string()
 	}
 	else
 	{
-		@~""
+		String::new()
 	}
 }
 
@@ -809,470 +1308,880 @@ pub fn or_mesg(mesg1: @~str, mesg2: @~str) -> @~str
 pub trait GenericParsers
 {
 	/// Returns value if input matches s. Also see lit.
-	fn litv<T: Copy Durable>(&self, value: T) -> Parser<T>;
+	fn litv<'a, T: Clone + 'a>(&self, value: T) -> Parser<'a, T>;
 }
 
 /// Parse methods used to compose parsers.
 ///
 /// Note that these don't actually consume input (although the parsers they are invoked with normally will).
-pub trait Combinators<T: Copy Durable>
+pub trait Combinators<'a, T>
 {
 	/// chainl1 := e (op e)*
-	/// 
+	///
 	/// Left associative binary operator. eval is called for each parsed op.
-	fn chainl1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>;
-	
+	fn chainl1<U: 'a>(&self, op: Parser<'a, U>, eval: impl Fn(T, U, T) -> T + 'a) -> Parser<'a, T>;
+
 	/// chainr1 := e (op e)*
-	/// 
+	///
 	/// Right associative binary operator. eval is called for each parsed op.
-	fn chainr1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>;
-	
+	fn chainr1<U: 'a>(&self, op: Parser<'a, U>, eval: impl Fn(T, U, T) -> T + 'a) -> Parser<'a, T>;
+
+	/// end_by0 := (e sep)*
+	///
+	/// Values for each parsed e are returned.
+	fn end_by0<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// end_by1 := (e sep)+
+	///
+	/// Values for each parsed e are returned.
+	fn end_by1<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
 	/// Like note except that the mesg is also used for error reporting.
-	/// 
+	///
 	/// If label is not empty then it is used if the previous parser completely failed to parse or if its error
 	/// message was empty. Otherwise it suppresses errors from the parser (in favor of a later err function).
 	/// Non-empty labels should look like \"expression\" or \"statement\".
-	fn err(&self, label: &str) -> Parser<T>;
-	
+	fn err(&self, label: &str) -> Parser<'a, T>;
+
 	/// Parses the text and fails if all the text was not consumed. Leading space is allowed.
-	/// 
+	///
 	/// This is typically used in conjunction with the parse method. Note that space has to have the
 	/// same type as parser which is backwards from how it is normally used.
-	fn everything<U: Copy Durable>(&self, space: Parser<U>) -> Parser<T>;
-	
+	fn everything<U: 'a>(&self, space: Parser<'a, U>) -> Parser<'a, T>;
+
+	/// If the parser fails an error like "missing ';'" is recorded and value is returned
+	/// as if the parser had matched (no input is consumed). See parse_recover.
+	fn insert_missing(&self, value: T) -> Parser<'a, T> where T: Clone;
+
 	/// list := e (sep e)*
-	/// 
-	/// Values for each parsed e are returned.
-	fn list<U: Copy Durable>(&self, sep: Parser<U>) -> Parser<@~[T]>;
-	
+	///
+	/// Values for each parsed e are returned. This is the same as sep_by1.
+	fn list<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// many_till := e* end
+	///
+	/// Values for each parsed e are returned. End is tried before each e so, for example,
+	/// `anycp(|_| true).many_till("*/".lit())` can be used to parse the body of a C comment.
+	fn many_till<U: 'a>(&self, end: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// Allows the parser to be left recursive, e.g. `expr := expr '-' term | term`.
+	///
+	/// This should be applied to the parser that a forward_ref points to. The first call at an
+	/// index fails the recursive calls so that the other alternatives can match. Then the
+	/// parser is called again with that result until it stops making progress. Indirect
+	/// recursion is supported: only one rule in each cycle needs to use left_rec.
+	fn left_rec(&self) -> Parser<'a, T> where T: Clone;
+
+	/// Caches the result of the parser for each index it is called with.
+	///
+	/// Memoizing the non-terminals of a grammar ensures that backtracking (e.g. via or)
+	/// takes linear time. Results are discarded when the parser is used with a new
+	/// input (see State.parse_id). Calls with a different indent, skipper, tab_width,
+	/// col_mode, or partial are cached separately. Also see memo_stats.
+	fn memo(&self) -> Parser<'a, T> where T: Clone;
+
 	/// Logs the result of the previous parser.
-	/// 
+	///
 	/// If it was successful then the log is at INFO level. Otherwise it is at DEBUG level.
 	/// Also see err method.
-	fn note(&self, mesg: &str) -> Parser<T>;
-	
+	fn note(&self, mesg: &str) -> Parser<'a, T>;
+
 	/// optional := e?
-	fn optional(&self) -> Parser<Option<T>>;
-	
+	fn optional(&self) -> Parser<'a, Option<T>>;
+
 	/// Returns a parser which first tries parser1, and if that fails, parser2.
-	fn or(&self, parser2: Parser<T>) -> Parser<T>;
-	
+	fn or(&self, parser2: Parser<'a, T>) -> Parser<'a, T>;
+
 	/// Uses parser to parse text. Also see everything method.
-	fn parse(&self, file: @~str, text: &str) -> ParseStatus<T>;
-	
+	fn parse(&self, file: &'a str, text: &'a str) -> ParseStatus<T>;
+
+	/// Uses parser to parse binary data. On errors line is 1 and col is the 1-based byte offset.
+	fn parse_bytes(&self, file: &'a str, bytes: &'a [u8]) -> ParseStatus<T>;
+
+	/// Like parse except that parsing starts with input. This is typically used to change
+	/// how columns are counted, e.g. `State::new(file, text).with_tab_width(8)`.
+	fn parse_from(&self, input: State<'a>) -> ParseStatus<T>;
+
+	/// Like parse except that index's text is parsed and index is used to compute the
+	/// cols of errors (see ParseFailed::position). Callers which also use the index, e.g.
+	/// for ErrorRenderer::render_indexed or to convert spans, only need to build it once.
+	fn parse_indexed(&self, file: &'a str, index: &LineIndex<'a>) -> ParseStatus<T>;
+
+	/// Uses parser to parse text which may be followed by more text, e.g. data read
+	/// from a socket. Once the input is known to be complete parse should be used instead.
+	///
+	/// Note that everything will always return Incomplete for partial input. Also, because parsers
+	/// borrow the text they parse, a new parser is normally created for each call.
+	fn parse_partial(&self, file: &'a str, text: &'a str) -> PartialStatus<T>;
+
+	/// Like parse_partial except that binary data is parsed.
+	fn parse_partial_bytes(&self, file: &'a str, bytes: &'a [u8]) -> PartialStatus<T>;
+
+	/// Like parse except that the errors recorded by recover_with, recover_until, and
+	/// insert_missing are returned along with the value. The other parse functions
+	/// return the first recorded error.
+	fn parse_recover(&self, file: &'a str, text: &'a str) -> Recovered<T>;
+
+	/// Like parse_recover except that index is used (see parse_indexed).
+	fn parse_recover_indexed(&self, file: &'a str, index: &LineIndex<'a>) -> Recovered<T>;
+
 	/// Succeeds if parser matches input n to m times (inclusive).
-	fn r(&self, n: uint, m: uint) -> Parser<@~[T]>;
-	
+	fn r(&self, n: usize, m: usize) -> Parser<'a, Vec<T>>;
+
 	/// r0 := e*
-	/// 
+	///
 	/// Values for each parsed e are returned.
-	fn r0(&self) -> Parser<@~[T]>;
-	
+	fn r0(&self) -> Parser<'a, Vec<T>>;
+
 	/// r1 := e+
-	/// 
+	///
+	/// Values for each parsed e are returned.
+	fn r1(&self) -> Parser<'a, Vec<T>>;
+
+	/// If the parser fails its error is recorded and the input is skipped up to, but not
+	/// including, the first place sync matches (or the end of the input). Default is
+	/// returned. For example, `statement().recover_until(";".lit(), Statement::Error)`.
+	///
+	/// Failures at the end of the input are not recovered from (there is nothing to skip)
+	/// so that it can be used with r0 and friends.
+	fn recover_until<U: 'a>(&self, sync: Parser<'a, U>, default: T) -> Parser<'a, T> where T: Clone;
+
+	/// If the parser fails its error is recorded and default is returned without
+	/// consuming any input. See parse_recover.
+	fn recover_with(&self, default: T) -> Parser<'a, T> where T: Clone;
+
+	/// s0 := e space
+	///
+	/// Space is matched by the input's skipper which, by default, matches [ \t\r\n]*.
+	/// See State::with_skipper.
+	fn s0(&self) -> Parser<'a, T>;
+
+	/// s1 := e space
+	///
+	/// Like s0 except that space must match at least one character.
+	fn s1(&self) -> Parser<'a, T>;
+
+	/// sep_by0 := (e (sep e)*)?
+	///
+	/// Values for each parsed e are returned. A trailing sep is not consumed.
+	fn sep_by0<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// sep_by1 := e (sep e)*
+	///
+	/// Values for each parsed e are returned. A trailing sep is not consumed.
+	fn sep_by1<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// sep_end_by0 := (e (sep e)* sep?)?
+	///
+	/// Values for each parsed e are returned.
+	fn sep_end_by0<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// sep_end_by1 := e (sep e)* sep?
+	///
 	/// Values for each parsed e are returned.
-	fn r1(&self) -> Parser<@~[T]>;
-	
-	/// s0 := e [ \t\r\n]*
-	fn s0(&self) -> Parser<T>;
-	
-	/// s1 := e [ \t\r\n]+
-	fn s1(&self) -> Parser<T>;
-	
+	fn sep_end_by1<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>;
+
+	/// Returns the parser's value along with the location of the input it matched.
+	/// Note that this will include trailing white space matched by s0 and s1.
+	fn spanned(&self) -> Parser<'a, (T, Span)>;
+
 	/// If parser1 is successful is successful then parser2 is called (and the value from parser1
 	/// is ignored). If parser1 fails parser2 is not called.
-	fn then<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<U>;
-	
+	fn then<U: 'a>(&self, parser2: Parser<'a, U>) -> Parser<'a, U>;
+
 	/// If parser is successful then the function returned by eval is called
 	/// with parser's result. If parser fails eval is not called.
-	/// 
-	/// Often used to translate parsed values: `p().thene({|pvalue| return(2*pvalue)})`
-	fn thene<U: Copy Durable>(&self, eval: fn@ (T) -> Parser<U>) -> Parser<U>;
+	///
+	/// Often used to translate parsed values: `p().thene(|pvalue| ret(2*pvalue))`
+	fn thene<U: 'a>(&self, eval: impl Fn(T) -> Parser<'a, U> + 'a) -> Parser<'a, U>;
+
+	/// Returns the parser's value along with the location where it started.
+	fn with_pos(&self) -> Parser<'a, (T, Position)>;
 }
 
-pub impl<T: Copy Durable> Parser<T> : Combinators<T>
+impl<'a, T: 'a> Combinators<'a, T> for Parser<'a, T>
 {
-	fn chainl1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>
+	fn chainl1<U: 'a>(&self, op: Parser<'a, U>, eval: impl Fn(T, U, T) -> T + 'a) -> Parser<'a, T>
 	{
-		|input: State|
+		let parser = self.clone();
+		let suffix = chain_suffix(self.clone(), op);
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			parser(input.clone()).and_then(|pass|
 			{
-				match chain_suffix(self, op)(pass.new_state)
+				match suffix(pass.new_state)
 				{
-					result::Ok(ref pass2) =>
+					Ok(pass2) =>
 					{
-						let value = vec::foldl(pass.value, *pass2.value, |lhs: T, rhs: &(U, T)| {eval(lhs, rhs.first(), rhs.second())});
-						result::Ok(Succeeded {new_state: pass2.new_state, value: value})
+						let value = pass2.value.into_iter().fold(pass.value, |lhs, (op, rhs)| eval(lhs, op, rhs));
+						Ok(Succeeded {new_state: pass2.new_state, value})
 					}
-					result::Err(ref failure) =>
+					Err(failure) =>
 					{
-						result::Err(Failed {old_state: input, ..*failure})
+						Err(Failed {old_state: input, ..failure})
 					}
 				}
-			}
-		}
+			})
+		})
 	}
-	
-	fn chainr1<U: Copy Durable>(&self, op: Parser<U>, eval: fn@ (T, U, T) -> T) -> Parser<T>
+
+	fn end_by0<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
+	{
+		sep_list(self.clone(), sep, 0, SepKind::After)
+	}
+
+	fn end_by1<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
 	{
-		|input: State|
+		sep_list(self.clone(), sep, 1, SepKind::After)
+	}
+
+	fn chainr1<U: 'a>(&self, op: Parser<'a, U>, eval: impl Fn(T, U, T) -> T + 'a) -> Parser<'a, T>
+	{
+		let parser = self.clone();
+		let suffix = chain_suffix(self.clone(), op);
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			parser(input.clone()).and_then(|pass|
 			{
-				match chain_suffix(self, op)(pass.new_state)
+				match suffix(pass.new_state)
 				{
-					result::Ok(ref pass2) =>
+					Ok(pass2) =>
 					{
-						if !vec::is_empty(*pass2.value)
+						// e1 and [(op1 e2), (op2 e3)]
+						let mut ops = Vec::with_capacity(pass2.value.len());
+						let mut values = vec![pass.value];
+						for (op, value) in pass2.value
 						{
-							// e1 and [(op1 e2), (op2 e3)]
-							let e1 = pass.value;
-							let terms = pass2.value;
-							
-							// e1 and [op1, op2] and [e2, e3]
-							let (ops, parsers) = vec::unzip(copy *terms);
-							
-							// [op1, op2] and [e1, e2] and e3
-							let e3 = vec::last(parsers);
-							let parsers = ~[e1] + vec::slice(parsers, 0u, vec::len(parsers) - 1u);
-							
-							// [(e1 op1), (e2 op2)] and e3
-							let terms = vec::zip(parsers, ops);
-							
-							let value = vec::foldr(terms, e3, {|lhs: &(T, U), rhs| eval(lhs.first(), lhs.second(), rhs)});
-							result::Ok(Succeeded {new_state: pass2.new_state, value: value})
+							ops.push(op);
+							values.push(value);
 						}
-						else
+
+						// e1 op1 (e2 op2 e3)
+						let mut value = values.pop().unwrap();
+						while let Some(op) = ops.pop()
 						{
-							result::Ok(Succeeded {new_state: pass2.new_state, value: pass.value})
+							let lhs = values.pop().unwrap();
+							value = eval(lhs, op, value);
 						}
+						Ok(Succeeded {new_state: pass2.new_state, value})
 					}
-					result::Err(ref failure) =>
+					Err(failure) =>
 					{
-						result::Err(Failed {old_state: input ,.. *failure})
+						Err(Failed {old_state: input, ..failure})
 					}
 				}
-			}
-		}
+			})
+		})
 	}
-	
-	fn err(&self, label: &str) -> Parser<T>
+
+	fn err(&self, label: &str) -> Parser<'a, T>
 	{
-		let label = label.to_owned();
-		
-		|input: State|
+		let label = label.to_string();
+		let parser = self.note(&label);
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain_err((self.note(label))(input))
-			|failure| 
+			parser(input.clone()).map_err(|failure|
 			{
-				if str::is_empty(label)
+				if label.is_empty()
+				{
+					Failed {mesg: String::new(), expected: Vec::new(), ..failure}
+				}
+				else if failure.err_state.index == input.index
 				{
-					result::Err(Failed {mesg: @~"", ..failure})
+					Failed {mesg: label.clone(), expected: vec![label.clone()], ..failure}
 				}
-				else if failure.err_state.index == input.index || str::is_empty(*failure.mesg)
+				else if failure.mesg.is_empty()
 				{
-					result::Err(Failed {mesg: @copy label, ..failure})
+					// The label replaces the error so it isn't also context.
+					let mut context = failure.context;
+					context.pop();
+					Failed {mesg: label.clone(), expected: vec![label.clone()], context, ..failure}
 				}
 				else
 				{
 					// If we managed to parse something then it is usually better to
 					// use that error message. (If that's not what you want then use
 					// empty strings there).
-					result::Err(failure)
+					failure
+				}
+			})
+		})
+	}
+
+	fn everything<U: 'a>(&self, space: Parser<'a, U>) -> Parser<'a, T>
+	{
+		seq3_ret1(space, self.clone(), eot())
+	}
+
+	fn insert_missing(&self, value: T) -> Parser<'a, T> where T: Clone
+	{
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			match parser(input.clone())
+			{
+				Err(failure) if !failure.is_incomplete() =>
+				{
+					let mesg = if failure.mesg.is_empty() {failure.mesg} else {format!("missing {}", failure.mesg)};
+					record_diagnostic(Failed {mesg, ..failure});
+					Ok(Succeeded {new_state: input, value: value.clone()})
+				}
+				status =>
+				{
+					status
 				}
 			}
-		}
+		})
 	}
-	
-	fn everything<U: Copy Durable>(&self, space: Parser<U>) -> Parser<T>
+
+	fn list<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
 	{
-		seq3_ret1(space, self, eot())
+		self.sep_by1(sep)
 	}
-	
-	fn list<U: Copy Durable>(&self, sep: Parser<U>) -> Parser<@~[T]>
+
+	fn many_till<U: 'a>(&self, end: Parser<'a, U>) -> Parser<'a, Vec<T>>
 	{
-		let term = sep.then(self).r0();
-		
-		|input: State|
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			let mut output = input.clone();
+			let mut values = Vec::new();
+			loop
 			{
-				match term(pass.new_state)
+				let mark = diagnostics_len();
+				let end_failure = match end(output.clone())
 				{
-					result::Ok(ref pass2) =>
+					Ok(pass) =>
+					{
+						return Ok(Succeeded {new_state: pass.new_state, value: values});
+					}
+					Err(failure) if failure.is_incomplete() =>
 					{
-						result::Ok(Succeeded {value: @(~[pass.value] + *pass2.value), ..*pass2})
+						return Err(Failed {old_state: input, ..failure});
 					}
-					result::Err(ref failure) =>
+					Err(failure) =>
 					{
-						result::Err(Failed {old_state: input, ..*failure})
+						truncate_diagnostics(mark);
+						failure
+					}
+				};
+
+				match parser(output.clone())
+				{
+					Ok(pass) if pass.new_state.index == output.index =>
+					{
+						// Neither end nor parser can make progress so end will never match.
+						return Err(Failed {old_state: input, ..end_failure});
+					}
+					Ok(pass) =>
+					{
+						output = pass.new_state;
+						values.push(pass.value);
+					}
+					Err(failure) if failure.is_incomplete() =>
+					{
+						return Err(Failed {old_state: input, ..failure});
+					}
+					Err(failure) =>
+					{
+						return Err(Failed {old_state: input, ..merge_failures(failure, end_failure)});
 					}
 				}
 			}
-		}
+		})
 	}
-	
-	fn note(&self, mesg: &str) -> Parser<T>
+
+	fn left_rec(&self) -> Parser<'a, T> where T: Clone
 	{
-		let mesg = mesg.to_owned();
-		
-		|input: State|
+		let parser = self.clone();
+		let cache: RefCell<(usize, HashMap<usize, SeedEntry<'a, T>>)> = RefCell::new((0, HashMap::new()));
+
+		Rc::new(move |input: State<'a>|
 		{
-			match (*self)(input)
 			{
-				result::Ok(ref pass) =>
+				let mut cache = cache.borrow_mut();
+				if cache.0 != input.parse_id
 				{
-					// Note that we make multiple calls to munge_chars which is fairly slow, but
-					// we only do that when actually logging: when info or debug logging is off
-					// the munge_chars calls aren't evaluated.
-					assert pass.new_state.index >= input.index;			// can't go backwards on success (but no progress is fine, eg e*)
-					if pass.new_state.index > input.index
-					{
-						info!("%s", munge_chars(input.text));
-						info!("%s^ %s parsed '%s'", repeat_char(' ', pass.new_state.index), mesg, str::slice(munge_chars(input.text), input.index, pass.new_state.index));
-					}
-					else
-					{
-						info!("%s", munge_chars(input.text));
-						info!("%s^ %s passed", repeat_char(' ', pass.new_state.index), mesg);
-					}
-					result::Ok(*pass)
+					cache.0 = input.parse_id;
+					cache.1.clear();
 				}
-				result::Err(ref failure) =>
+				else if let Some(entry) = cache.1.get_mut(&input.index)
 				{
-					assert failure.old_state.index == input.index;			// on errors the next parser must begin at the start
-					assert failure.err_state.index >= input.index;			// errors can't be before the input
-					
-					debug!("%s", munge_chars(input.text));
-					if failure.err_state.index > input.index 
+					if entry.1
 					{
-						debug!("%s^%s! %s failed", repeat_char('-', input.index), repeat_char(' ', failure.err_state.index - input.index), mesg);
+						entry.2 += 1;
+						read_seed();
 					}
-					else
+					return entry.0.clone();
+				}
+			}
+
+			// Plant a seed which fails so that the recursive calls fail.
+			let reads = seed_reads();
+			let seed = Err(Failed::new(input.clone(), input.clone(), String::new()));
+			cache.borrow_mut().1.insert(input.index, (seed, true, 0));
+
+			// Grow the seed until the parser stops making progress.
+			GROWING.with(|growing| growing.borrow_mut().push((input.parse_id, input.index)));
+			loop
+			{
+				let status = parser(input.clone());
+
+				let mut cache = cache.borrow_mut();
+				let entry = cache.1.get_mut(&input.index).unwrap();
+				if let Err(failure) = &status
+				{
+					if failure.is_incomplete()
 					{
-						debug!("%s^ %s failed", repeat_char('-', input.index), mesg);
+						GROWING.with(|growing| growing.borrow_mut().pop());
+						cache.1.remove(&input.index);
+						return status;
 					}
-					result::Err(*failure)
+				}
+
+				let grew = match (&status, &entry.0)
+				{
+					(Ok(pass), Ok(seed)) => pass.new_state.index > seed.new_state.index,
+					(Ok(_), Err(_)) => true,
+					(Err(_), _) => false,
+				};
+				if grew || entry.0.is_err()
+				{
+					entry.0 = status;		// note that if we never grew we want the real error
+				}
+				if !grew || entry.2 == 0
+				{
+					break;			// no progress or we weren't actually called recursively
 				}
 			}
-		}
+			GROWING.with(|growing| growing.borrow_mut().pop());
+
+			let mut cache = cache.borrow_mut();
+			let entry = cache.1.get_mut(&input.index).unwrap();
+			entry.1 = false;
+			let status = entry.0.clone();
+			if seed_reads() - reads > entry.2
+			{
+				cache.1.remove(&input.index);		// depends upon an outer seed
+			}
+			status
+		})
 	}
-	
-	fn optional(&self) -> Parser<Option<T>>
+
+	fn memo(&self) -> Parser<'a, T> where T: Clone
 	{
-		|input: State|
+		let parser = self.clone();
+		let cache: RefCell<(usize, HashMap<MemoKey, MemoEntry<'a, T>>)> = RefCell::new((0, HashMap::new()));
+
+		Rc::new(move |input: State<'a>|
 		{
-			match (*self)(input)
+			let key = memo_key(&input);
 			{
-				result::Ok(ref pass) =>
+				let mut cache = cache.borrow_mut();
+				if cache.0 != input.parse_id
 				{
-					result::Ok(Succeeded {new_state: pass.new_state, value: option::Some(pass.value)})
+					cache.0 = input.parse_id;
+					cache.1.clear();
 				}
-				result::Err(ref _failure) =>
+				else if let Some((_, status, errors)) = cache.1.get(&key).filter(|entry| entry.0 == input.skipper)
 				{
-					result::Ok(Succeeded {new_state: input, value: option::None})
+					record_memo(true);
+					replay_diagnostics(errors);
+					return status.clone();
 				}
 			}
-		}
+
+			// Note that we can't hold the borrow here because the parser may be recursive.
+			record_memo(false);
+			let reads = seed_reads();
+			let mark = diagnostics_len();
+			let status = parser(input.clone());
+			if seed_reads() == reads
+			{
+				cache.borrow_mut().1.insert(key, (input.skipper, status.clone(), diagnostics_since(mark)));
+			}
+			status
+		})
 	}
-	
-	fn or(&self, parser2: Parser<T>) -> Parser<T>
+
+	fn note(&self, mesg: &str) -> Parser<'a, T>
 	{
-		|input: State|
+		let parser = self.clone();
+		let mesg = mesg.to_string();
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain_err((*self)(input))
-			|failure1|
+			if input.is_binary()
 			{
-				do result::chain_err(parser2(input))
-				|failure2|
+				return note_bytes(&parser, input.clone(), &mesg).map_err(|failure| in_context(failure, input, &mesg));
+			}
+
+			match parser(input.clone())
+			{
+				Ok(pass) =>
 				{
-					if failure1.err_state.index > failure2.err_state.index
+					// Note that we make multiple calls to munge_chars which is fairly slow, but
+					// we only do that when actually logging: when info or debug logging is off
+					// the munge_chars calls aren't evaluated.
+					assert!(pass.new_state.index >= input.index);			// can't go backwards on success (but no progress is fine, eg e*)
+					if pass.new_state.index > input.index
+					{
+						info!("{}", munge_chars(input.text));
+						info!("{}^ {} parsed '{}'", repeat_char(' ', chars_before(input.text, pass.new_state.index)), mesg, munge_chars(&input.text[input.index..pass.new_state.index]));
+					}
+					else
 					{
-						result::Err(failure1)
+						info!("{}", munge_chars(input.text));
+						info!("{}^ {} passed", repeat_char(' ', chars_before(input.text, pass.new_state.index)), mesg);
 					}
-					else if failure1.err_state.index < failure2.err_state.index
+					Ok(pass)
+				}
+				Err(failure) =>
+				{
+					assert!(failure.old_state.index == input.index);			// on errors the next parser must begin at the start
+					assert!(failure.err_state.index >= input.index);			// errors can't be before the input
+
+					debug!("{}", munge_chars(input.text));
+					if failure.err_state.index > input.index
 					{
-						result::Err(failure2)
+						debug!("{}^{}! {} failed", repeat_char('-', chars_before(input.text, input.index)), repeat_char(' ', chars_before(input.text, failure.err_state.index) - chars_before(input.text, input.index)), mesg);
 					}
 					else
 					{
-						result::Err(Failed {mesg: or_mesg(failure1.mesg, failure2.mesg), ..failure2})
+						debug!("{}^ {} failed", repeat_char('-', chars_before(input.text, input.index)), mesg);
 					}
+					Err(in_context(failure, input, &mesg))
 				}
 			}
-		}
+		})
 	}
-	
-	fn parse(&self, file: @~str, text: &str) -> ParseStatus<T>
+
+	fn optional(&self) -> Parser<'a, Option<T>>
 	{
-		let chars = chars_with_eot(text);
-		let input = State {file: file, text: chars, index: 0u, line: 1};
-		match (*self)(input)
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			result::Ok(ref pass) =>
+			let mark = diagnostics_len();
+			match parser(input.clone())
 			{
-				result::Ok(pass.value)
+				Ok(pass) =>
+				{
+					Ok(Succeeded {new_state: pass.new_state, value: Some(pass.value)})
+				}
+				Err(failure) if failure.is_incomplete() =>
+				{
+					Err(failure)
+				}
+				Err(_failure) =>
+				{
+					truncate_diagnostics(mark);
+					Ok(Succeeded {new_state: input, value: None})
+				}
 			}
-			result::Err(ref failure) =>
+		})
+	}
+
+	fn or(&self, parser2: Parser<'a, T>) -> Parser<'a, T>
+	{
+		let parser1 = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			let mark = diagnostics_len();
+			parser1(input.clone()).or_else(|failure1|
 			{
-				let col = get_col(chars, failure.err_state.index);
-				result::Err(ParseFailed {file: failure.old_state.file, line: failure.err_state.line as uint, col: col, mesg: failure.mesg})
-			}
-		}
+				if failure1.is_incomplete()
+				{
+					return Err(failure1);
+				}
+
+				truncate_diagnostics(mark);
+				parser2(input).map_err(|failure2| merge_failures(failure1, failure2))
+			})
+		})
+	}
+
+	fn parse(&self, file: &'a str, text: &'a str) -> ParseStatus<T>
+	{
+		first_error(recover_status(self, State::new(file, text), None))
+	}
+
+	fn parse_bytes(&self, file: &'a str, bytes: &'a [u8]) -> ParseStatus<T>
+	{
+		first_error(recover_status(self, State::from_bytes(file, bytes), None))
+	}
+
+	fn parse_from(&self, input: State<'a>) -> ParseStatus<T>
+	{
+		first_error(recover_status(self, input, None))
+	}
+
+	fn parse_indexed(&self, file: &'a str, index: &LineIndex<'a>) -> ParseStatus<T>
+	{
+		first_error(recover_status(self, State::new(file, index.text()), Some(index)))
+	}
+
+	fn parse_partial(&self, file: &'a str, text: &'a str) -> PartialStatus<T>
+	{
+		partial_status(self, State {partial: true, ..State::new(file, text)})
+	}
+
+	fn parse_partial_bytes(&self, file: &'a str, bytes: &'a [u8]) -> PartialStatus<T>
+	{
+		partial_status(self, State {partial: true, ..State::from_bytes(file, bytes)})
+	}
+
+	fn parse_recover(&self, file: &'a str, text: &'a str) -> Recovered<T>
+	{
+		recover_status(self, State::new(file, text), None)
+	}
+
+	fn parse_recover_indexed(&self, file: &'a str, index: &LineIndex<'a>) -> Recovered<T>
+	{
+		recover_status(self, State::new(file, index.text()), Some(index))
 	}
-	
-	fn r(&self, n: uint, m: uint) -> Parser<@~[T]>
+
+	fn r(&self, n: usize, m: usize) -> Parser<'a, Vec<T>>
 	{
-		|input: State|
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			let mut output = input;
-			let mut values = ~[];
+			let mut output = input.clone();
+			let mut values = Vec::new();
 			loop
 			{
-				match (*self)(output)
+				let mark = diagnostics_len();
+				match parser(output.clone())
 				{
-					result::Ok(ref pass) =>
+					Ok(pass) =>
 					{
-						assert pass.new_state.index > output.index;	// must make progress to ensure loop termination
+						assert!(pass.new_state.index > output.index);	// must make progress to ensure loop termination
 						output = pass.new_state;
-						vec::push(&mut values, pass.value);
+						values.push(pass.value);
+					}
+					Err(failure) if failure.is_incomplete() =>
+					{
+						return Err(Failed {old_state: input, ..failure});
 					}
-					result::Err(_) =>
+					Err(_failure) =>
 					{
+						truncate_diagnostics(mark);
 						break;
 					}
 				}
 			}
-			
-			let count = vec::len(values);
+
+			let count = values.len();
 			if n <= count && count <= m
 			{
-				result::Ok(Succeeded {new_state: output, value: @values})
+				Ok(Succeeded {new_state: output, value: values})
 			}
 			else
 			{
-				result::Err(Failed {old_state: input, err_state: output, mesg: @~""})
+				Err(Failed::new(input, output, String::new()))
 			}
-		}
+		})
 	}
-	
-	fn r0(&self) -> Parser<@~[T]>
+
+	fn r0(&self) -> Parser<'a, Vec<T>>
 	{
-		self.r(0u, uint::max_value)
+		self.r(0, usize::MAX)
 	}
-	
-	fn r1(&self) -> Parser<@~[T]>
+
+	fn r1(&self) -> Parser<'a, Vec<T>>
 	{
-		self.r(1u, uint::max_value)
+		self.r(1, usize::MAX)
 	}
-	
-	fn s0(&self) -> Parser<T>
+
+	fn recover_until<U: 'a>(&self, sync: Parser<'a, U>, default: T) -> Parser<'a, T> where T: Clone
 	{
-		// It would be simpler to write this with scan0, but scan0 is relatively inefficient
-		// and s0 is typically called a lot.
-		|input: State|
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			match parser(input.clone())
 			{
-				let mut i = pass.new_state.index;
-				let mut line = pass.new_state.line;
-				loop
+				Err(failure) if !failure.is_incomplete() =>
 				{
-					if input.text[i] == '\r' && input.text[i+1u] == '\n'
+					let mut state = input.clone();
+					loop
 					{
-						line += 1;
-						i += 1u;
-					}
-					else if input.text[i] == '\n'
-					{
-						line += 1;
-					}
-					else if input.text[i] == '\r'
-					{
-						line += 1;
-					}
-					else if input.text[i] != ' ' && input.text[i] != '\t'
-					{
-						break;
+						match sync(state.clone())
+						{
+							Ok(_) => break,
+							Err(failure) if failure.is_incomplete() => return incomplete(input),
+							Err(_) => (),
+						}
+						match skip_one(state.clone())
+						{
+							Some(next) => state = next,
+							None if input.partial => return incomplete(input),
+							None if state.index == input.index => return Err(failure),
+							None => break,
+						}
 					}
-					i += 1u;
+
+					record_diagnostic(failure);
+					Ok(Succeeded {new_state: state, value: default.clone()})
+				}
+				status =>
+				{
+					status
 				}
-				
-				result::Ok(Succeeded {new_state: State {index: i, line: line, ..pass.new_state}, value: pass.value})
 			}
-		}
+		})
 	}
-	
-	fn s1(&self) -> Parser<T>
+
+	fn recover_with(&self, default: T) -> Parser<'a, T> where T: Clone
 	{
-		|input: State|
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain(self.s0()(input))
-			|pass|
+			match parser(input.clone())
 			{
-				if option::is_some(&str::find_char(" \t\r\n", input.text[pass.new_state.index - 1u]))	// little cheesy, but saves us from adding a helper fn
+				Err(failure) if !failure.is_incomplete() =>
 				{
-					result::Ok(pass)
+					record_diagnostic(failure);
+					Ok(Succeeded {new_state: input, value: default.clone()})
 				}
-				else
+				status =>
 				{
-					result::Err(Failed {old_state: input, err_state: pass.new_state, mesg: @~"whitespace"})
+					status
 				}
 			}
-		}
+		})
 	}
-	
-	fn then<U: Copy Durable>(&self, parser2: Parser<U>) -> Parser<U>
+
+	fn s0(&self) -> Parser<'a, T>
 	{
-		|input: State|
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			parser(input.clone()).and_then(|pass| skip_space(input, pass))
+		})
+	}
+
+	fn sep_by0<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
+	{
+		sep_list(self.clone(), sep, 0, SepKind::Between)
+	}
+
+	fn sep_by1<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
+	{
+		sep_list(self.clone(), sep, 1, SepKind::Between)
+	}
+
+	fn sep_end_by0<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
+	{
+		sep_list(self.clone(), sep, 0, SepKind::Trailing)
+	}
+
+	fn sep_end_by1<U: 'a>(&self, sep: Parser<'a, U>) -> Parser<'a, Vec<T>>
+	{
+		sep_list(self.clone(), sep, 1, SepKind::Trailing)
+	}
+
+	fn s1(&self) -> Parser<'a, T>
+	{
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input.clone()).and_then(|pass|
 			{
-				do result::chain_err(parser2(pass.new_state))
-					|failure| {result::Err(Failed {old_state: input, ..failure})}
-			}
-		}
+				let index = pass.new_state.index;
+				let pass = skip_space(input.clone(), pass)?;
+				if pass.new_state.index > index
+				{
+					Ok(pass)
+				}
+				else
+				{
+					Err(Failed::new(input, pass.new_state, "whitespace".to_string()))
+				}
+			})
+		})
 	}
-	
-	fn thene<U: Copy Durable>(&self, eval: fn@ (T) -> Parser<U>) -> Parser<U>
+
+	fn spanned(&self) -> Parser<'a, (T, Span)>
 	{
-		|input: State|
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
 		{
-			do result::chain((*self)(input))
-			|pass|
+			parser(input.clone()).map(|pass|
 			{
-				do result::chain_err(eval(pass.value)(pass.new_state))
-					|failure| {result::Err(Failed {old_state: input, ..failure})}
-			}
-		}
+				let span = Span {start: input.position(), end: pass.new_state.position()};
+				Succeeded {new_state: pass.new_state, value: (pass.value, span)}
+			})
+		})
+	}
+
+	fn then<U: 'a>(&self, parser2: Parser<'a, U>) -> Parser<'a, U>
+	{
+		let parser1 = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			parser1(input.clone()).and_then(|pass|
+			{
+				parser2(pass.new_state).map_err(|failure| Failed {old_state: input.clone(), ..failure})
+			})
+		})
+	}
+
+	fn thene<U: 'a>(&self, eval: impl Fn(T) -> Parser<'a, U> + 'a) -> Parser<'a, U>
+	{
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input.clone()).and_then(|pass|
+			{
+				eval(pass.value)(pass.new_state).map_err(|failure| Failed {old_state: input.clone(), ..failure})
+			})
+		})
+	}
+
+	fn with_pos(&self) -> Parser<'a, (T, Position)>
+	{
+		let parser = self.clone();
+
+		Rc::new(move |input: State<'a>|
+		{
+			parser(input.clone()).map(|pass| Succeeded {new_state: pass.new_state, value: (pass.value, input.position())})
+		})
 	}
 }
 
-impl &str : GenericParsers
+impl GenericParsers for str
 {
-	fn litv<T: Copy Durable>(&self, value: T) -> Parser<T>
+	fn litv<'a, T: Clone + 'a>(&self, value: T) -> Parser<'a, T>
 	{
-		let s = self.to_owned();
-		
-		|input: State|
+		let parser = self.lit();
+
+		Rc::new(move |input: State<'a>|
 		{
-			match s.lit()(input)
+			match parser(input)
 			{
-				result::Ok(ref pass) =>
+				Ok(pass) =>
 				{
-					result::Ok(Succeeded {new_state: pass.new_state, value: value})
+					Ok(Succeeded {new_state: pass.new_state, value: value.clone()})
 				}
-				result::Err(ref failure) =>
+				Err(failure) =>
 				{
-					result::Err(*failure)
+					Err(failure)
 				}
 			}
-		}
+		})
 	}
 }
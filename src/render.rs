@@ -0,0 +1,141 @@
+//! Renders parse errors along with the source they refer to, e.g.
+//!
+//! ```text
+//! error: expected one of ',' or ')' while parsing attribute list, found 'x'
+//!  --> test:1:4
+//!   |
+//! 1 | (a x
+//!   |    ^
+//! ```
+use crate::line_index::*;
+use crate::misc::*;
+use crate::parsers::*;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Formats a ParseFailed like rustc formats its diagnostics.
+#[derive(Clone, Debug)]
+pub struct ErrorRenderer
+{
+	color: bool,
+	context: usize,
+}
+
+impl Default for ErrorRenderer
+{
+	fn default() -> Self
+	{
+		Self::new()
+	}
+}
+
+impl ErrorRenderer
+{
+	/// Returns a renderer which doesn't use color and shows only the line with the error.
+	/// Tabs are expanded using the tab width the error's col was computed with (see
+	/// State::with_tab_width) so that the carets line up with the col.
+	pub fn new() -> ErrorRenderer
+	{
+		ErrorRenderer {color: false, context: 0}
+	}
+
+	/// If enabled ANSI escape codes are used to highlight the error.
+	pub fn color(mut self, enabled: bool) -> Self
+	{
+		self.color = enabled;
+		self
+	}
+
+	/// Number of lines before the error line to show.
+	pub fn context(mut self, lines: usize) -> Self
+	{
+		self.context = lines;
+		self
+	}
+
+	/// Text should be the input that was parsed to produce err. If err came from
+	/// parse_bytes only the message and location are rendered.
+	pub fn render(&self, err: &ParseFailed, text: &str) -> String
+	{
+		self.render_indexed(err, &LineIndex::new(text))
+	}
+
+	/// Like render except that an existing index for the text is used.
+	pub fn render_indexed(&self, err: &ParseFailed, index: &LineIndex) -> String
+	{
+		let number_width = err.line.to_string().len();
+		let gutter = repeat_char(' ', number_width);
+
+		let mut result = String::new();
+		result.push_str(&format!("{}: {}\n", self.paint(RED, "error"), self.paint(BOLD, &err.description())));
+		result.push_str(&format!("{}{} {}:{}:{}\n", gutter, self.paint(BLUE, "-->"), err.file, err.line, err.col));
+
+		let pos = match index.position(err.span.start, ColUnit::Utf8)
+		{
+			Some(pos) => pos,
+			None => return result,
+		};
+		let text = index.line(pos.line).unwrap();
+		let tab_width = err.tab_width.max(1);
+		let start = pos.col - 1;
+		let end = (start + err.span.len()).min(text.len());
+
+		result.push_str(&format!("{} {}\n", gutter, self.paint(BLUE, "|")));
+		for line in pos.line.saturating_sub(self.context).max(1)..pos.line
+		{
+			result.push_str(&self.source_line(line, number_width, index.line(line).unwrap(), tab_width));
+		}
+		result.push_str(&self.source_line(pos.line, number_width, text, tab_width));
+
+		// Carets go under the span or just past the end of the line if there is nothing to underline.
+		let indent = expand(&text[..start], 0, tab_width).chars().count();
+		let width = expand(&text[start..end], indent, tab_width).chars().count().max(1);
+		let carets = repeat_char('^', width);
+		result.push_str(&format!("{} {} {}{}\n", gutter, self.paint(BLUE, "|"), repeat_char(' ', indent), self.paint(RED, &carets)));
+		result
+	}
+
+	fn source_line(&self, number: usize, number_width: usize, line: &str, tab_width: usize) -> String
+	{
+		let number = format!("{:>1$}", number, number_width);
+		format!("{} {} {}\n", self.paint(BLUE, &number), self.paint(BLUE, "|"), expand(line, 0, tab_width))
+	}
+
+	fn paint(&self, color: &str, text: &str) -> String
+	{
+		if self.color
+		{
+			format!("{}{}{}", color, text, RESET)
+		}
+		else
+		{
+			text.to_string()
+		}
+	}
+}
+
+// Column is the display column that text starts at. Tabs are expanded the same way
+// that State.advance counts them.
+fn expand(text: &str, column: usize, tab_width: usize) -> String
+{
+	let mut result = String::with_capacity(text.len());
+	let mut column = column;
+	for ch in text.chars()
+	{
+		if ch == '\t'
+		{
+			let count = tab_width - column % tab_width;
+			result.push_str(&repeat_char(' ', count));
+			column += count;
+		}
+		else
+		{
+			result.push(ch);
+			column += 1;
+		}
+	}
+	result
+}
@@ -0,0 +1,168 @@
+use crate::byte_parsers::*;
+use crate::cst::*;
+use crate::grammar::*;
+use crate::parsers::*;
+use crate::tests::test_helpers::*;
+use crate::types::*;
+
+#[derive(Debug, PartialEq)]
+struct Chunk<'a> {kind: &'a [u8], data: &'a [u8]}
+
+#[test]
+fn test_take()
+{
+	let p = take(3);
+
+	assert!(check_ok(&p(bytes_state(b"\x00\x01\x02")), &&b"\x00\x01\x02"[..]));
+	assert!(check_ok(&p(bytes_state(b"abcd")), &&b"abc"[..]));
+	assert!(check_failed(&p(bytes_state(b"ab")), "3 bytes", 1));
+	assert!(check_ok(&take(0)(bytes_state(b"")), &&b""[..]));
+}
+
+#[test]
+fn test_magic()
+{
+	let p = magic(b"\x89PNG");
+
+	assert!(check_ok(&p(bytes_state(b"\x89PNG\r\n")), &&b"\x89PNG"[..]));
+	assert!(check_failed(&p(bytes_state(b"\x89PNx")), "magic number 89 50 4E 47", 1));
+	assert!(check_failed(&p(bytes_state(b"\x89P")), "magic number 89 50 4E 47", 1));
+
+	let result = p(bytes_state(b"\x89PNx"));
+	assert_eq!(result.unwrap_err().err_state.index, 3);
+}
+
+#[test]
+fn test_anyb()
+{
+	let p = anyb(|b| b < 0x80);
+
+	assert!(check_ok(&p(bytes_state(b"\x7F")), &0x7F));
+	assert!(check_failed(&p(bytes_state(b"\x80")), "", 1));
+	assert!(check_failed(&p(bytes_state(b"")), "", 1));
+}
+
+#[test]
+fn test_integers()
+{
+	let data = b"\x01\x02\x03\x04\x05\x06\x07\x08";
+
+	assert!(check_ok(&u8()(bytes_state(data)), &0x01));
+	assert!(check_ok(&i8()(bytes_state(b"\xFF")), &-1));
+	assert!(check_ok(&be_u16()(bytes_state(data)), &0x0102));
+	assert!(check_ok(&le_u16()(bytes_state(data)), &0x0201));
+	assert!(check_ok(&be_i16()(bytes_state(b"\xFF\xFE")), &-2));
+	assert!(check_ok(&le_i16()(bytes_state(b"\xFE\xFF")), &-2));
+	assert!(check_ok(&be_u32()(bytes_state(data)), &0x01020304));
+	assert!(check_ok(&le_u32()(bytes_state(data)), &0x04030201));
+	assert!(check_ok(&be_i32()(bytes_state(b"\xFF\xFF\xFF\xFF")), &-1));
+	assert!(check_ok(&le_i32()(bytes_state(b"\xFE\xFF\xFF\xFF")), &-2));
+	assert!(check_ok(&be_u64()(bytes_state(data)), &0x0102030405060708));
+	assert!(check_ok(&le_u64()(bytes_state(data)), &0x0807060504030201));
+	assert!(check_ok(&be_i64()(bytes_state(b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFE")), &-2));
+	assert!(check_ok(&le_i64()(bytes_state(b"\xFE\xFF\xFF\xFF\xFF\xFF\xFF\xFF")), &-2));
+
+	assert!(check_failed(&be_u32()(bytes_state(b"\x01\x02\x03")), "big-endian u32", 1));
+	assert!(check_failed(&le_u64()(bytes_state(b"")), "little-endian u64", 1));
+}
+
+#[test]
+fn test_length_data()
+{
+	let p = length_data(u8());
+
+	assert!(check_ok(&p(bytes_state(b"\x03abcd")), &&b"abc"[..]));
+	assert!(check_ok(&p(bytes_state(b"\x00abcd")), &&b""[..]));
+	assert!(check_failed(&p(bytes_state(b"\x05abc")), "5 bytes", 1));
+	assert!(check_failed(&p(bytes_state(b"")), "u8", 1));
+
+	let p = length_data(be_u16());
+	assert!(check_ok(&p(bytes_state(b"\x00\x02xyz")), &&b"xy"[..]));
+}
+
+#[test]
+fn test_length_value()
+{
+	let p = length_value(u8(), be_u16());
+
+	// The unread byte within the field is skipped.
+	let result = p(bytes_state(b"\x03\x01\x02\x03\x04"));
+	assert!(check_ok(&result, &0x0102));
+	assert_eq!(result.unwrap().new_state.index, 4);
+
+	// The inner parser can't read past the field.
+	assert!(check_failed(&p(bytes_state(b"\x01\x01\x02")), "big-endian u16", 1));
+	assert!(check_failed(&p(bytes_state(b"\x04\x01\x02")), "4 bytes", 1));
+
+	let p = seq2(length_value(u8(), be_u16()), u8(), |a, b| Ok((a, b)));
+	assert!(check_ok(&p(bytes_state(b"\x02\x01\x02\x09")), &(0x0102, 0x09)));
+}
+
+#[test]
+fn test_bits()
+{
+	let p = bits(&[4, 4, 3, 13]);
+
+	// 0x45 is an IPv4 version and header length, 0x4000 is the don't fragment flag.
+	assert!(check_ok(&p(bytes_state(b"\x45\x40\x00\xFF")), &vec![4, 5, 2, 0]));
+	assert!(check_ok(&p(bytes_state(b"\xFF\x1F\xFF")), &vec![15, 15, 0, 0x1FFF]));
+	assert!(check_failed(&p(bytes_state(b"\x45\x40")), "4 bit fields", 1));
+
+	let p = bits(&[64]);
+	assert!(check_ok(&p(bytes_state(b"\x80\x00\x00\x00\x00\x00\x00\x01")), &vec![0x8000000000000001]));
+}
+
+#[test]
+fn test_composition()
+{
+	// chunk := length kind data
+	let chunk = be_u32().thene(|len| seq2(take(4), take(len as usize), |kind, data| Ok(Chunk {kind, data})));
+	let chunks = magic(b"\x89PNG").then(chunk.r0());
+
+	let data = b"\x89PNG\x00\x00\x00\x02IHDRab\x00\x00\x00\x00IEND";
+	assert!(check_ok(&chunks(bytes_state(data)), &vec![Chunk {kind: b"IHDR", data: b"ab"}, Chunk {kind: b"IEND", data: b""}]));
+
+	let tag = magic(b"v1").or(magic(b"v2"));
+	assert!(check_ok(&tag(bytes_state(b"v2")), &&b"v2"[..]));
+	assert!(check_failed(&tag(bytes_state(b"v3")), "magic number 76 31 or magic number 76 32", 1));
+
+	let version = u8().optional();
+	assert!(check_ok(&version(bytes_state(b"")), &None));
+
+	let p = u8().r(2, 3);
+	assert!(check_ok(&p(bytes_state(b"\x01\x02\x03")), &vec![1, 2, 3]));
+	assert!(check_failed(&p(bytes_state(b"\x01")), "", 1));
+}
+
+#[test]
+fn test_parse_bytes()
+{
+	let p = be_u16().everything(ret(0));
+
+	assert_eq!(p.parse_bytes("test", b"\x12\x34"), Ok(0x1234));
+	let err = p.parse_bytes("test", b"\x12\x34\x56").unwrap_err();
+	assert_eq!((err.col, err.mesg.as_str(), err.found.as_deref()), (3, "EOT", Some("0x56")));
+	assert_eq!(err.to_string(), "test:1:3: expected EOT, found 0x56");
+
+	// Text parsers see no input when parsing binary data.
+	let p = u8().then("x".lit());
+	assert!(p.parse_bytes("test", b"\x01x").is_err());
+}
+
+#[test]
+fn test_binary_text_slices()
+{
+	// Parsers which return slices of text fail (instead of panicking) on binary input,
+	// even when it is empty.
+	assert!(State::from_bytes("test", b"").is_binary());
+	assert!(!State::new("test", "").is_binary());
+
+	let grammar = Grammar::new("test", "a := x:'y'?").unwrap();
+	let p = grammar.parser("a");
+	assert!(p.parse_bytes("test", b"").is_err());
+	assert!(p.parse("test", "").is_ok());
+
+	let p = take(2).token("data").node("chunk");
+	assert_eq!(p.parse_bytes("test", b"ab").unwrap_err().mesg, "text input");
+	assert_eq!(p.parse_bytes("test", b"").unwrap_err().mesg, "text input");
+}
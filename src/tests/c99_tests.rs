@@ -1,105 +1,291 @@
-use c99_parsers::*;
-use tests::test_helpers::*;
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::seq;
+use crate::tests::test_helpers::*;
 
 #[test]
 fn test_identifier()
 {
 	let p = identifier();
-	assert check_str_ok("hey", p, "hey");
-	assert check_str_ok("hey_there", p, "hey_there");
-	assert check_str_ok("hey there", p, "hey");
-	assert check_str_ok("spanky123xy", p, "spanky123xy");
-	assert check_str_failed("", p, "", 1);
+	assert!(check_str_ok("hey", &p, "hey"));
+	assert!(check_str_ok("hey_there", &p, "hey_there"));
+	assert!(check_str_ok("hey there", &p, "hey"));
+	assert!(check_str_ok("spanky123xy", &p, "spanky123xy"));
+	assert!(check_str_failed("", &p, "", 1));
 }
 
 #[test]
 fn test_decimal_number()
 {
 	let p = decimal_number();
-	
-	assert check_int_ok("1", p, 1);
-	assert check_int_ok("123", p, 123);
-	assert check_int_ok("123x", p, 123);
-	assert check_int_failed("+78", p, "", 1);
-	assert check_int_failed("", p, "", 1);
-	assert check_int_failed("in", p, "", 1);
+
+	assert!(check_int_ok("1", &p, 1));
+	assert!(check_int_ok("123", &p, 123));
+	assert!(check_int_ok("123x", &p, 123));
+	assert!(check_int_failed("+78", &p, "", 1));
+	assert!(check_int_failed("", &p, "", 1));
+	assert!(check_int_failed("in", &p, "", 1));
 }
 
 #[test]
 fn test_octal_number()
 {
 	let p = octal_number();
-	
-	assert check_int_ok("01", p, 1);
-	assert check_int_ok("010", p, 8);
-	assert check_int_ok("012", p, 10);
-	assert check_int_failed("1", p, "", 1);
-	assert check_int_failed("in", p, "", 1);
-	assert check_int_failed("0777777777777777777777777", p, "Octal number is too large", 1);
+
+	assert!(check_int_ok("01", &p, 1));
+	assert!(check_int_ok("010", &p, 8));
+	assert!(check_int_ok("012", &p, 10));
+	assert!(check_int_failed("1", &p, "", 1));
+	assert!(check_int_failed("in", &p, "", 1));
+	assert!(check_int_failed("0777777777777777777777777", &p, "'0777777777777777777777777' is out of range for i64", 1));
 }
 
 #[test]
 fn test_hex_number()
 {
 	let p = hex_number();
-	
-	assert check_int_ok("0x2", p, 2);
-	assert check_int_ok("0xa", p, 10);
-	assert check_int_ok("0xF", p, 15);
-	assert check_int_ok("0x10", p, 16);
-	assert check_int_ok("0xff", p, 255);
-	assert check_int_ok("0X80", p, 128);
-	assert check_int_failed("1", p, "'0'", 1);
-	assert check_int_failed("0xx", p, "", 1);
+
+	assert!(check_int_ok("0x2", &p, 2));
+	assert!(check_int_ok("0xa", &p, 10));
+	assert!(check_int_ok("0xF", &p, 15));
+	assert!(check_int_ok("0x10", &p, 16));
+	assert!(check_int_ok("0xff", &p, 255));
+	assert!(check_int_ok("0X80", &p, 128));
+	assert!(check_int_failed("1", &p, "'0'", 1));
+	assert!(check_int_failed("0xx", &p, "", 1));
 }
 
 #[test]
 fn test_float_number()
 {
 	let p = float_number();
-	
-	assert check_float_ok("0.1", p, 0.1f64);
-	assert check_float_ok("0.1e2", p, 10.0f64);
-	assert check_float_ok("0.1e-1", p, 0.01f64);
-	assert check_float_ok("2.", p, 2.0f64);
-	assert check_float_ok("2.e3", p, 2000.0f64);
-	assert check_float_ok("1e3", p, 1000.0f64);
-	assert check_float_failed("x", p, "", 1);
-	assert check_float_failed("0", p, "", 1);
-	assert check_float_failed("0x.0", p, "", 1);
+
+	assert!(check_float_ok("0.1", &p, 0.1f64));
+	assert!(check_float_ok("0.1e2", &p, 10.0f64));
+	assert!(check_float_ok("0.1e-1", &p, 0.01f64));
+	assert!(check_float_ok("2.", &p, 2.0f64));
+	assert!(check_float_ok("2.e3", &p, 2000.0f64));
+	assert!(check_float_ok("1e3", &p, 1000.0f64));
+	assert!(check_float_failed("x", &p, "", 1));
+	assert!(check_float_failed("0", &p, "", 1));
+	assert!(check_float_failed("0x.0", &p, "", 1));
 }
 
 #[test]
 fn test_char_literal()
 {
 	let p = char_literal();
-	
-	assert check_char_ok("'x'", p, 'x');
-	assert check_char_ok("'\\n'", p, '\n');
-	assert check_char_ok("'\\52'", p, '*');
-	assert check_char_ok("'\\x2A'", p, '*');
-	assert check_char_ok("'\\u002A'", p, '*');
-	assert check_char_failed("'\\q'", p, "escape character", 1);
-	assert check_char_failed("'xx'", p, "'''", 1);
+
+	assert!(check_char_ok("'x'", &p, 'x'));
+	assert!(check_char_ok("'\\n'", &p, '\n'));
+	assert!(check_char_ok("'\\52'", &p, '*'));
+	assert!(check_char_ok("'\\x2A'", &p, '*'));
+	assert!(check_char_ok("'\\u00E9'", &p, 'é'));
+	assert!(check_char_ok("'\\U0001F600'", &p, '😀'));
+	assert!(check_char_ok("'\\b'", &p, '\x08'));
+	assert!(check_char_failed("'\\q'", &p, "invalid escape sequence '\\q'", 1));
+	assert!(check_char_failed("'\\u002A'", &p, "'\\u002A' is not a valid universal character name", 1));
+	assert!(check_char_failed("'\\uD800'", &p, "'\\uD800' is not a valid universal character name", 1));
+	assert!(check_char_failed("'\\u12'", &p, "\\u needs 4 hex digits", 1));
+	assert!(check_char_failed("'\\x110000'", &p, "'\\x110000' is out of range", 1));
+	assert!(check_char_failed("'xx'", &p, "'''", 1));
 }
 
 #[test]
 fn test_string_literal()
 {
 	let p = string_literal();
-	
-	assert check_str_ok("\"\"", p, "");
-	assert check_str_ok("\"xyz\"", p, "xyz");
-	assert check_str_ok("\"a\\nx\"", p, "a\nx");
-	assert check_str_failed("\"xx", p, "'\"'", 1);
+
+	assert!(check_str_ok("\"\"", &p, ""));
+	assert!(check_str_ok("\"xyz\"", &p, "xyz"));
+	assert!(check_str_ok("\"a\\nx\"", &p, "a\nx"));
+	assert!(check_str_failed("\"xx", &p, "'\"'", 1));
+	assert!(check_str_ok("\"\\1234\"", &p, "S4"));					// octal escapes have at most 3 digits
+	assert!(check_str_ok("\"\\x4A\\0\"", &p, "J\0"));
 }
 
 #[test]
 fn test_comment()
 {
 	let p = comment();
-	
-	assert check_str_ok("/**/", p, "");
-	assert check_str_ok("/* blah */", p, " blah ");
-	assert check_str_failed("/* xxx\nyyy\nzz", p, "'*/'", 1);
+
+	assert!(check_str_ok("/**/", &p, ""));
+	assert!(check_str_ok("/* blah */", &p, " blah "));
+	assert!(check_str_failed("/* xxx\nyyy\nzz", &p, "'*/'", 1));
+}
+
+#[test]
+fn test_c99_tokens()
+{
+	let p = c99_tokens();
+	let text = "int x/* c */= a->b >>= 0x1Fu + 1.5e3f; // end\nchar *s = L\"hi\";";
+	let tokens = p.parse("test", text).unwrap();
+	let kinds: Vec<(TokenKind, &str)> = tokens.iter().map(|t| (t.kind, t.text)).collect();
+	assert_eq!(kinds, vec![
+		(TokenKind::Keyword, "int"),
+		(TokenKind::Identifier, "x"),
+		(TokenKind::Comment, "/* c */"),
+		(TokenKind::Punctuator, "="),
+		(TokenKind::Identifier, "a"),
+		(TokenKind::Punctuator, "->"),
+		(TokenKind::Identifier, "b"),
+		(TokenKind::Punctuator, ">>="),
+		(TokenKind::Constant, "0x1Fu"),
+		(TokenKind::Punctuator, "+"),
+		(TokenKind::Constant, "1.5e3f"),
+		(TokenKind::Punctuator, ";"),
+		(TokenKind::Comment, "// end"),
+		(TokenKind::Keyword, "char"),
+		(TokenKind::Punctuator, "*"),
+		(TokenKind::Identifier, "s"),
+		(TokenKind::Punctuator, "="),
+		(TokenKind::StringLiteral, "L\"hi\""),
+		(TokenKind::Punctuator, ";"),
+	]);
+	assert!(tokens[2].is_trivia());
+	assert_eq!(tokens[13].span.start.line, 2);
+	assert_eq!(tokens[13].span.range(), 46..50);
+
+	// Maximal munch and digraphs.
+	let texts = |text| p.parse("test", text).unwrap().iter().map(|t| t.text).collect::<Vec<_>>();
+	assert_eq!(texts("a+++++b"), vec!["a", "++", "++", "+", "b"]);
+	assert_eq!(texts("f(...)..x"), vec!["f", "(", "...", ")", ".", ".", "x"]);
+	assert_eq!(texts("<: :> <% %> %:%: %:"), vec!["<:", ":>", "<%", "%>", "%:%:", "%:"]);
+	assert_eq!(texts(".5 0x1.8p3 'a' L'\\n' 07 10ULL integer"), vec![".5", "0x1.8p3", "'a'", "L'\\n'", "07", "10ULL", "integer"]);
+	assert_eq!(texts("a \\\n b"), vec!["a", "b"]);
+}
+
+#[test]
+fn test_c99_token_errors()
+{
+	let p = c99_tokens();
+	let err = p.parse("test", "x = 12ab;").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:7: invalid suffix 'ab' on integer constant while parsing constant");
+
+	let err = p.parse("test", "x @ y").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: expected one of token or EOT, found '@'");
+
+	let err = p.parse("test", "x /* y").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:5: expected '*/', found ' '");
+}
+
+#[test]
+fn test_integer_constant()
+{
+	let p = integer_constant();
+	let value = |text| match p.parse("test", text)
+	{
+		Ok(Constant::Integer {value, ty}) => (value, ty),
+		result => panic!("{}: {:?}", text, result),
+	};
+
+	assert_eq!(value("0"), (0, IntegerType::Int));
+	assert_eq!(value("017"), (15, IntegerType::Int));
+	assert_eq!(value("2147483647"), (2147483647, IntegerType::Int));
+	assert_eq!(value("2147483648"), (2147483648, IntegerType::Long));
+	assert_eq!(value("0x80000000"), (0x80000000, IntegerType::UnsignedInt));
+	assert_eq!(value("0xFFFFFFFFFFFFFFFF"), (u64::MAX, IntegerType::UnsignedLong));
+	assert_eq!(value("10u"), (10, IntegerType::UnsignedInt));
+	assert_eq!(value("10L"), (10, IntegerType::Long));
+	assert_eq!(value("10lu"), (10, IntegerType::UnsignedLong));
+	assert_eq!(value("10ll"), (10, IntegerType::LongLong));
+	assert_eq!(value("18446744073709551615ULL"), (u64::MAX, IntegerType::UnsignedLongLong));
+	assert_eq!(IntegerType::Long.max_value(), i64::MAX as u64);
+	assert_eq!(IntegerType::UnsignedInt.max_value(), u32::MAX as u64);
+
+	let err = p.parse("test", "9223372036854775808").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:20: '9223372036854775808' is too large");
+	let err = p.parse("test", "18446744073709551616u").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:22: '18446744073709551616' is too large");
+	let err = p.parse("test", "10lL").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: invalid suffix 'lL' on integer constant");
+	let err = p.parse("test", "09").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: invalid digit '9' in octal constant");
+}
+
+#[test]
+fn test_floating_constant()
+{
+	let p = floating_constant();
+	let value = |text| match p.parse("test", text)
+	{
+		Ok(Constant::Floating {value, ty}) => (value, ty),
+		result => panic!("{}: {:?}", text, result),
+	};
+
+	assert_eq!(value("1.5"), (1.5, FloatType::Double));
+	assert_eq!(value("1e3L"), (1000.0, FloatType::LongDouble));
+	assert_eq!(value("0.1f"), (0.1f32 as f64, FloatType::Float));
+	assert_eq!(value("0x1.8p3"), (12.0, FloatType::Double));
+	assert_eq!(value("0X.8P-1f"), (0.25, FloatType::Float));
+	assert_eq!(value("0xAp0"), (10.0, FloatType::Double));
+	assert_eq!(value("0x1.fffffffffffffp1023"), (f64::MAX, FloatType::Double));
+	assert_eq!(value("0x1p-1074"), (f64::from_bits(1), FloatType::Double));
+	assert_eq!(value("0x1.000001p0f"), (1.0, FloatType::Float));				// ties to even
+	assert_eq!(value("0x1.0000011p0f"), (1.0 + f32::EPSILON as f64, FloatType::Float));
+	assert_eq!(value("0x1.00000000000008000000000000000000001p0"), (1.0 + f64::EPSILON, FloatType::Double));
+
+	let err = p.parse("test", "1e999").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:6: '1e999' is out of range");
+	let err = p.parse("test", "1e39f").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:6: '1e39' is out of range");
+	let err = p.parse("test", "1.5u").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:4: invalid suffix 'u' on floating constant");
+	assert!(p.parse("test", "0x1.8").is_err());
+}
+
+#[test]
+fn test_c_char_constant()
+{
+	let p = c_char_constant();
+	let value = |text| p.parse("test", text).map(|c| (c.encoding, c.value));
+
+	assert_eq!(value("'a'"), Ok((Encoding::Plain, 97)));
+	assert_eq!(value("'\\377'"), Ok((Encoding::Plain, -1)));
+	assert_eq!(value("'ab'"), Ok((Encoding::Plain, 0x6162)));
+	assert_eq!(value("'é'"), Ok((Encoding::Plain, 0xC3A9)));
+	assert_eq!(value("L'\\xFFFFFFFF'"), Ok((Encoding::Wide, -1)));
+	assert_eq!(value("u'é'"), Ok((Encoding::Utf16, 0xE9)));
+	assert_eq!(value("U'\\U0001F600'"), Ok((Encoding::Utf32, 0x1F600)));
+	assert_eq!(value("u8'a'"), Ok((Encoding::Utf8, 97)));
+
+	let err = p.parse("test", "''").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:2: empty character constant");
+	let err = p.parse("test", "'abcde'").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:2: character constant is too long");
+	let err = p.parse("test", "u'\\U0001F600'").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: character constant must be a single code unit");
+	let err = p.parse("test", "'\\x100'").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: '\\x100' is out of range");
+	let err = p.parse("test", "u'\\x10000'").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:4: '\\x10000' is out of range");
+}
+
+#[test]
+fn test_c_string_literal()
+{
+	let p = c_string_literal();
+	let units = |text| p.parse("test", text).map(|s| (s.encoding, s.units));
+
+	assert_eq!(units("\"\""), Ok((Encoding::Plain, vec![])));
+	assert_eq!(units("\"é\""), Ok((Encoding::Plain, vec![0xC3, 0xA9])));
+	assert_eq!(units("u\"é\\U0001F600\""), Ok((Encoding::Utf16, vec![0xE9, 0xD83D, 0xDE00])));
+	assert_eq!(units("\"\\x1\" \"2\""), Ok((Encoding::Plain, vec![1, b'2' as u32])));
+
+	// The prefixed encoding is used for the whole string.
+	let s = p.parse("test", "\"a\\x100\"\n  L\"b\"").unwrap();
+	assert_eq!(s, StringLiteral {encoding: Encoding::Wide, units: vec![97, 0x100, 98]});
+	assert_eq!(s.to_string_lossy(), "a\u{100}b");
+	assert_eq!(p.parse("test", "u8\"a\" \"é\"").unwrap().to_string_lossy(), "aé");
+
+	// Trailing space isn't consumed.
+	let p2 = seq!(c_string_literal(), skip " ;".lit());
+	assert!(p2.parse("test", "\"a\" ;").is_ok());
+
+	let err = p.parse("test", "u\"a\" L\"b\"").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:6: can't concatenate u and L string literals");
+	let err = p.parse("test", "\"a\" \"\\c\"").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:7: invalid escape sequence '\\c'");
+	let err = p.parse("test", "\"\\x100\"").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: '\\x100' is out of range");
 }
@@ -0,0 +1,90 @@
+use crate::c99_parsers::*;
+use crate::cst::*;
+use crate::misc::*;
+use crate::parsers::*;
+use crate::types::*;
+use crate::{alt, seq};
+
+// space := ([ \t\r\n] | comment | line_comment)*
+fn space<'a>() -> Parser<'a, Vec<&'a str>>
+{
+	alt!(match1(is_whitespace), comment(), line_comment()).r0()
+}
+
+// file := space stmt* EOT
+// stmt := identifier '=' expr ';'
+// expr := number ('+' number)*
+fn file<'a>() -> Parser<'a, Cst<'a>>
+{
+	let number = seq!(decimal_number().token("number"), skip space());
+	let plus = seq!("+".lit().token("plus"), skip space());
+	let expr = seq!(number.clone(), seq!(plus, number).r0()).node("expr");
+
+	let name = seq!(identifier().token("name"), skip space());
+	let assign = seq!("=".lit().token("assign"), skip space());
+	let semi = seq!(";".lit().token("semi"), skip space());
+	let stmt = seq!(name, assign, expr, semi).node("stmt");
+
+	stmt.r0().everything(space()).node("file")
+}
+
+#[test]
+fn test_round_trip()
+{
+	let p = file();
+	for text in ["", "  ", "x=1;", " x = 1 + 2 ;\n", "/* a */ x = 1 // b\n  + 2; // c\r\n\ty=3;\t"]
+	{
+		let tree = p.parse("test", text).unwrap();
+		assert_eq!(tree.to_string(), text);
+		assert_eq!(tree.span(), 0..text.len());
+	}
+}
+
+#[test]
+fn test_structure()
+{
+	let tree = file().parse("test", " x = 1 + 2;").unwrap();
+	assert_eq!(tree.kind(), "file");
+
+	let kinds: Vec<&str> = tree.children().iter().map(|c| c.kind()).collect();
+	assert_eq!(kinds, vec!["", "stmt"]);
+	assert_eq!(tree.children()[0], Cst::Trivia {offset: 0, text: " "});
+
+	let stmt = &tree.children()[1];
+	let kinds: Vec<&str> = stmt.children().iter().map(|c| c.kind()).collect();
+	assert_eq!(kinds, vec!["name", "", "assign", "", "expr", "semi"]);
+
+	let expr = &stmt.children()[4];
+	assert_eq!(expr.span(), 5..10);
+	assert_eq!(expr.to_string(), "1 + 2");
+
+	let tokens: Vec<String> = tree.tokens().iter().map(|t| format!("{}:{}", t.kind(), t)).collect();
+	assert_eq!(tokens, vec!["name:x", "assign:=", "number:1", "plus:+", "number:2", "semi:;"]);
+}
+
+#[test]
+fn test_trivia()
+{
+	// Trivia can also be explicit.
+	let p = seq!(match0(is_whitespace).trivia(), "a".lit().token("a"));
+	let (space, a) = p.parse("test", "  a").unwrap();
+	assert_eq!(space, Cst::Trivia {offset: 0, text: "  "});
+	assert_eq!(a.span(), 2..3);
+
+	// Values that aren't Cst become trivia.
+	let p = seq!("a".lit().token("a"), skip ",".lit(), "b".lit().token("b")).node("pair");
+	let tree = p.parse("test", "a,b").unwrap();
+	assert_eq!(tree.children()[1], Cst::Trivia {offset: 1, text: ","});
+}
+
+#[test]
+fn test_backtracking()
+{
+	// Children from alternatives that failed aren't included.
+	let long = seq!("a".lit().token("a"), "b".lit().token("b"), "c".lit().token("c")).node("long");
+	let short = seq!("a".lit().token("a"), "b".lit().token("b")).node("short");
+	let tree = long.or(short).node("root").parse("test", "ab").unwrap();
+	assert_eq!(tree.children().len(), 1);
+	assert_eq!(tree.children()[0].kind(), "short");
+	assert_eq!(tree.children()[0].children().len(), 2);
+}
@@ -0,0 +1,114 @@
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::Parse;
+
+#[derive(Debug, PartialEq, Parse)]
+#[rparse("'<' name attributes content")]
+struct Element<'a>
+{
+	#[rparse(parser = "identifier()")]
+	name: &'a str,
+	attributes: Vec<Attribute<'a>>,
+	content: Content<'a>,
+}
+
+#[derive(Debug, PartialEq, Parse)]
+#[rparse("name '=' value")]
+struct Attribute<'a>
+{
+	#[rparse(parser = "identifier()")]
+	name: &'a str,
+	#[rparse(parser = "string_literal()")]
+	value: String,
+}
+
+#[derive(Debug, PartialEq, Parse)]
+enum Content<'a>
+{
+	#[rparse("'/>'")]
+	Empty,
+
+	#[rparse("'>' 0 '</' 1 '>'")]
+	Children(Vec<Element<'a>>, #[rparse(parser = "identifier()")] &'a str),
+}
+
+#[derive(Debug, PartialEq, Parse)]
+#[rparse(label = "value")]
+enum Value
+{
+	#[rparse("'true'")]
+	True,
+
+	#[rparse("'false'")]
+	False,
+
+	Number(#[rparse(parser = "decimal_number()")] i64),
+
+	#[rparse("'-' 0")]
+	Negate(Box<Value>),
+
+	#[rparse("'[' 0 ']'")]
+	List(#[rparse(sep = ",")] Vec<Value>),
+}
+
+#[test]
+fn test_struct()
+{
+	let p = Attribute::parser();
+	assert_eq!(p.parse("test", "x = \"1\""), Ok(Attribute {name: "x", value: "1".to_string()}));
+
+	let err = p.parse("test", "x \"1\"").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: expected '=' while parsing attribute, found '\"'");
+}
+
+#[test]
+fn test_recursive()
+{
+	let p = Element::parser();
+	let element = p.parse("test", "<a x=\"1\">  <b/> <c y=\"2\" z=\"3\"/> </a>").unwrap();
+	assert_eq!(element.name, "a");
+	assert_eq!(element.attributes.len(), 1);
+
+	let children = match element.content
+	{
+		Content::Children(children, end) => {assert_eq!(end, "a"); children}
+		Content::Empty => panic!("expected children"),
+	};
+	assert_eq!(children.len(), 2);
+	assert_eq!(children[0].content, Content::Empty);
+	assert_eq!(children[1].attributes[1], Attribute {name: "z", value: "3".to_string()});
+
+	let err = p.parse("test", "<a><b/></a").unwrap_err();
+	assert_eq!(err.col, 11);
+}
+
+#[test]
+fn test_enum()
+{
+	let p = Value::parser();
+	assert_eq!(p.parse("test", "true"), Ok(Value::True));
+	assert_eq!(p.parse("test", "- 12"), Ok(Value::Negate(Box::new(Value::Number(12)))));
+	assert_eq!(p.parse("test", "[1, false, []]"), Ok(Value::List(vec![Value::Number(1), Value::False, Value::List(vec![])])));
+
+	let err = p.parse("test", "x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: expected value, found 'x'");
+
+	// Like sep_by0 the trailing separator isn't consumed.
+	let err = p.parse("test", "[1, x]").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: expected ']' while parsing value, found ','");
+}
+
+#[test]
+fn test_option()
+{
+	#[derive(Debug, PartialEq, Parse)]
+	#[rparse("'f' '(' arg ')'")]
+	struct Call
+	{
+		arg: Option<Value>,
+	}
+
+	let p = Call::parser();
+	assert_eq!(p.parse("test", "f()"), Ok(Call {arg: None}));
+	assert_eq!(p.parse("test", "f ( true )"), Ok(Call {arg: Some(Value::True)}));
+}
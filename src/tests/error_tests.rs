@@ -0,0 +1,85 @@
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::tests::test_helpers::*;
+use crate::types::*;
+
+// attrs := '(' identifier (',' | ')')
+fn attrs<'a>() -> Parser<'a, &'a str>
+{
+	"(".lit().then(identifier().s0()).then(",".lit().or(")".lit())).err("attribute list")
+}
+
+#[test]
+fn test_expected()
+{
+	let p = "a".lit().or("b".lit()).or("a".lit());
+	let err = p.parse("test", "x").unwrap_err();
+	assert_eq!(err.expected, vec!["'a'".to_string(), "'b'".to_string()]);
+	assert_eq!(err.mesg, "'a' or 'b'");
+
+	// Only the alternatives that got furthest count.
+	let p = or_v(vec!["a".lit().then("c".lit()), "b".lit(), "ab".lit(), "ac".lit()]);
+	let err = p.parse("test", "ax").unwrap_err();
+	assert_eq!(err.expected, vec!["'c'".to_string(), "'ab'".to_string(), "'ac'".to_string()]);
+	assert_eq!(err.col, 2);
+
+	// Messages that aren't token lists are still joined.
+	let p = "a".lit().err("letter a").or(fails("no b"));
+	let err = p.parse("test", "x").unwrap_err();
+	assert_eq!(err.expected, vec!["letter a".to_string()]);
+	assert_eq!(err.mesg, "letter a or no b");
+}
+
+#[test]
+fn test_found_and_span()
+{
+	let err = attrs().parse("test", "(a é").unwrap_err();
+	assert_eq!(err.found, Some("é".to_string()));
+	assert_eq!(err.span, 3..5);
+
+	let err = attrs().parse("test", "(a").unwrap_err();
+	assert_eq!(err.found, None);
+	assert_eq!(err.span, 2..2);
+}
+
+#[test]
+fn test_context()
+{
+	// The label becomes context once the parser has made progress.
+	let err = attrs().parse("test", "(a x").unwrap_err();
+	assert_eq!(err.expected, vec!["','".to_string(), "')'".to_string()]);
+	assert_eq!(err.context, vec!["attribute list".to_string()]);
+
+	// Otherwise it's what was expected.
+	let err = attrs().parse("test", "x").unwrap_err();
+	assert_eq!(err.expected, vec!["attribute list".to_string()]);
+	assert!(err.context.is_empty());
+
+	// Context is innermost first.
+	let p = "[".lit().then(attrs()).err("element");
+	let err = p.parse("test", "[(a x").unwrap_err();
+	assert_eq!(err.context, vec!["attribute list".to_string(), "element".to_string()]);
+}
+
+#[test]
+fn test_display()
+{
+	let err = attrs().parse("test", "(a x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:4: expected one of ',' or ')' while parsing attribute list, found 'x'");
+
+	let err = attrs().parse("test", "(a").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: expected one of ',' or ')' while parsing attribute list, found end of input");
+
+	let err = fails::<i32>("bad value").parse("test", "x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: bad value");
+}
+
+#[test]
+fn test_failed()
+{
+	let p = "a".lit();
+	let failure = p(unit_state("x")).unwrap_err();
+	assert_eq!(failure.expected, vec!["'a'".to_string()]);
+	assert_eq!(failure.found(), Some("x".to_string()));
+	assert_eq!(failure.span(), 0..1);
+}
@@ -1,69 +1,76 @@
-use misc::*;
-use parsers::*;
-use tests::test_helpers::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::misc::*;
+use crate::parsers::*;
+use crate::tests::test_helpers::*;
+use crate::types::*;
+use crate::{alt, seq, seq_str};
 
 // Usually these would be written using then, but we are using this
-// to test then and don't want to confuse things by testing then 
+// to test then and don't want to confuse things by testing then
 // multiple times for each input string.
-fn parse_unary() -> Parser<char>
+fn parse_unary<'a>() -> Parser<'a, char>
 {
-	|input: State|
+	Rc::new(|input: State<'a>|
 	{
-		let ch = input.text[input.index];
-		if ch == '-' || ch == '+'
-		{
-			result::Ok(Succeeded {new_state: State {index: input.index + 1u ,.. input}, value: ch})
-		}
-		else
+		match input.peek()
 		{
-			result::Err(Failed {old_state: input, err_state: State {index: input.index ,.. input}, mesg: @~"'-' or '+'"})
+			Some(ch) if ch == '-' || ch == '+' =>
+			{
+				Ok(Succeeded {new_state: State {index: input.index + 1, ..input}, value: ch})
+			}
+			_ =>
+			{
+				Err(Failed::new(input.clone(), input, "'-' or '+'".to_string()))
+			}
 		}
-	}
+	})
 }
 
-fn parse_digit() -> Parser<int>
+fn parse_digit<'a>() -> Parser<'a, i64>
 {
-	|input: State|
+	Rc::new(|input: State<'a>|
 	{
-		let ch = input.text[input.index];
-		if ch >= '0' && ch <= '9'
-		{
-			let value = option::get(char::to_digit(ch, 10u)) as int;
-			result::Ok(Succeeded {new_state: State {index: input.index + 1u ,.. input}, value: value})
-		}
-		else
+		match input.peek()
 		{
-			result::Err(Failed {old_state: input, err_state: State {index: input.index ,.. input}, mesg: @~"digit"})
+			Some(ch) if ch >= '0' && ch <= '9' =>
+			{
+				let value = ch.to_digit(10).unwrap() as i64;
+				Ok(Succeeded {new_state: State {index: input.index + 1, ..input}, value})
+			}
+			_ =>
+			{
+				Err(Failed::new(input.clone(), input, "digit".to_string()))
+			}
 		}
-	}
+	})
 }
 
-fn parse_num(op: char) -> Parser<int>
+fn parse_num<'a>(op: char) -> Parser<'a, i64>
 {
-	|input: State|
+	Rc::new(move |input: State<'a>|
 	{
-		do result::chain(parse_digit()(input))
-		|output|
+		parse_digit()(input).map(|output|
 		{
 			let value = if op == '-' {-output.value} else {output.value};
-			result::Ok(Succeeded {value: value, ..output})
-		}
-	}
+			Succeeded {value, ..output}
+		})
+	})
 }
 
-
 #[test]
 fn test_chainl1()
 {
 	let factor = parse_digit();
 	let op = "*".lit().or("/".lit());
-	let p = factor.chainl1(op, |lhs, op, rhs| if op == @~"*" {lhs * rhs} else {lhs / rhs} );
-	
-	assert check_int_ok("2", p, 2);
-	assert check_int_ok("2*3", p, 6);
-	assert check_int_ok("2*3/4", p, 1);
-	assert check_int_ok("2*3/4/2", p, 0);
-	assert check_int_ok("2*3-4", p, 6);
+	let p = factor.chainl1(op, |lhs, op, rhs| if op == "*" {lhs * rhs} else {lhs / rhs});
+
+	assert!(check_int_ok("2", &p, 2));
+	assert!(check_int_ok("2*3", &p, 6));
+	assert!(check_int_ok("2*3/4", &p, 1));
+	assert!(check_int_ok("2*3/4/2", &p, 0));
+	assert!(check_int_ok("2*3-4", &p, 6));
 }
 
 #[test]
@@ -71,24 +78,24 @@ fn test_chainr1()
 {
 	let factor = parse_digit();
 	let op = "*".lit().or("/".lit());
-	let p = factor.chainr1(op, |lhs, op, rhs| if op == @~"*" {lhs * rhs} else {lhs / rhs} );
-	
-	assert check_int_ok("2", p, 2);
-	assert check_int_ok("2*3", p, 6);
-	assert check_int_ok("2*3/4", p, 0);
-	assert check_int_ok("2*3/4/2", p, 2);
-	assert check_int_ok("2*3-4", p, 6);
+	let p = factor.chainr1(op, |lhs, op, rhs| if op == "*" {lhs * rhs} else {lhs / rhs});
+
+	assert!(check_int_ok("2", &p, 2));
+	assert!(check_int_ok("2*3", &p, 6));
+	assert!(check_int_ok("2*3/4", &p, 0));
+	assert!(check_int_ok("2*3/4/2", &p, 2));
+	assert!(check_int_ok("2*3-4", &p, 6));
 }
 
 #[test]
 fn test_err()
 {
 	let p = "<".lit().then("foo".lit()).then(">".lit()).err("bracketed foo");
-	
-	assert check_str_ok("<foo>", p, ">");
-	assert check_str_failed("", p, "bracketed foo", 1);
-	assert check_str_failed("<", p, "'foo'", 1);
-	assert check_str_failed("<foo", p, "'>'", 1);
+
+	assert!(check_str_ok("<foo>", &p, ">"));
+	assert!(check_str_failed("", &p, "bracketed foo", 1));
+	assert!(check_str_failed("<", &p, "'foo'", 1));
+	assert!(check_str_failed("<foo", &p, "'>'", 1));
 }
 
 #[test]
@@ -96,229 +103,555 @@ fn test_everything()
 {
 	let s = ret(0).s0();
 	let p = parse_digit().everything(s);
-	
-	assert check_int_ok("2", p, 2);
-	assert check_int_ok("   \t3", p, 3);
-	assert check_int_failed("2 ", p, "EOT", 1);
-	assert check_int_failed("\t2\n", p, "EOT", 1);
+
+	assert!(check_int_ok("2", &p, 2));
+	assert!(check_int_ok("   \t3", &p, 3));
+	assert!(check_int_failed("2 ", &p, "EOT", 1));
+	assert!(check_int_failed("\t2\n", &p, "EOT", 1));
 }
 
 #[test]
 fn test_fails()
 {
 	let p = fails::<char>("ack");
-	
-	assert check_char_failed("", p, "ack", 1);
-	assert check_char_failed("9", p, "ack", 1);
+
+	assert!(check_char_failed("", &p, "ack", 1));
+	assert!(check_char_failed("9", &p, "ack", 1));
+}
+
+#[test]
+fn test_left_rec()
+{
+	// expr := expr '-' num | num
+	let expr_ptr = Rc::new(RefCell::new(ret(0)));
+	let expr_ref = forward_ref(expr_ptr.clone());
+	let num = match1(is_digit).thene(|text| ret(text.parse::<i64>().unwrap())).err("number");
+	let expr = seq3(expr_ref, "-".lit(), num.clone(), |lhs, _, rhs| Ok(lhs - rhs)).or(num).left_rec();
+	*expr_ptr.borrow_mut() = expr.clone();
+
+	assert!(check_int_ok("7", &expr, 7));
+	assert!(check_int_ok("10-3-2", &expr, 5));				// left associative
+	assert!(check_int_ok("10-3-", &expr, 7));
+	assert!(check_int_failed("", &expr, "number", 1));
+	assert!(check_int_failed("x", &expr, "number", 1));
+
+	let p = expr.everything(ret(0));
+	assert!(check_int_ok("1-1-1-1", &p, -2));
+	assert!(check_int_failed("1-1-", &p, "EOT", 1));
+}
+
+#[test]
+fn test_indirect_left_rec()
+{
+	// a := b 'x' | 'y'
+	// b := a 'z' | a
+	let a_ptr = Rc::new(RefCell::new(ret("")));
+	let a_ref = forward_ref(a_ptr.clone());
+	let b = seq2_ret_str(a_ref.clone(), "z".lit()).memo().or(a_ref);
+	let a = seq2_ret_str(b, "x".lit()).or("y".lit()).left_rec();
+	*a_ptr.borrow_mut() = a.clone();
+
+	assert!(check_str_ok("y", &a, "y"));
+	assert!(check_str_ok("yx", &a, "yx"));
+	assert!(check_str_ok("yzx", &a, "yzx"));
+	assert!(check_str_ok("yzxxzx!", &a, "yzxxzx"));
+	assert!(check_str_failed("z", &a, "'y'", 1));
+}
+
+#[test]
+fn test_left_rec_detection()
+{
+	// expr := expr '-' num | num
+	let expr_ptr = Rc::new(RefCell::new(ret("")));
+	let expr_ref = forward_ref(expr_ptr.clone());
+	let expr = seq3_ret_str(expr_ref, "-".lit(), match1(is_digit)).or(match1(is_digit));
+	*expr_ptr.borrow_mut() = expr.clone();
+
+	// The error is reported even though the num alternative matched.
+	let err = expr.parse("test", "1-2").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: left recursion (use left_rec)");
+	assert_eq!(expr.parse_partial("test", "1-"), PartialStatus::Failed(err));
+
+	let result = expr.parse_recover("test", "1-2");
+	assert_eq!((result.value, result.errors.len()), (None, 1));
+
+	// item := prefix item | 'y' where prefix is a left_rec which matches nothing (so
+	// it doesn't stop the recursion).
+	let item_ptr = Rc::new(RefCell::new(ret("")));
+	let item_ref = forward_ref(item_ptr.clone());
+	let prefix = "z".lit().optional().left_rec();
+	let item = seq2_ret1(prefix, item_ref).or("y".lit());
+	*item_ptr.borrow_mut() = item.clone();
+	let err = item.parse("test", "y").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: left recursion (use left_rec)");
+
+	// But recursion through a left_rec which is growing a seed is fine.
+	let expr_ptr = Rc::new(RefCell::new(ret("")));
+	let expr_ref = forward_ref(expr_ptr.clone());
+	let expr = seq3_ret_str(expr_ref, "-".lit(), match1(is_digit)).or(match1(is_digit)).left_rec();
+	*expr_ptr.borrow_mut() = expr.clone();
+	assert_eq!(expr.parse("test", "1-2-3"), Ok("1-2-3"));
 }
 
 #[test]
 fn test_list()
 {
 	let p = "b".lit().list(",".lit());
-	
-	assert check_str_array_ok("b", p, @~[@~"b"]);
-	assert check_str_array_ok("b,b", p, @~[@~"b", @~"b"]);
-	assert check_str_array_ok("b,b,b", p, @~[@~"b", @~"b", @~"b"]);
-	assert check_str_array_ok("b,b,c", p, @~[@~"b", @~"b"]);
-	
-	assert check_str_array_failed("", p, "'b'", 1);
-	assert check_str_array_failed("c", p, "'b'", 1);
+
+	assert!(check_str_array_ok("b", &p, &["b"]));
+	assert!(check_str_array_ok("b,b", &p, &["b", "b"]));
+	assert!(check_str_array_ok("b,b,b", &p, &["b", "b", "b"]));
+	assert!(check_str_array_ok("b,b,c", &p, &["b", "b"]));
+
+	assert!(check_str_array_failed("", &p, "'b'", 1));
+	assert!(check_str_array_failed("c", &p, "'b'", 1));
+}
+
+// Returns the number of bytes consumed by a successful parse.
+fn consumed<'a, T>(parser: &Parser<'a, T>, in_text: &'a str) -> usize
+{
+	parser(unit_state(in_text)).map(|pass| pass.new_state.index).unwrap_or(usize::MAX)
+}
+
+#[test]
+fn test_sep_by()
+{
+	let p = "b".lit().sep_by0(",".lit());
+	assert!(check_str_array_ok("", &p, &[]));
+	assert!(check_str_array_ok("c", &p, &[]));
+	assert!(check_str_array_ok("b,b", &p, &["b", "b"]));
+	assert_eq!(consumed(&p, "b,b,"), 3);		// the trailing separator isn't consumed
+
+	let p = "b".lit().sep_by1(",".lit());
+	assert!(check_str_array_ok("b", &p, &["b"]));
+	assert!(check_str_array_ok("b,b,c", &p, &["b", "b"]));
+	assert_eq!(consumed(&p, "b,b,c"), 3);
+	assert!(check_str_array_failed("", &p, "'b'", 1));
+	assert!(check_str_array_failed("c", &p, "'b'", 1));
+
+	// Elements may be empty.
+	let p = match0(is_digit).sep_by0(",".lit());
+	assert!(check_str_array_ok("x", &p, &[""]));
+	assert!(check_str_array_ok("1,,2", &p, &["1", "", "2"]));
+	let p = match0(is_digit).sep_by1(match0(|ch| ch == ','));
+	assert!(check_str_array_ok("12x", &p, &["12", ""]));
+	let p = match0(is_digit).end_by0(match0(|ch| ch == ';'));
+	assert!(check_str_array_ok("x", &p, &[""]));
+}
+
+#[test]
+fn test_sep_end_by()
+{
+	let p = "b".lit().sep_end_by0(",".lit());
+	assert!(check_str_array_ok("", &p, &[]));
+	assert!(check_str_array_ok("b,b", &p, &["b", "b"]));
+	assert!(check_str_array_ok("b,b,", &p, &["b", "b"]));
+	assert_eq!(consumed(&p, "b,b,"), 4);
+	assert_eq!(consumed(&p, ","), 0);
+
+	let p = "b".lit().sep_end_by1(",".lit());
+	assert!(check_str_array_ok("b,", &p, &["b"]));
+	assert_eq!(consumed(&p, "b,b,c"), 4);
+	assert!(check_str_array_failed(",", &p, "'b'", 1));
+}
+
+#[test]
+fn test_end_by()
+{
+	let p = "b".lit().end_by0(";".lit());
+	assert!(check_str_array_ok("", &p, &[]));
+	assert!(check_str_array_ok("b;b;", &p, &["b", "b"]));
+	assert!(check_str_array_ok("b;b", &p, &["b"]));
+	assert_eq!(consumed(&p, "b;b"), 2);		// the last element isn't terminated
+
+	let p = "b".lit().end_by1(";".lit());
+	assert!(check_str_array_ok("b;", &p, &["b"]));
+	assert!(check_str_array_failed("b", &p, "';'", 1));
+	assert!(check_str_array_failed("", &p, "'b'", 1));
+}
+
+#[test]
+fn test_many_till()
+{
+	let p = anycp(|_| true).many_till("*/".lit());
+	assert!(check_ok(&p(unit_state("*/")), &Vec::new()));
+	assert!(check_ok(&p(unit_state("ab*/")), &vec!['a', 'b']));
+	assert_eq!(consumed(&p, "a*/b"), 3);
+
+	let p = "b".lit().many_till(";".lit());
+	assert!(check_str_array_ok("bb;", &p, &["b", "b"]));
+	assert!(check_str_array_failed("bc;", &p, "'b' or ';'", 1));
+
+	let p = match0(is_alpha).many_till(";".lit());
+	assert!(check_str_array_ok("ab;", &p, &["ab"]));
+	assert!(check_str_array_failed("ab1;", &p, "';'", 1));
+}
+
+// Wraps parser so that the number of times it is called is recorded.
+fn counted<'a, T: 'a>(parser: Parser<'a, T>, count: Rc<Cell<usize>>) -> Parser<'a, T>
+{
+	Rc::new(move |input: State<'a>|
+	{
+		count.set(count.get() + 1);
+		parser(input)
+	})
+}
+
+#[test]
+fn test_memo()
+{
+	let count = Rc::new(Cell::new(0));
+	let prefix = counted(seq2_ret_str("<".lit(), match1(is_alpha)), count.clone()).memo();
+	let p = seq2_ret_str(prefix.clone(), "/>".lit()).or(seq2_ret_str(prefix, ">".lit()));
+
+	reset_memo_stats();
+	assert!(check_str_ok("<foo>", &p, "<foo>"));
+	assert_eq!(count.get(), 1);
+	assert_eq!(memo_stats(), MemoStats {hits: 1, misses: 1});
+	assert_eq!(memo_stats().hit_rate(), 0.5);
+
+	// A new input discards the cached results.
+	assert!(check_str_ok("<bar/>", &p, "<bar/>"));
+	assert_eq!(count.get(), 2);
+	assert!(check_str_failed("<baz", &p, "'/>' or '>'", 1));
+	assert_eq!(count.get(), 3);
+	assert_eq!(memo_stats(), MemoStats {hits: 2, misses: 3});
+
+	// Failures are cached too.
+	assert!(check_str_failed("<1", &p, "", 1));
+	assert_eq!(count.get(), 4);
+	assert_eq!(memo_stats(), MemoStats {hits: 3, misses: 4});
+}
+
+#[test]
+fn test_memo_state()
+{
+	// Calls at the same index with a different skipper or indent aren't cache hits.
+	let word = "x".s0().memo();
+	let p: Parser<(usize, usize, usize)> = Rc::new(move |input: State|
+	{
+		let plain = word(input.clone())?;
+		let comments = word(input.clone().with_skipper(Skipper::c_comments()))?;
+		let indented = word(State {indent: 3, ..input})?;
+		Ok(Succeeded {new_state: plain.new_state.clone(), value: (plain.new_state.index, comments.new_state.index, indented.new_state.index)})
+	});
+
+	reset_memo_stats();
+	assert_eq!(p.parse("test", "x /**/ y"), Ok((2, 7, 2)));
+	assert_eq!(p.parse("test", "x\n y"), Ok((3, 3, 1)));
+	assert_eq!(memo_stats().hits, 0);
+}
+
+#[test]
+fn test_memo_linear()
+{
+	// s := 'a' s 'b' | 'a' s 'c' | ''
+	// Without memo this takes 2^n calls to match n a's.
+	let text = format!("{}{}", "a".repeat(20), "c".repeat(20));
+	let count = Rc::new(Cell::new(0));
+	let s_ptr = Rc::new(RefCell::new(ret(0)));
+	let s_ref = counted(forward_ref(s_ptr.clone()), count.clone()).memo();
+	let s = or_v(vec![
+		seq3("a".lit(), s_ref.clone(), "b".lit(), |_, n, _| Ok(n + 1)),
+		seq3("a".lit(), s_ref.clone(), "c".lit(), |_, n, _| Ok(n + 1)),
+		ret(0)]);
+	*s_ptr.borrow_mut() = s.clone();
+
+	assert!(check_int_ok(&text, &s, 20));
+	assert!(count.get() <= 21);
 }
 
 #[test]
 fn test_parse()
 {
 	let p = "<".lit().s0().then("foo".lit().s0()).then(">".lit()).err("bracketed foo");
-	
-	match p.parse(@~"unit test", ~"< foo\t>")
+
+	match p.parse("unit test", "< foo\t>")
 	{
-		result::Ok(s) =>
+		Ok(s) =>
 		{
-			if s != @~">"
+			if s != ">"
 			{
-				io::stderr().write_line(fmt!("'>' but found '%s'.", *s));
-				assert false;
+				eprintln!("'>' but found '{}'.", s);
+				panic!();
 			}
 		}
-		result::Err(ParseFailed {file, line, col, mesg}) =>
+		Err(ParseFailed {line, col, mesg, ..}) =>
 		{
-			util::ignore(file);
-			io::stderr().write_line(fmt!("Error '%s' on line %u and col %u.", *mesg, line, col));
-			assert false;
+			eprintln!("Error '{}' on line {} and col {}.", mesg, line, col);
+			panic!();
 		}
 	}
-	
-	assert check_str_failed("<foo", p, "'>'", 1);
-	match p.parse(@~"unit test", ~"< \n\nfoo\tx")
+
+	assert!(check_str_failed("<foo", &p, "'>'", 1));
+	match p.parse("unit test", "< \n\nfoo\tx")
 	{
-		result::Ok(s) =>
+		Ok(s) =>
 		{
-			io::stderr().write_line(fmt!("Somehow parsed '%s'.", *s));
-			assert false;
+			eprintln!("Somehow parsed '{}'.", s);
+			panic!();
 		}
-		result::Err(ParseFailed {file, line, col, mesg}) =>
+		Err(ParseFailed {file, line, col, mesg, ..}) =>
 		{
-			assert file == @~"unit test";
-			assert line == 3u;
-			assert col == 5u;
-			assert mesg == @~"'>'";
+			assert_eq!(file, "unit test");
+			assert_eq!(line, 3);
+			assert_eq!(col, 5);
+			assert_eq!(mesg, "'>'");
 		}
 	}
 }
 
 #[test]
-fn test__r0()
+fn test_r0()
 {
 	let p = "b".lit().r0();
-	
-	assert check_str_array_ok("", p, @~[]);
-	assert check_str_array_ok("b", p, @~[@~"b"]);
-	assert check_str_array_ok("bb", p, @~[@~"b", @~"b"]);
-	assert check_str_array_ok("bbb", p, @~[@~"b", @~"b", @~"b"]);
-	assert check_str_array_ok("c", p, @~[]);
+
+	assert!(check_str_array_ok("", &p, &[]));
+	assert!(check_str_array_ok("b", &p, &["b"]));
+	assert!(check_str_array_ok("bb", &p, &["b", "b"]));
+	assert!(check_str_array_ok("bbb", &p, &["b", "b", "b"]));
+	assert!(check_str_array_ok("c", &p, &[]));
 }
 
 #[test]
-fn test__r1()
+fn test_r1()
 {
 	let p = "b".lit().r1().err("b's");
-	
-	assert check_str_array_ok("b", p, @~[@~"b"]);
-	assert check_str_array_ok("bb", p, @~[@~"b", @~"b"]);
-	assert check_str_array_ok("bbb", p, @~[@~"b", @~"b", @~"b"]);
-	
-	assert check_str_array_failed("", p, "b's", 1);
-	assert check_str_array_failed("c", p, "b's", 1);
+
+	assert!(check_str_array_ok("b", &p, &["b"]));
+	assert!(check_str_array_ok("bb", &p, &["b", "b"]));
+	assert!(check_str_array_ok("bbb", &p, &["b", "b", "b"]));
+
+	assert!(check_str_array_failed("", &p, "b's", 1));
+	assert!(check_str_array_failed("c", &p, "b's", 1));
 }
 
 #[test]
 fn test_ret()
 {
 	let p = ret('x');
-	
-	assert check_char_ok("a", p, 'x');
-	assert check_char_ok("e", p, 'x');
-	assert check_char_ok(" ", p, 'x');
+
+	assert!(check_char_ok("a", &p, 'x'));
+	assert!(check_char_ok("e", &p, 'x'));
+	assert!(check_char_ok(" ", &p, 'x'));
 }
 
 #[test]
 fn test_litv()
 {
-	let p = "foo".litv(@~"hmm");
-	
-	assert check_str_ok("foo", p, "hmm");
-	assert check_str_ok("foo-shizzle", p, "hmm");
-	assert check_str_failed("", p, "'foo'", 1);
-	assert check_str_failed("bar", p, "'foo'", 1);
-	assert check_str_failed("pseudo foo", p, "'foo'", 1);
+	let p = "foo".litv("hmm");
+
+	assert!(check_str_ok("foo", &p, "hmm"));
+	assert!(check_str_ok("foo-shizzle", &p, "hmm"));
+	assert!(check_str_failed("", &p, "'foo'", 1));
+	assert!(check_str_failed("bar", &p, "'foo'", 1));
+	assert!(check_str_failed("pseudo foo", &p, "'foo'", 1));
 }
 
 #[test]
 fn test_optional()
 {
 	let p = seq3_ret_str("a".lit(), "b".lit().optional(), "c".lit());
-	
-	assert check_str_ok("abc", p, "abc");
-	assert check_str_ok("ac", p, "ac");
-	assert check_str_failed("ad", p, "'c'", 1);
-	assert check_str_failed("dbe", p, "'a'", 1);
+
+	assert!(check_str_ok("abc", &p, "abc"));
+	assert!(check_str_ok("ac", &p, "ac"));
+	assert!(check_str_failed("ad", &p, "'c'", 1));
+	assert!(check_str_failed("dbe", &p, "'a'", 1));
 }
 
 #[test]
 fn test_or_v()
 {
-	let p = or_v(@~["a".lit(), "bb".lit(), "c".lit()]);
-	
-	assert check_str_ok("a", p, "a");
-	assert check_str_ok("bb", p, "bb");
-	assert check_str_ok("c", p, "c");
-	assert check_str_ok("ca", p, "c");
-	assert check_str_failed("", p, "'a' or 'bb' or 'c'", 1);
-	
-	let text = chars_with_eot("bz");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
-	assert result::get_err(&result).old_state.index == 0u;
+	let p = or_v(vec!["a".lit(), "bb".lit(), "c".lit()]);
+
+	assert!(check_str_ok("a", &p, "a"));
+	assert!(check_str_ok("bb", &p, "bb"));
+	assert!(check_str_ok("c", &p, "c"));
+	assert!(check_str_ok("ca", &p, "c"));
+	assert!(check_str_failed("", &p, "'a' or 'bb' or 'c'", 1));
+
+	let result = p(unit_state("bz"));
+	assert!(result.unwrap_err().old_state.index == 0);
 }
 
 #[test]
 fn test_s0()
 {
 	let p = "x".lit().s0().then("y".lit());
-	
-	assert check_str_ok("xy", p, "y");
-	assert check_str_ok("x y", p, "y");
-	assert check_str_ok("x \n\t y", p, "y");
-	
-	assert check_str_failed("x z", p, "'y'", 1);
-	assert check_str_failed("x\nz", p, "'y'", 2);
-	assert check_str_failed("x\n\r\nz", p, "'y'", 3);
+
+	assert!(check_str_ok("xy", &p, "y"));
+	assert!(check_str_ok("x y", &p, "y"));
+	assert!(check_str_ok("x \n\t y", &p, "y"));
+
+	assert!(check_str_failed("x z", &p, "'y'", 1));
+	assert!(check_str_failed("x\nz", &p, "'y'", 2));
+	assert!(check_str_failed("x\n\r\nz", &p, "'y'", 3));
 }
 
 #[test]
 fn test_s1()
 {
 	let p = "x".lit().s1().then("y".lit());
-	
-	assert check_str_ok("x y", p, "y");
-	assert check_str_ok("x \n\t y", p, "y");
-	
-	assert check_str_failed("xy", p, "whitespace", 1);
-	assert check_str_failed("x z", p, "'y'", 1);
-	assert check_str_failed("x\nz", p, "'y'", 2);
-	assert check_str_failed("x\n\r\nz", p, "'y'", 3);
+
+	assert!(check_str_ok("x y", &p, "y"));
+	assert!(check_str_ok("x \n\t y", &p, "y"));
+
+	assert!(check_str_failed("xy", &p, "whitespace", 1));
+	assert!(check_str_failed("x z", &p, "'y'", 1));
+	assert!(check_str_failed("x\nz", &p, "'y'", 2));
+	assert!(check_str_failed("x\n\r\nz", &p, "'y'", 3));
+}
+
+#[test]
+fn test_skippers()
+{
+	let p = seq!("x".s0(), "y".s1(), "z".lit());
+	let input = unit_state("x /* a */ // b\n\ty\t/**/z").with_skipper(Skipper::c_comments());
+	assert_eq!(p.parse_from(input), Ok(("x", "y", "z")));
+
+	// s1 needs white space or a comment.
+	let input = unit_state("x y/**/z").with_skipper(Skipper::c_comments());
+	assert!(p.parse_from(input).is_ok());
+	let input = unit_state("x yz").with_skipper(Skipper::c_comments());
+	assert_eq!(p.parse_from(input).unwrap_err().mesg, "whitespace");
+
+	let input = unit_state("x # a\n y z").with_skipper(Skipper::hash_comments());
+	assert!(p.parse_from(input).is_ok());
+
+	// The default doesn't skip comments.
+	assert!(p.parse("test", "x /* a */ y z").is_err());
+
+	// Skippers can be functions.
+	fn dots(input: State) -> State
+	{
+		input.advance(input.rest().len() - input.rest().trim_start_matches('.').len())
+	}
+	let input = unit_state("x..y.z").with_skipper(Skipper(Rc::new(dots)));
+	assert!(p.parse_from(input).is_ok());
+
+	// Or parsers.
+	let semis = alt!(";".s0(), match1(is_whitespace)).r0();
+	let skip = Skipper::parser(semis.clone());
+	let input = unit_state("x; ;y ;z").with_skipper(skip);
+	assert!(p.parse_from(input).is_ok());
+	assert_eq!(Rc::strong_count(&semis), 1);	// the skipper doesn't leak its parser
+
+	// With partial input we don't know if there is more white space.
+	let input = State {partial: true, ..unit_state("x /* a")}.with_skipper(Skipper::c_comments());
+	assert!(p.parse_from(input).unwrap_err().mesg.contains("more input"));
 }
 
 #[test]
 fn test_seq3()
 {
-	let p = do seq3("+-".anyc(), anycp(is_digit), anycp(is_digit))
+	let p = seq3("+-".anyc(), anycp(is_digit), anycp(is_digit),
 		|a, b, c|
 		{
-			let x = (10*char::to_digit(b, 10).get() + char::to_digit(c, 10).get()) as int; 
-			result::Ok(if a == '-' {-x} else {x})
-		};
-	
-	assert check_int_ok("+23", p, 23);
-	assert check_int_ok("+239", p, 23);
-	assert check_int_ok("-19", p, -19);
-	assert check_int_failed("", p, "[+-]", 1);
-	assert check_int_failed("+2", p, "", 1);
-	assert check_int_failed("2", p, "[+-]", 1);
+			let x = (10*b.to_digit(10).unwrap() + c.to_digit(10).unwrap()) as i64;
+			Ok(if a == '-' {-x} else {x})
+		});
+
+	assert!(check_int_ok("+23", &p, 23));
+	assert!(check_int_ok("+239", &p, 23));
+	assert!(check_int_ok("-19", &p, -19));
+	assert!(check_int_failed("", &p, "[+-]", 1));
+	assert!(check_int_failed("+2", &p, "", 1));
+	assert!(check_int_failed("2", &p, "[+-]", 1));
+}
+
+#[test]
+fn test_seq_macro()
+{
+	let p = seq!("+-".anyc(), anycp(is_digit), anycp(is_digit));
+	assert!(check_ok(&p(unit_state("+23")), &('+', '2', '3')));
+	assert!(check_failed(&p(unit_state("+2")), "", 1));
+
+	// Skipped values aren't returned and a single value isn't wrapped in a tuple.
+	let p = seq!(skip "(".lit(), "x".lit(), skip ")".lit(),);
+	assert!(check_str_ok("(x)", &p, "x"));
+	assert!(check_str_failed("(x", &p, "')'", 1));
+
+	let p = seq!(skip "a".lit(), skip "b".lit());
+	assert!(check_ok(&p(unit_state("ab")), &()));
+
+	// Any number of parsers can be used.
+	let d = || anycp(is_digit);
+	let p = seq!(d(), d(), d(), d(), d(), d(), d(), d(), d(), skip ",".lit(), d(), d());
+	assert!(check_ok(&p(unit_state("123456789,01")), &('1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '1')));
+
+	// Failures are reported relative to the start of the sequence.
+	let failure = p(unit_state("123456789;01")).unwrap_err();
+	assert_eq!((failure.old_state.index, failure.err_state.index), (0, 9));
+}
+
+#[test]
+fn test_seq_str_macro()
+{
+	let p = seq_str!("a".lit(), "b".lit().optional(), skip "c".lit());
+	assert!(check_str_ok("abc", &p, "abc"));
+	assert!(check_str_ok("acd", &p, "ac"));
+	assert!(check_str_failed("ab", &p, "'c'", 1));
+}
+
+#[test]
+fn test_alt_macro()
+{
+	let p = alt!("a".lit(), "bb".lit(), "c".lit(),);
+	assert!(check_str_ok("bb", &p, "bb"));
+	assert!(check_str_ok("c", &p, "c"));
+	assert!(check_str_failed("x", &p, "'a' or 'bb' or 'c'", 1));
+}
+
+#[test]
+fn test_macros_match_functions()
+{
+	// The macros return the same results (and errors) as the functions they generalize.
+	let texts = ["(x)", "(x", "x)", "+(x)", ""];
+	let pairs = [
+		(seq!(skip "(".lit(), "x".lit(), skip ")".lit()), seq3_ret1("(".lit(), "x".lit(), ")".lit())),
+		(seq!(skip "+".lit(), skip "(".lit(), "x".lit(), skip ")".lit()), seq4_ret2("+".lit(), "(".lit(), "x".lit(), ")".lit())),
+		(seq_str!("(".lit(), "x".lit(), ")".lit().optional()), seq3_ret_str("(".lit(), "x".lit(), ")".lit().optional())),
+		(alt!("(x)".lit(), "(".lit(), "x".lit()), or_v(vec!["(x)".lit(), "(".lit(), "x".lit()])),
+	];
+	for (macro_parser, function) in pairs.iter()
+	{
+		for text in texts
+		{
+			let input = unit_state(text);
+			assert_eq!(format!("{:?}", macro_parser(input.clone())), format!("{:?}", function(input)), "{}", text);
+		}
+	}
 }
 
 #[test]
 fn test_then()
 {
 	let p = "<".lit().then("foo".lit()).then(">".lit());
-	
-	assert check_str_ok("<foo>", p, ">");
-	assert check_str_failed("", p, "'<'", 1);
-	assert check_str_failed("<", p, "'foo'", 1);
-	assert check_str_failed("<foo", p, "'>'", 1);
-	assert check_str_failed("<foo-", p, "'>'", 1);
-	
-	let text = chars_with_eot("<foo-");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
-	assert result::get_err(&result).old_state.index == 0u;	// if any of the then clauses fails we need to start over
+
+	assert!(check_str_ok("<foo>", &p, ">"));
+	assert!(check_str_failed("", &p, "'<'", 1));
+	assert!(check_str_failed("<", &p, "'foo'", 1));
+	assert!(check_str_failed("<foo", &p, "'>'", 1));
+	assert!(check_str_failed("<foo-", &p, "'>'", 1));
+
+	let result = p(unit_state("<foo-"));
+	assert!(result.unwrap_err().old_state.index == 0);	// if any of the then clauses fails we need to start over
 }
 
 #[test]
 fn test_thene()
 {
-	let p = do parse_unary().thene |c| {parse_num(c)};
-	
-	assert check_int_ok("-9", p, -9);
-	assert check_int_ok("+3", p, 3);
-	assert check_int_failed("", p, "'-' or '+'", 1);
-	assert check_int_failed("~9", p, "'-' or '+'", 1);
-	assert check_int_failed("--9", p, "digit", 1);
-	
-	let text = chars_with_eot("~9");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
-	assert result::get_err(&result).old_state.index == 0u;	// simple case where parse_unary fails
-	
-	let text = chars_with_eot("--");
-	let result = p(State {file: @~"unit test", text: text, index: 0u, line: 1});
-	assert result::get_err(&result).old_state.index == 0u;	// if parse_num fails we need to start over
+	let p = parse_unary().thene(parse_num);
+
+	assert!(check_int_ok("-9", &p, -9));
+	assert!(check_int_ok("+3", &p, 3));
+	assert!(check_int_failed("", &p, "'-' or '+'", 1));
+	assert!(check_int_failed("~9", &p, "'-' or '+'", 1));
+	assert!(check_int_failed("--9", &p, "digit", 1));
+
+	let result = p(unit_state("~9"));
+	assert!(result.unwrap_err().old_state.index == 0);	// simple case where parse_unary fails
+
+	let result = p(unit_state("--"));
+	assert!(result.unwrap_err().old_state.index == 0);	// if parse_num fails we need to start over
 }
@@ -0,0 +1,190 @@
+use crate::grammar::*;
+use crate::parsers::*;
+
+const EXPR: &str = r#"
+	// Left recursion isn't supported so operators are repeated instead.
+	expr    := term (op:[+\-] _ term)*
+	term    := factor (op:[*/] _ factor)*
+	factor  := value:[0-9]+ _ | '(' _ expr ')' _
+	_       := [ \t]*
+"#;
+
+fn eval(node: Node<i64>) -> Result<i64, String>
+{
+	if node.rule == "factor" && node.children.is_empty()
+	{
+		return node.capture("value").unwrap().parse::<i64>().map_err(|e| e.to_string());
+	}
+
+	let mut children = node.children.into_iter();
+	let mut result = children.next().unwrap();
+	let ops = node.captures.iter().filter(|c| c.0 == "op");
+	for (op, rhs) in ops.zip(children)
+	{
+		result = match op.1
+		{
+			"+" => result + rhs,
+			"-" => result - rhs,
+			"*" => result * rhs,
+			_ if rhs == 0 => return Err("divide by zero".to_string()),
+			_ => result / rhs,
+		};
+	}
+	Ok(result)
+}
+
+#[test]
+fn test_tree()
+{
+	let grammar = Grammar::new("test", EXPR).unwrap();
+	let tree = grammar.parser("expr").parse("test", "1 + 23").unwrap();
+	assert_eq!(tree.rule, "expr");
+	assert_eq!(tree.text, "1 + 23");
+	assert_eq!(tree.capture("op"), Some("+"));
+	assert_eq!(tree.children.len(), 2);
+
+	let term = &tree.children[1];
+	assert_eq!(term.rule, "term");
+	assert_eq!(term.span.range(), 4..6);
+	assert_eq!(term.children[0].rule, "factor");
+	assert_eq!(term.children[0].capture("value"), Some("23"));
+	assert!(term.children[0].children.is_empty());
+}
+
+#[test]
+fn test_actions()
+{
+	let grammar = Grammar::new("test", EXPR).unwrap();
+	let p = grammar.parser_with("expr", Actions::new(eval));
+	assert_eq!(p.parse("test", "2 * (3 + 4) - 5"), Ok(9));
+	assert_eq!(p.parse("test", "7"), Ok(7));
+
+	// Like the seqN functions, actions that fail cause backtracking.
+	let err = p.parse("test", "4 / (2 - 2)").unwrap_err();
+	assert_eq!(err.mesg, "divide by zero");
+	assert_eq!(p.parse("test", "1 + 4 / 0"), Ok(1));
+
+	// Actions can be set per rule.
+	let actions = Actions::new(|node: Node<String>| Ok(node.children.concat())).rule("factor", |node| Ok(format!("<{}>", node.text.trim())));
+	let p = grammar.parser_with("expr", actions);
+	assert_eq!(p.parse("test", "1+2 * 3"), Ok("<1><2><3>".to_string()));
+}
+
+#[test]
+fn test_errors()
+{
+	let grammar = Grammar::new("test", EXPR).unwrap();
+	let p = grammar.parser("expr");
+	let err = p.parse("test", "x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: expected expr, found 'x'");
+
+	let err = p.parse("test", "(1 + 2").unwrap_err();
+	assert_eq!(err.col, 7);
+	assert_eq!(err.expected, vec!["')'".to_string()]);
+}
+
+#[test]
+fn test_classes()
+{
+	let grammar = Grammar::new("test", r#"
+		string := '"' body:([^"\\] | '\\' .)* '"'
+		word   := [a-zA-Z_] [a-zA-Z0-9_]*
+		dash   := [-x]+ | [y-]+
+	"#).unwrap();
+
+	let p = grammar.parser("string");
+	assert_eq!(p.parse("test", r#""a\"b""#).unwrap().capture("body"), Some(r#"a\"b"#));
+
+	let p = grammar.parser("word");
+	assert_eq!(p.parse("test", "x_1 y").unwrap().text, "x_1");
+	let err = p.parse("test", "1x").unwrap_err();
+	assert_eq!(err.expected, vec!["word".to_string()]);
+
+	let p = grammar.parser("dash");
+	assert_eq!(p.parse("test", "-x-").unwrap().text, "-x-");
+	assert_eq!(p.parse("test", "y-").unwrap().text, "y-");
+}
+
+#[test]
+fn test_lookahead()
+{
+	// Keywords can't be identifiers.
+	let grammar = Grammar::new("test", r#"
+		name    := !keyword [a-z]+ ![0-9]
+		keyword := ('if' | 'else') ![a-z]
+	"#).unwrap();
+
+	let p = grammar.parser("name");
+	assert_eq!(p.parse("test", "iffy").unwrap().text, "iffy");
+	assert!(p.parse("test", "if").is_err());
+	assert!(p.parse("test", "ab1").is_err());
+	assert_eq!(p.parse("test", "ab;").unwrap().children, Vec::new());
+}
+
+#[test]
+fn test_hidden_and_repeats()
+{
+	// Hidden rules pass their children and captures to their parent.
+	let grammar = Grammar::new("test", r#"
+		list   := _items? ';'
+		_items := item (',' item)*
+		item   := name:[a-z]+ ('?')*
+	"#).unwrap();
+
+	let p = grammar.parser("list");
+	let tree = p.parse("test", "a,bc??,d;").unwrap();
+	let names: Vec<_> = tree.children.iter().map(|child| child.capture("name").unwrap()).collect();
+	assert_eq!(names, vec!["a", "bc", "d"]);
+	assert_eq!(p.parse("test", ";").unwrap().children.len(), 0);
+
+	// Repeating things that match nothing doesn't loop forever.
+	let grammar = Grammar::new("test", "a := ('x'?)* 'y'").unwrap();
+	assert!(grammar.parser("a").parse("test", "xxy").is_ok());
+}
+
+#[test]
+fn test_bad_grammars()
+{
+	let err = Grammar::new("test", "a := 'x' b").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:10: rule b is not defined");
+
+	let err = Grammar::new("test", "a := 'x'\na := 'y'").unwrap_err();
+	assert_eq!(err.to_string(), "test:2:1: rule a is defined more than once");
+
+	let err = Grammar::new("test", "a := 'x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:8: expected closing ' while parsing expression, found end of input");
+
+	let err = Grammar::new("test", "a := [z-a]").unwrap_err();
+	assert_eq!(err.mesg, "bad range z-a");
+
+	let err = Grammar::new("test", "a := 'x' (").unwrap_err();
+	assert_eq!((err.line, err.col), (1, 11));
+
+	let err = Grammar::new("test", "a := 'x' |").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:11: expected expression, found end of input");
+	assert!(Grammar::new("test", "").is_err());
+}
+
+#[test]
+fn test_left_recursion()
+{
+	let err = Grammar::new("test", "e := e '+' n | n\nn := [0-9]+").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: rule e is left recursive");
+
+	// Indirect recursion and recursion after something that can match nothing.
+	let err = Grammar::new("test", "a := 'x' | b\nb := 'y'* c\nc := !'z' a").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:1: rule a is left recursive");
+
+	// Recursion after input has been consumed is fine.
+	let grammar = Grammar::new("test", "e := n '+' e | n\nn := [0-9]+").unwrap();
+	assert!(grammar.parser("e").parse("test", "1+2+3").is_ok());
+}
+
+#[test]
+fn test_partial()
+{
+	let grammar = Grammar::new("test", "a := 'x'+ ';'").unwrap();
+	let p = grammar.parser("a");
+	assert!(matches!(p.parse_partial("test", "xx"), PartialStatus::Incomplete));
+	assert!(matches!(p.parse_partial("test", "xx;"), PartialStatus::Complete(_, 3)));
+}
@@ -0,0 +1,114 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::c99_parsers::*;
+use crate::indent_parsers::*;
+use crate::misc::*;
+use crate::parsers::*;
+use crate::types::*;
+use crate::seq;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value<'a>
+{
+	Str(&'a str),
+	Map(Vec<(&'a str, Value<'a>)>),
+}
+
+// entry := key ':' (text | map)
+// text  := [ \t]* [^\n]+
+// map   := indented_block(entry)
+fn config<'a>() -> Parser<'a, Value<'a>>
+{
+	let entry_ptr = Rc::new(RefCell::new(fails("")));
+	let map = indented_block(forward_ref(entry_ptr.clone())).thene(|entries| ret(Value::Map(entries)));
+	let text = seq!(skip match0(|ch| ch == ' ' || ch == '\t'), match1(|ch| ch != '\n')).thene(|s| ret(Value::Str(s.trim_end()))).s0();
+	let entry = seq!(identifier(), skip ":".lit(), text.or(map.clone()));
+	*entry_ptr.borrow_mut() = entry;
+
+	map.everything(space())
+}
+
+fn space<'a>() -> Parser<'a, ()>
+{
+	match0(is_whitespace).thene(|_| ret(()))
+}
+
+#[test]
+fn test_blocks()
+{
+	let p = config();
+	let text = "\
+server:
+  host: example.com
+  limits:
+    max: 10
+
+    min: 1
+  port: 80
+log: off
+";
+	let expected = Value::Map(vec![
+		("server", Value::Map(vec![
+			("host", Value::Str("example.com")),
+			("limits", Value::Map(vec![("max", Value::Str("10")), ("min", Value::Str("1"))])),
+			("port", Value::Str("80")),
+		])),
+		("log", Value::Str("off")),
+	]);
+	assert_eq!(p.parse("test", text), Ok(expected));
+
+	// Items can start at any column as long as they line up.
+	assert_eq!(p.parse("test", "  a:\n    b: 1\n  c: 2"), Ok(Value::Map(vec![
+		("a", Value::Map(vec![("b", Value::Str("1"))])),
+		("c", Value::Str("2")),
+	])));
+}
+
+#[test]
+fn test_block_errors()
+{
+	let p = config();
+	let err = p.parse("test", "a:\n  b: 1\n   c: 2\n").unwrap_err();
+	assert_eq!(err.to_string(), "test:3:4: unexpected indentation");
+
+	// Nested blocks have to be indented past their parent.
+	let err = p.parse("test", "a:\nb: 1\n").unwrap_err();
+	assert_eq!(err.to_string(), "test:2:1: expected indented block, found 'b'");
+
+	// Items within a block have to start their own line.
+	let p = indented_block("x".lit().s0()).everything(space());
+	let err = p.parse("test", "x x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: expected EOT, found 'x'");
+}
+
+#[test]
+fn test_markers()
+{
+	let p = seq!(skip "a".lit(), indent());
+	assert_eq!(p.parse("test", "a\n  x"), Ok(3));
+	let err = p.parse("test", "a x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: expected indentation, found 'x'");
+
+	// block := (same_indent e)+ dedent
+	let p = seq!(same_indent("x".lit().s0()).r1(), skip dedent());
+	let state = State {indent: 3, ..State::new("test", "  x\n  x\n y")};
+	let pass = p(state).ok().unwrap();
+	assert_eq!((pass.value.len(), pass.new_state.col), (2, 2));
+
+	let p = dedent();
+	assert!(p.parse("test", "  \n\n ").is_ok());
+	assert_eq!(p.parse_partial("test", "  \n"), PartialStatus::Incomplete);
+}
+
+#[test]
+fn test_line_fold()
+{
+	// stmt := word+
+	let word = match1(|ch| ch.is_alphabetic()).s0();
+	let stmt = line_fold(word.r1());
+	let p = stmt.r1();
+
+	let text = "print a\n   b c\nprint d\n";
+	assert_eq!(p.parse("test", text), Ok(vec![vec!["print", "a", "b", "c"], vec!["print", "d"]]));
+}
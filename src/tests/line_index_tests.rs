@@ -0,0 +1,130 @@
+use crate::c99_parsers::*;
+use crate::line_index::*;
+use crate::parsers::*;
+use crate::render::*;
+use crate::types::*;
+
+#[test]
+fn test_lines()
+{
+	let index = LineIndex::new("ab\r\ncd\ref\n\ngh");
+	assert_eq!(index.line_count(), 5);
+	assert_eq!(index.line(1), Some("ab"));
+	assert_eq!(index.line(2), Some("cd"));
+	assert_eq!(index.line(3), Some("ef"));
+	assert_eq!(index.line(4), Some(""));
+	assert_eq!(index.line(5), Some("gh"));
+	assert_eq!(index.line(0), None);
+	assert_eq!(index.line(6), None);
+
+	assert_eq!(LineIndex::new("").line_count(), 1);
+	assert_eq!(LineIndex::new("a\n").line(2), Some(""));
+}
+
+#[test]
+fn test_position()
+{
+	let index = LineIndex::new("ab\r\ncd");
+	assert_eq!(index.position(0, ColUnit::Chars), Some(Position {offset: 0, line: 1, col: 1}));
+	assert_eq!(index.position(2, ColUnit::Chars), Some(Position {offset: 2, line: 1, col: 3}));
+	assert_eq!(index.position(3, ColUnit::Chars), Some(Position {offset: 2, line: 1, col: 3}));
+	assert_eq!(index.position(5, ColUnit::Chars), Some(Position {offset: 5, line: 2, col: 2}));
+	assert_eq!(index.position(6, ColUnit::Chars), Some(Position {offset: 6, line: 2, col: 3}));
+	assert_eq!(index.position(7, ColUnit::Chars), None);
+}
+
+#[test]
+fn test_units()
+{
+	// é is two UTF-8 bytes and one UTF-16 code unit, 😀 is four bytes and two code units.
+	let text = "x\n\u{E9}\u{1F600}y";
+	let index = LineIndex::new(text);
+	let y = text.find('y').unwrap();
+	assert_eq!(index.position(y, ColUnit::Utf8).map(|p| p.col), Some(7));
+	assert_eq!(index.position(y, ColUnit::Utf16).map(|p| p.col), Some(4));
+	assert_eq!(index.position(y, ColUnit::Chars).map(|p| p.col), Some(3));
+	assert_eq!(index.position(y - 1, ColUnit::Chars), None);
+
+	assert_eq!(index.offset(2, 7, ColUnit::Utf8), Some(y));
+	assert_eq!(index.offset(2, 4, ColUnit::Utf16), Some(y));
+	assert_eq!(index.offset(2, 3, ColUnit::Chars), Some(y));
+	assert_eq!(index.offset(2, 4, ColUnit::Chars), Some(text.len()));
+	assert_eq!(index.offset(2, 5, ColUnit::Chars), None);
+	assert_eq!(index.offset(2, 3, ColUnit::Utf16), None);		// middle of the surrogate pair
+	assert_eq!(index.offset(1, 2, ColUnit::Utf16), Some(1));
+	assert_eq!(index.offset(1, 3, ColUnit::Utf16), None);
+	assert_eq!(index.offset(3, 1, ColUnit::Utf16), None);
+}
+
+#[test]
+fn test_round_trip()
+{
+	let text = "a\u{E9}\r\n\t\u{1F600}b\rc";
+	let index = LineIndex::new(text);
+	for unit in [ColUnit::Utf8, ColUnit::Utf16, ColUnit::Chars]
+	{
+		for (offset, _) in text.char_indices().filter(|(i, _)| !text[..*i].ends_with('\r') || !text[*i..].starts_with('\n'))
+		{
+			let pos = index.position(offset, unit).unwrap();
+			assert_eq!(index.offset(pos.line, pos.col, unit), Some(offset), "{:?} {}", unit, offset);
+		}
+	}
+}
+
+#[test]
+fn test_spanned()
+{
+	// Spans from parsers can be converted to other units.
+	let text = "\u{1F600} = foo";
+	let p = "\u{1F600}".s0().then("=".s0()).then(identifier().spanned());
+	let (_, span) = p.parse("test", text).unwrap();
+	assert_eq!(span.start.col, 5);
+
+	let index = LineIndex::new(text);
+	let span = index.span(span.range(), ColUnit::Utf16).unwrap();
+	assert_eq!((span.start.col, span.end.col), (6, 9));
+}
+
+#[test]
+fn test_parse_failed_position()
+{
+	// é is two UTF-8 bytes and one UTF-16 code unit, 😀 is four bytes and two code units.
+	let p = seq2_ret0("a".s0(), "\u{E9}\u{1F600}".s0()).then("y".lit());
+	let err = p.parse("test", "a\r\n\u{E9}\u{1F600} x").unwrap_err();
+	assert_eq!((err.line, err.col), (2, 4));
+	assert_eq!(err.position(ColUnit::Utf8), Position {offset: 10, line: 2, col: 8});
+	assert_eq!(err.position(ColUnit::Utf16), Position {offset: 10, line: 2, col: 5});
+	assert_eq!(err.position(ColUnit::Chars), Position {offset: 10, line: 2, col: 4});
+
+	// Errors from parse_partial and parse_recover are also indexed.
+	match p.parse_partial("test", "\u{1F600}")
+	{
+		PartialStatus::Failed(err) => assert_eq!(err.position(ColUnit::Utf16).col, 1),
+		status => panic!("{:?}", status),
+	}
+	let result = "a".lit().recover_with("?").then("\u{1F600}".lit()).then("b".lit()).parse_recover("test", "\u{1F600}c");
+	assert_eq!(result.errors.len(), 2);
+	let cols: Vec<usize> = result.errors.iter().map(|err| err.position(ColUnit::Utf16).col).collect();
+	assert_eq!(cols, vec![1, 3]);
+
+	// Tabs are one column (unlike col which uses the tab width).
+	let err = "y".lit().parse_from(State::new("test", "\tx").with_tab_width(4).skip_space()).unwrap_err();
+	assert_eq!((err.col, err.position(ColUnit::Chars).col), (5, 2));
+}
+
+#[test]
+fn test_parse_indexed()
+{
+	// The same index can be used to parse, render errors, and convert spans.
+	let index = LineIndex::new("a\n\u{1F600} x");
+	let p = seq2_ret1("a".s0(), "\u{1F600}".lit().spanned()).then("y".s0());
+	let err = p.parse_indexed("test", &index).unwrap_err();
+	assert_eq!(err.position(ColUnit::Utf16), Position {offset: 6, line: 2, col: 3});
+	assert!(ErrorRenderer::new().render_indexed(&err, &index).ends_with("2 | \u{1F600} x\n  |  ^\n"));
+
+	let (_, span) = seq2_ret1("a".s0(), "\u{1F600}".lit().spanned()).parse_indexed("test", &index).unwrap();
+	assert_eq!(index.span(span.start.offset..span.end.offset, ColUnit::Utf16).unwrap().end.col, 3);
+
+	let result = p.parse_recover_indexed("test", &index);
+	assert_eq!(result.errors, vec![err]);
+}
@@ -0,0 +1,85 @@
+use crate::number_parsers::*;
+use crate::parsers::*;
+
+#[test]
+fn test_widths()
+{
+	let p = integer::<i8>(NumberFormat::new(10).with_sign());
+	assert_eq!(p.parse("test", "127"), Ok(127));
+	assert_eq!(p.parse("test", "-128"), Ok(-128));
+	assert_eq!(p.parse("test", "+5"), Ok(5));
+
+	let err = p.parse("test", "128").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: '128' is out of range for i8");
+	let err = p.parse("test", "-1290").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:4: '-1290' is out of range for i8");
+
+	let p = integer::<u8>(NumberFormat::new(10).with_sign());
+	assert_eq!(p.parse("test", "255"), Ok(255));
+	assert_eq!(p.parse("test", "-0"), Ok(0));
+	let err = p.parse("test", "-1").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:2: '-1' is out of range for u8");
+
+	let p = integer::<i128>(NumberFormat::new(10).with_sign());
+	assert_eq!(p.parse("test", "-170141183460469231731687303715884105728"), Ok(i128::MIN));
+
+	let p = integer::<u128>(NumberFormat::new(16));
+	assert_eq!(p.parse("test", "ffffffffffffffffffffffffffffffff"), Ok(u128::MAX));
+	let err = p.parse("test", "1ffffffffffffffffffffffffffffffff").unwrap_err();
+	assert_eq!(err.col, 33);
+}
+
+#[test]
+fn test_bases()
+{
+	assert_eq!(integer::<u32>(NumberFormat::new(2)).parse("test", "1012"), Ok(5));
+	assert_eq!(integer::<u32>(NumberFormat::new(8)).parse("test", "129"), Ok(10));
+	assert_eq!(integer::<u32>(NumberFormat::new(16)).parse("test", "fF"), Ok(255));
+	assert_eq!(integer::<u64>(NumberFormat::new(36)).parse("test", "Zz"), Ok(36 * 35 + 35));
+
+	let err = integer::<u32>(NumberFormat::new(2)).parse("test", "2").unwrap_err();
+	assert_eq!((err.col, err.mesg.as_str()), (1, ""));
+	let err = integer::<i32>(NumberFormat::new(10).with_sign()).parse("test", "-x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:2: expected digit, found 'x'");
+}
+
+#[test]
+fn test_separators()
+{
+	let p = integer::<u32>(NumberFormat::new(10).with_separators());
+	assert_eq!(p.parse("test", "1_000_000"), Ok(1_000_000));
+	assert_eq!(p.parse("test", "_1").unwrap_err().col, 1);
+
+	let err = p.parse("test", "1__0").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:2: '_' must be between digits");
+	let err = p.parse("test", "10_").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:3: '_' must be between digits");
+
+	// Separators have to be enabled.
+	let p = integer::<u32>(NumberFormat::new(10));
+	assert_eq!(p.parse("test", "1_0"), Ok(1));
+}
+
+#[test]
+fn test_partial()
+{
+	let p = integer::<u32>(NumberFormat::new(10).with_separators());
+	assert_eq!(p.parse_partial("test", "12"), PartialStatus::Incomplete);
+	assert_eq!(p.parse_partial("test", "12_"), PartialStatus::Incomplete);
+	assert_eq!(p.parse_partial("test", "12 "), PartialStatus::Complete(12, 2));
+}
+
+#[cfg(feature = "bigint")]
+#[test]
+fn test_bigint()
+{
+	use num_bigint::{BigInt, BigUint};
+
+	let p = integer::<BigInt>(NumberFormat::new(10).with_sign());
+	let text = "-123456789012345678901234567890123456789012345678901234567890";
+	assert_eq!(p.parse("test", text), Ok(text.parse::<BigInt>().unwrap()));
+
+	let p = integer::<BigUint>(NumberFormat::new(10).with_sign());
+	assert_eq!(p.parse("test", "-0"), Ok(BigUint::from(0u32)));
+	assert!(p.parse("test", "-1").is_err());
+}
@@ -0,0 +1,163 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::c99_parsers::*;
+use crate::misc::*;
+use crate::operator_parsers::*;
+use crate::parsers::*;
+use crate::tests::test_helpers::*;
+use crate::types::*;
+
+// Returns the expression as a fully parenthesized string so that we can check
+// precedence and associativity.
+fn expr_parser<'a>() -> Parser<'a, String>
+{
+	let expr_ptr = Rc::new(RefCell::new(ret(String::new())));
+	let expr_ref = forward_ref(expr_ptr.clone());
+
+	// primary := identifier | '(' expr ')'
+	let name = identifier().s0().thene(|s| ret(s.to_string()));
+	let primary = name.or(seq3_ret1("(".s0(), expr_ref, ")".s0())).err("primary");
+
+	let expr = OperatorTable::new()
+		.ternary("?".s0(), ":".s0(), 1, |c, a, b| format!("({} ? {} : {})", c, a, b))
+		.infix("==".s0(), 2, Assoc::None, |a, b| format!("({} == {})", a, b))
+		.infix("+".s0(), 3, Assoc::Left, |a, b| format!("({} + {})", a, b))
+		.infix("-".s0(), 3, Assoc::Left, |a, b| format!("({} - {})", a, b))
+		.infix("*".s0(), 4, Assoc::Left, |a, b| format!("({} * {})", a, b))
+		.infix("^".s0(), 6, Assoc::Right, |a, b| format!("({} ^ {})", a, b))
+		.prefix("-".s0(), 5, |a| format!("(-{})", a))
+		.prefix("!".s0(), 5, |a| format!("(!{})", a))
+		.postfix("++".s0(), 7, |a| format!("({}++)", a))
+		.call("(".s0(), ",".s0(), ")".s0(), 7, |f, args| format!("{}({})", f, args.join(", ")))
+		.index("[".s0(), "]".s0(), 7, |a, i| format!("{}[{}]", a, i))
+		.build(primary);
+	*expr_ptr.borrow_mut() = expr.clone();
+
+	expr.everything(ret(String::new()).s0())
+}
+
+#[test]
+fn test_infix()
+{
+	let p = expr_parser();
+
+	assert!(check_str_ok("a", &p, "a"));
+	assert!(check_str_ok("a + b", &p, "(a + b)"));
+	assert!(check_str_ok("a + b * c", &p, "(a + (b * c))"));
+	assert!(check_str_ok("a * b + c", &p, "((a * b) + c)"));
+	assert!(check_str_ok("a - b - c", &p, "((a - b) - c)"));
+	assert!(check_str_ok("a ^ b ^ c", &p, "(a ^ (b ^ c))"));
+	assert!(check_str_ok("(a + b) * c", &p, "((a + b) * c)"));
+	assert!(check_str_ok("a == b + c", &p, "(a == (b + c))"));
+	assert!(check_str_failed("a == b == c", &p, "non-associative operator", 1));
+	assert!(check_str_failed("a +", &p, "primary", 1));
+	assert!(check_str_failed("", &p, "primary", 1));
+}
+
+#[test]
+fn test_prefix_postfix()
+{
+	let p = expr_parser();
+
+	assert!(check_str_ok("-a", &p, "(-a)"));
+	assert!(check_str_ok("--a", &p, "(-(-a))"));
+	assert!(check_str_ok("-a * b", &p, "((-a) * b)"));
+	assert!(check_str_ok("-a ^ b", &p, "(-(a ^ b))"));
+	assert!(check_str_ok("!a++", &p, "(!(a++))"));
+	assert!(check_str_ok("a++ + b", &p, "((a++) + b)"));
+	assert!(check_str_ok("a - -b", &p, "(a - (-b))"));
+	assert!(check_str_failed("-", &p, "primary", 1));
+}
+
+#[test]
+fn test_call_index()
+{
+	let p = expr_parser();
+
+	assert!(check_str_ok("f()", &p, "f()"));
+	assert!(check_str_ok("f(a)", &p, "f(a)"));
+	assert!(check_str_ok("f(a, b + c)", &p, "f(a, (b + c))"));
+	assert!(check_str_ok("f(a)(b)", &p, "f(a)(b)"));
+	assert!(check_str_ok("a[i + j]", &p, "a[(i + j)]"));
+	assert!(check_str_ok("-a[i]", &p, "(-a[i])"));
+	assert!(check_str_ok("f(x)[y]++", &p, "(f(x)[y]++)"));
+	assert!(check_str_failed("f(a b)", &p, "',' or ')'", 1));
+	assert!(check_str_failed("f(a,", &p, "primary", 1));
+}
+
+#[test]
+fn test_ternary()
+{
+	let p = expr_parser();
+
+	assert!(check_str_ok("a ? b : c", &p, "(a ? b : c)"));
+	assert!(check_str_ok("a ? b : c ? d : e", &p, "(a ? b : (c ? d : e))"));
+	assert!(check_str_ok("a ? b ? c : d : e", &p, "(a ? (b ? c : d) : e)"));
+	assert!(check_str_ok("a == b ? c + d : e", &p, "((a == b) ? (c + d) : e)"));
+	assert!(check_str_failed("a ? b", &p, "':'", 1));
+}
+
+#[test]
+fn test_operand_errors()
+{
+	let p = expr_parser();
+
+	// The bad operand's error is reported, not the operator's.
+	let failure = p(unit_state("a + )")).unwrap_err();
+	assert_eq!((failure.mesg.as_str(), failure.err_state.index), ("primary", 4));
+
+	let failure = p(unit_state("a + (b * )")).unwrap_err();
+	assert_eq!((failure.mesg.as_str(), failure.err_state.index), ("primary", 9));
+
+	let failure = p(unit_state("f(a, ]")).unwrap_err();
+	assert_eq!((failure.mesg.as_str(), failure.err_state.index), ("primary", 5));
+
+	// Other operators are still tried when an operand fails.
+	assert!(check_str_ok("a++", &p, "(a++)"));
+}
+
+#[test]
+fn test_partial_operators()
+{
+	let p = OperatorTable::new()
+		.infix("+".lit(), 1, Assoc::Left, |a: i64, b| a + b)
+		.build(decimal_number());
+
+	assert!(check_incomplete(&p(partial_state("1+2"))));
+	assert!(check_incomplete(&p(partial_state("1+"))));
+	assert!(check_ok(&p(partial_state("1+2;")), &3));
+
+	let p = OperatorTable::new()
+		.infix("+".lit(), 1, Assoc::Left, |a: i64, b| a + b)
+		.build(match1(is_digit).thene(|s| ret(s.len() as i64)));
+	assert!(check_int_ok("11+222", &p, 5));
+}
+
+#[test]
+fn test_arithmetic()
+{
+	let p = OperatorTable::new()
+		.infix("+".s0(), 1, Assoc::Left, |lhs: i64, rhs| lhs + rhs)
+		.infix("-".s0(), 1, Assoc::Left, |lhs, rhs| lhs - rhs)
+		.infix("*".s0(), 2, Assoc::Left, |lhs, rhs| lhs*rhs)
+		.infix("/".s0(), 2, Assoc::Left, |lhs, rhs| lhs/rhs)
+		.build(decimal_number().s0())
+		.everything(ret(()).s0());
+
+	assert!(check_int_ok("2*3 + 4", &p, 10));
+	assert!(check_int_ok("2 + 3*4", &p, 14));
+	assert!(check_int_ok("100/10/5", &p, 2));
+	assert!(check_int_ok("10 - 4 - 3", &p, 3));
+}
+
+#[test]
+fn test_max_precedence()
+{
+	// Left associative operators parse their right operand one level higher.
+	let p = OperatorTable::new()
+		.infix("-".lit(), u32::MAX, Assoc::Left, |lhs: i64, rhs| lhs - rhs)
+		.build(decimal_number());
+
+	assert!(check_int_ok("10-4-3", &p, 3));
+}
@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+
+use crate::c99_preprocessor::*;
+use crate::parsers::*;
+
+fn texts<'a>(tokens: &[PpToken<'a>]) -> Vec<&'a str>
+{
+	tokens.iter().map(|t| t.text).collect()
+}
+
+fn names<'a>(names: &'a Option<Vec<Cow<str>>>) -> Option<Vec<&'a str>>
+{
+	names.as_ref().map(|names| names.iter().map(|name| name.as_ref()).collect())
+}
+
+fn directive<'a>(text: &'a str) -> Directive<'a>
+{
+	match pp_file().parse("test", text)
+	{
+		Ok(mut parts) if parts.len() == 1 => match parts.remove(0)
+		{
+			GroupPart::Directive(directive, _) => directive,
+			part => panic!("expected a directive but found {:?}", part),
+		},
+		result => panic!("expected one directive but found {:?}", result),
+	}
+}
+
+#[test]
+fn test_tokens()
+{
+	let p = pp_token().r1();
+	let tokens = p.parse("test", "x+=1.2.3e+4'a'L\"s\"@").unwrap();
+	let kinds: Vec<(PpTokenKind, &str)> = tokens.iter().map(|t| (t.kind, t.text)).collect();
+	assert_eq!(kinds, vec![
+		(PpTokenKind::Identifier, "x"),
+		(PpTokenKind::Punctuator, "+="),
+		(PpTokenKind::Number, "1.2.3e+4"),
+		(PpTokenKind::CharConstant, "'a'"),
+		(PpTokenKind::StringLiteral, "L\"s\""),
+		(PpTokenKind::Other, "@"),
+	]);
+}
+
+#[test]
+fn test_text_lines()
+{
+	let text = "int x; /* a\ncomment */ int y;\n\n  // blank\nchar *s = \"a\" \\\n  \"b\";";
+	let parts = pp_file().parse("test", text).unwrap();
+	assert_eq!(parts.len(), 2);
+
+	let GroupPart::Text(ref tokens) = parts[0] else {panic!()};
+	assert_eq!(texts(tokens), vec!["int", "x", ";", "int", "y", ";"]);
+	assert_eq!(tokens[3].span.start.line, 2);
+
+	let GroupPart::Text(ref tokens) = parts[1] else {panic!()};
+	assert_eq!(texts(tokens), vec!["char", "*", "s", "=", "\"a\"", "\"b\"", ";"]);
+}
+
+#[test]
+fn test_define()
+{
+	let Directive::Define {name, params, variadic, body} = directive("#define PI 3.14") else {panic!()};
+	assert_eq!((name.as_ref(), params, variadic, texts(&body)), ("PI", None, false, vec!["3.14"]));
+
+	// Object-like because of the space before the '('.
+	let Directive::Define {params, body, ..} = directive("#define X (1)\n") else {panic!()};
+	assert_eq!((params, texts(&body)), (None, vec!["(", "1", ")"]));
+
+	let Directive::Define {params, variadic, body, ..} = directive("  #  define F( a , b ) a ## b \\\n  + #a\n") else {panic!()};
+	assert_eq!((names(&params), variadic, texts(&body)), (Some(vec!["a", "b"]), false, vec!["a", "##", "b", "+", "#", "a"]));
+
+	let Directive::Define {params, variadic, ..} = directive("%:define LOG(fmt, ...) printf(fmt, __VA_ARGS__)") else {panic!()};
+	assert_eq!((names(&params), variadic), (Some(vec!["fmt"]), true));
+
+	let Directive::Define {params, variadic, ..} = directive("#define G(...) 0") else {panic!()};
+	assert_eq!((params, variadic), (Some(vec![]), true));
+
+	let Directive::Define {params, variadic, ..} = directive("#define H() 0") else {panic!()};
+	assert_eq!((params, variadic), (Some(vec![]), false));
+
+	let err = pp_file().parse("test", "#define 3").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:9: expected macro name, found '3'");
+
+	let err = pp_file().parse("test", "#define F(a,) a").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:13: expected parameter name, found ')'");
+}
+
+#[test]
+fn test_directives()
+{
+	assert_eq!(directive("#include <stdio.h>"), Directive::Include {path: "stdio.h", system: true});
+	assert_eq!(directive("#include \"foo/bar.h\" // local\n"), Directive::Include {path: "foo/bar.h", system: false});
+	let Directive::IncludeMacro(tokens) = directive("#include HEADER") else {panic!()};
+	assert_eq!(texts(&tokens), vec!["HEADER"]);
+
+	assert_eq!(directive("#undef X"), Directive::Undef("X".into()));
+	assert_eq!(directive("#line 10"), Directive::Line {line: 10, file: None});
+	assert_eq!(directive("#line 10 \"foo.c\""), Directive::Line {line: 10, file: Some("foo.c".to_string())});
+	assert_eq!(directive("#"), Directive::Null);
+
+	let Directive::Error(tokens) = directive("#error don't do that") else {panic!()};
+	assert_eq!(texts(&tokens), vec!["don", "'", "t", "do", "that"]);
+
+	let Directive::Pragma(tokens) = directive("#pragma once") else {panic!()};
+	assert_eq!(texts(&tokens), vec!["once"]);
+
+	let Directive::Unknown {name, tokens} = directive("#warning careful") else {panic!()};
+	assert_eq!((name.as_ref(), texts(&tokens)), ("warning", vec!["careful"]));
+
+	let err = pp_file().parse("test", "#line x").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:7: expected line number, found 'x'");
+	let err = pp_file().parse("test", "#undef X Y").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:10: expected end of line, found 'Y'");
+}
+
+#[test]
+fn test_conditionals()
+{
+	let text = "\
+#ifdef A
+a
+#elif defined(B) && C > 1
+  #ifndef D
+  d
+  #endif
+#else
+e
+#endif /* A */
+f
+";
+	let parts = pp_file().parse("test", text).unwrap();
+	assert_eq!(parts.len(), 2);
+
+	let GroupPart::Conditional(ref conditional) = parts[0] else {panic!()};
+	assert_eq!(conditional.branches.len(), 2);
+	assert_eq!(conditional.branches[0].0, Condition::Ifdef("A".into()));
+
+	let (Condition::If(ref tokens), ref group) = conditional.branches[1] else {panic!()};
+	assert_eq!(texts(tokens), vec!["defined", "(", "B", ")", "&&", "C", ">", "1"]);
+	let GroupPart::Conditional(ref nested) = group[0] else {panic!()};
+	assert_eq!(nested.branches[0].0, Condition::Ifndef("D".into()));
+	assert_eq!(nested.else_group, None);
+
+	let else_group = conditional.else_group.as_ref().unwrap();
+	let GroupPart::Text(ref tokens) = else_group[0] else {panic!()};
+	assert_eq!(texts(tokens), vec!["e"]);
+
+	let err = pp_file().parse("test", "#if X\nx\n").unwrap_err();
+	assert_eq!(err.to_string(), "test:3:1: expected #endif, found end of input");
+	let err = pp_file().parse("test", "x\n#endif\n").unwrap_err();
+	assert_eq!(err.to_string(), "test:2:1: #endif without #if");
+	let err = pp_file().parse("test", "#if X\n#else\n#elif Y\n#endif\n").unwrap_err();
+	assert_eq!((err.line, err.col), (3, 1));
+}
+
+#[test]
+fn test_splices()
+{
+	// Within identifiers, numbers, strings, and punctuators.
+	let parts = pp_file().parse("test", "int a\\\nb = 1\\\r\n2 +\\\n= \"x\\\ny\";").unwrap();
+	let GroupPart::Text(ref tokens) = parts[0] else {panic!()};
+	assert_eq!(texts(tokens), vec!["int", "a\\\nb", "=", "1\\\r\n2", "+\\\n=", "\"x\\\ny\"", ";"]);
+	let spellings: Vec<_> = tokens.iter().map(|t| t.spelling()).collect();
+	assert_eq!(spellings, vec!["int", "ab", "=", "12", "+=", "\"xy\"", ";"]);
+	assert_eq!((tokens[1].span.start.line, tokens[1].span.end.line, tokens[1].span.end.col), (1, 2, 2));
+
+	// Within directive and macro names.
+	let Directive::Define {name, body, ..} = directive("#def\\\nine X 1\n") else {panic!()};
+	assert_eq!((name.as_ref(), texts(&body)), ("X", vec!["1"]));
+	let Directive::Define {name, params, ..} = directive("#define F\\\nOO(a) a") else {panic!()};
+	assert_eq!((name.as_ref(), names(&params)), ("FOO", Some(vec!["a"])));
+	assert_eq!(directive("#line 1\\\n0 \"a\\\n.c\""), Directive::Line {line: 10, file: Some("a.c".to_string())});
+
+	// Lines may also end with just \r.
+	let parts = pp_file().parse("test", "#define X 1\r#undef X\rx // y\\\rz\r").unwrap();
+	assert_eq!(parts.len(), 3);
+	let GroupPart::Directive(Directive::Undef(ref name), span) = parts[1] else {panic!()};
+	assert_eq!((name.as_ref(), span.start.line), ("X", 2));
+	let GroupPart::Text(ref tokens) = parts[2] else {panic!()};
+	assert_eq!(texts(tokens), vec!["x"]);	// the comment is spliced onto the next line
+}
+
+#[test]
+fn test_errors()
+{
+	let err = pp_file().parse("test", "int x; /* oops\n").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:8: unterminated comment");
+
+	let err = pp_file().parse("test", "#ifdef 3\n#endif\n").unwrap_err();
+	assert_eq!(err.to_string(), "test:1:8: expected macro name, found '3'");
+}
@@ -0,0 +1,150 @@
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::types::*;
+
+// config := statement*
+// statement := identifier '=' value ';'
+fn config<'a>() -> Parser<'a, Vec<(String, i64)>>
+{
+	let value = decimal_number().s0().err("value");
+	let setting = seq3(identifier().s0(), "=".s0(), value, |name, _, value| Ok((name.to_string(), value)));
+	let statement = seq2_ret0(setting.recover_until(";".lit(), ("?".to_string(), 0)), ";".s0().insert_missing(""));
+	statement.r0().everything(ret(Vec::<(String, i64)>::new()))
+}
+
+fn settings(values: &[(&str, i64)]) -> Vec<(String, i64)>
+{
+	values.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+}
+
+#[test]
+fn test_recover_until()
+{
+	let result = config().parse_recover("test", "a = 1; b = x y; c = 3;");
+	assert_eq!(result.value, Some(settings(&[("a", 1), ("?", 0), ("c", 3)])));
+	assert_eq!(result.errors.len(), 1);
+	assert_eq!(result.errors[0].col, 12);
+	assert_eq!(result.errors[0].mesg, "value");
+
+	// Skipping advances the line.
+	let result = config().parse_recover("test", "a = \n\n;\nc = 3;");
+	assert_eq!(result.value, Some(settings(&[("?", 0), ("c", 3)])));
+	assert_eq!(result.errors[0].line, 3);
+}
+
+#[test]
+fn test_insert_missing()
+{
+	let result = config().parse_recover("test", "a = 1 b = 2;\nc = ;");
+	assert_eq!(result.value, Some(settings(&[("a", 1), ("b", 2), ("?", 0)])));
+
+	let errors: Vec<String> = result.errors.iter().map(|err| err.to_string()).collect();
+	assert_eq!(errors, vec!["test:1:7: missing ';'".to_string(), "test:2:5: expected value, found ';'".to_string()]);
+}
+
+#[test]
+fn test_recover_with()
+{
+	let p = "a".lit().recover_with("?").then("b".lit());
+	let result = p.parse_recover("test", "b");
+	assert_eq!(result.value, Some("b"));
+	assert_eq!(result.errors.len(), 1);
+	assert_eq!(result.errors[0].mesg, "'a'");
+
+	// The value is discarded if a later error can't be recovered from.
+	let result = p.parse_recover("test", "x");
+	assert_eq!(result.value, None);
+	assert_eq!(result.errors.len(), 2);
+}
+
+#[test]
+fn test_parse_reports_first_error()
+{
+	let err = config().parse("test", "a = ; b = ;").unwrap_err();
+	assert_eq!(err.col, 5);
+	assert_eq!(config().parse("test", "a = 1; b = 2;"), Ok(settings(&[("a", 1), ("b", 2)])));
+}
+
+#[test]
+fn test_backtracking()
+{
+	// Errors recorded by alternatives which failed are dropped.
+	let p = "a".lit().recover_with("?").then("b".lit()).or("c".lit());
+	let result = p.parse_recover("test", "c");
+	assert_eq!(result.value, Some("c"));
+	assert!(result.errors.is_empty());
+
+	let p = "a".lit().recover_with("?").then("b".lit()).optional();
+	let result = p.parse_recover("test", "c");
+	assert_eq!(result.value, Some(None));
+	assert!(result.errors.is_empty());
+}
+
+#[test]
+fn test_backtracking_repeats()
+{
+	// Errors recorded by the last (failed) iteration of a repetition are dropped.
+	let item = "a".lit().then("b".lit().recover_with("?")).then(";".lit());
+	let rest = || "a".lit().then("x".lit());
+	let parsers = [
+		item.r0().then(rest()),
+		item.sep_by0(",".lit()).then(rest()),
+		item.sep_end_by0(",".lit()).then(rest()),
+		item.end_by0(",".lit()).then(rest()),
+	];
+	for p in parsers.iter()
+	{
+		let result = p.parse_recover("test", "ax");
+		assert_eq!(result.value, Some("x"));
+		assert!(result.errors.is_empty(), "{:?}", result.errors);
+	}
+
+	let parsers = [
+		item.r1().then(rest()),
+		item.sep_by1(",".lit()).then(",".lit()).then(rest()),
+		item.sep_end_by1(",".lit()).then(rest()),
+		item.end_by1(",".lit()).then(rest()),
+	];
+	for (p, text) in parsers.iter().zip(["ab;ax", "ab;,ax", "ab;,ax", "ab;,ax"])
+	{
+		let result = p.parse_recover("test", text);
+		assert_eq!(result.value, Some("x"), "{}", text);
+		assert!(result.errors.is_empty(), "{:?}", result.errors);
+	}
+
+	// Including the attempts to match end.
+	let p = "a".lit().many_till(item);
+	assert_eq!(p.parse("test", "aab;"), Ok(vec!["a"]));
+}
+
+#[test]
+fn test_recover_partial()
+{
+	// We can't skip past the end of partial input.
+	let p = "a".lit().recover_until(";".lit(), "?");
+	assert_eq!(p.parse_partial("test", "x y"), PartialStatus::Incomplete);
+
+	// Recovered errors are still errors.
+	match p.parse_partial("test", "x;")
+	{
+		PartialStatus::Failed(err) => assert_eq!(err.mesg, "'a'"),
+		status => panic!("{:?}", status),
+	}
+}
+
+#[test]
+fn test_recover_memo()
+{
+	// The errors recorded the first time inner runs are recorded again when memo
+	// returns its cached result.
+	for memo in [false, true]
+	{
+		let inner = "a".lit().then("b".lit().recover_with("?"));
+		let inner = if memo {inner.memo()} else {inner};
+		let p = seq2_ret1(inner.clone(), "c".lit()).or(seq2_ret1(inner, "d".lit()));
+		let result = p.parse_recover("test", "ad");
+		assert_eq!(result.value, Some("d"));
+		assert_eq!(result.errors.len(), 1, "memo: {}", memo);
+		assert_eq!(result.errors[0].to_string(), "test:1:2: expected 'b', found 'd'");
+	}
+}
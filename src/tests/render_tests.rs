@@ -0,0 +1,71 @@
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::render::*;
+use crate::types::*;
+
+// attrs := '(' identifier (',' | ')')
+fn attrs<'a>() -> Parser<'a, &'a str>
+{
+	"(".lit().s0().then(identifier().s0()).then(",".lit().or(")".lit())).err("attribute list")
+}
+
+#[test]
+fn test_render()
+{
+	let text = "(a x";
+	let err = attrs().parse("test", text).unwrap_err();
+	let expected = "\
+error: expected one of ',' or ')' while parsing attribute list, found 'x'
+ --> test:1:4
+  |
+1 | (a x
+  |    ^
+";
+	assert_eq!(ErrorRenderer::new().render(&err, text), expected);
+}
+
+#[test]
+fn test_render_context()
+{
+	let text = "one\r\ntwo\nthree\r(\n\ta\tx\nfour";
+	let err = attrs().parse("test", &text[15..]).unwrap_err();
+	assert_eq!(err.line, 2);
+
+	// Only as many lines as exist are shown. By default tabs are one column.
+	let expected = "\
+error: expected one of ',' or ')' while parsing attribute list, found 'x'
+ --> test:2:4
+  |
+1 | (
+2 |  a x
+  |    ^
+";
+	assert_eq!(ErrorRenderer::new().context(3).render(&err, &text[15..]), expected);
+
+	// Tabs are expanded the same way the col was computed.
+	let p = "one".s0().then("two".s0()).then("three".s0()).then(attrs());
+	let err = p.parse_from(State::new("test", text).with_tab_width(4)).unwrap_err();
+	assert_eq!((err.line, err.col), (5, 9));
+	let rendered = ErrorRenderer::new().context(2).render(&err, text);
+	assert!(rendered.contains(" --> test:5:9\n"), "{}", rendered);
+	assert!(rendered.contains("\n3 | three\n4 | (\n5 |     a   x\n  |         ^\n"), "{}", rendered);
+}
+
+#[test]
+fn test_render_end()
+{
+	let text = "(a";
+	let err = attrs().parse("test", text).unwrap_err();
+	let rendered = ErrorRenderer::new().render(&err, text);
+	assert!(rendered.ends_with("1 | (a\n  |   ^\n"), "{}", rendered);
+}
+
+#[test]
+fn test_render_color()
+{
+	let text = "(a x";
+	let err = attrs().parse("test", text).unwrap_err();
+	let rendered = ErrorRenderer::new().color(true).render(&err, text);
+	assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m: "), "{:?}", rendered);
+	assert!(rendered.contains("\x1b[1;31m^\x1b[0m"), "{:?}", rendered);
+}
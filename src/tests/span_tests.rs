@@ -0,0 +1,89 @@
+use crate::byte_parsers::*;
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::types::*;
+
+#[test]
+fn test_spanned()
+{
+	let p = identifier().spanned().list(",".s0());
+	let values = p.parse("test", "ab,\n  cd").unwrap();
+
+	let (name, span) = values[0];
+	assert_eq!(name, "ab");
+	assert_eq!(span.start, Position {offset: 0, line: 1, col: 1});
+	assert_eq!(span.end, Position {offset: 2, line: 1, col: 3});
+
+	let (name, span) = values[1];
+	assert_eq!(name, "cd");
+	assert_eq!(span.start, Position {offset: 6, line: 2, col: 3});
+	assert_eq!(span.range(), 6..8);
+}
+
+#[test]
+fn test_spanned_lines()
+{
+	// Spans can cover more than one line.
+	let p = "a".s0().then("b".lit()).spanned();
+	let (_, span) = p.parse("test", "a\r\n\tb").unwrap();
+	assert_eq!((span.start.line, span.start.col), (1, 1));
+	assert_eq!((span.end.line, span.end.col), (2, 3));
+}
+
+#[test]
+fn test_with_pos()
+{
+	let p = "x".s0().then(identifier().with_pos());
+	assert_eq!(p.parse("test", "x\n ab"), Ok(("ab", Position {offset: 3, line: 2, col: 2})));
+
+	let p = "x".s0().then(position());
+	assert_eq!(p.parse("test", "x  "), Ok(Position {offset: 3, line: 1, col: 4}));
+}
+
+#[test]
+fn test_binary_positions()
+{
+	let p = u8().then(be_u16().spanned());
+	let (value, span) = p.parse_bytes("test", b"\x01\x02\x03").unwrap();
+	assert_eq!(value, 0x0203);
+	assert_eq!(span.start, Position {offset: 1, line: 1, col: 2});
+	assert_eq!(span.end, Position {offset: 3, line: 1, col: 4});
+}
+
+#[test]
+fn test_columns()
+{
+	let p = match0(|ch| ch != 'x').then(position());
+	assert_eq!(p.parse("test", "ab\r\ncd\refx").map(|pos| (pos.line, pos.col)), Ok((3, 3)));
+	assert_eq!(p.parse("test", "\n\n\tx").map(|pos| (pos.line, pos.col)), Ok((3, 2)));
+
+	let input = State::new("test", "\tab\tx").with_tab_width(4);
+	assert_eq!(p.parse_from(input).map(|pos| pos.col), Ok(9));
+	let input = State::new("test", "a\tx").with_tab_width(8);
+	assert_eq!(p.parse_from(input).map(|pos| pos.col), Ok(9));
+}
+
+#[test]
+fn test_col_modes()
+{
+	let p = match0(|ch| ch != 'x').then(position());
+	let text = "e\u{301}\u{1F1FA}\u{1F1F8}x";
+	assert_eq!(p.parse("test", text).map(|pos| pos.col), Ok(5));
+
+	let input = State::new("test", text).with_col_mode(ColMode::Graphemes);
+	assert_eq!(p.parse_from(input).map(|pos| pos.col), Ok(3));
+}
+
+#[test]
+fn test_error_columns()
+{
+	// Errors within a literal are reported at the character which didn't match.
+	let p = "x".s0().then("\u{E9}t\u{E9}".lit());
+	let err = p.parse("test", "x \t\n  \u{E9}t\u{E0}").unwrap_err();
+	assert_eq!((err.line, err.col), (2, 5));
+
+	let p = "x".s1().then("y".lit());
+	let input = State::new("test", "x\t z").with_tab_width(4);
+	let err = p.parse_from(input).unwrap_err();
+	assert_eq!((err.line, err.col), (1, 6));
+}
@@ -0,0 +1,162 @@
+use crate::byte_parsers::*;
+use crate::c99_parsers::*;
+use crate::misc::*;
+use crate::parsers::*;
+use crate::tests::test_helpers::*;
+use crate::types::*;
+
+#[test]
+fn test_partial_lit()
+{
+	let p = "foo".lit();
+
+	assert!(check_ok_strs(&p(partial_state("foo")), "foo"));
+	assert!(check_ok_strs(&p(partial_state("foobar")), "foo"));
+	assert!(check_incomplete(&p(partial_state(""))));
+	assert!(check_incomplete(&p(partial_state("fo"))));
+	assert!(check_failed(&p(partial_state("fx")), "'foo'", 1));
+}
+
+#[test]
+fn test_partial_match()
+{
+	let p = match0(is_alpha);
+	assert!(check_ok_strs(&p(partial_state("abc1")), "abc"));
+	assert!(check_incomplete(&p(partial_state("abc"))));
+	assert!(check_incomplete(&p(partial_state(""))));
+
+	let p = match1(is_digit);
+	assert!(check_ok_strs(&p(partial_state("12 ")), "12"));
+	assert!(check_incomplete(&p(partial_state("12"))));
+	assert!(check_failed(&p(partial_state("x")), "", 1));
+
+	let p = decimal_number();
+	assert!(check_ok(&p(partial_state("123;")), &123));
+	assert!(check_incomplete(&p(partial_state("123"))));
+}
+
+#[test]
+fn test_partial_scan()
+{
+	fn to_quote(text: &str, index: usize) -> usize
+	{
+		match text[index..].find('"')
+		{
+			Some(count) => count,
+			None => text.len() - index,
+		}
+	}
+
+	let p = seq3_ret1("\"".lit(), scan(to_quote), "\"".lit());
+	assert!(check_ok_strs(&p(partial_state("\"hmm\"")), "hmm"));
+	assert!(check_incomplete(&p(partial_state("\"hm"))));
+	assert!(check_incomplete(&p(partial_state("\""))));
+}
+
+#[test]
+fn test_partial_repeat()
+{
+	let p = "ab".lit().r0();
+	assert!(check_ok(&p(partial_state("ababx")), &vec!["ab", "ab"]));
+	assert!(check_incomplete(&p(partial_state("abab"))));
+	assert!(check_incomplete(&p(partial_state("aba"))));
+
+	let p = decimal_number().list(",".lit());
+	assert!(check_ok(&p(partial_state("1,2,3;")), &vec![1, 2, 3]));
+	assert!(check_incomplete(&p(partial_state("1,2,"))));
+	assert!(check_incomplete(&p(partial_state("1,2"))));
+
+	let p = "a".lit().optional();
+	assert!(check_ok(&p(partial_state("b")), &None));
+	assert!(check_incomplete(&p(partial_state(""))));
+}
+
+#[test]
+fn test_partial_or()
+{
+	// Can't pick "f" until we know that the input isn't "foo".
+	let p = "foo".lit().or("f".lit());
+	assert!(check_ok_strs(&p(partial_state("fox")), "f"));
+	assert!(check_incomplete(&p(partial_state("fo"))));
+
+	let p = or_v(vec!["foo".lit(), "bar".lit(), "f".lit()]);
+	assert!(check_ok_strs(&p(partial_state("bar")), "bar"));
+	assert!(check_incomplete(&p(partial_state("fo"))));
+	assert!(check_failed(&p(partial_state("x")), "'foo' or 'bar' or 'f'", 1));
+}
+
+#[test]
+fn test_partial_space()
+{
+	let p = "x".s0();
+	assert!(check_ok_strs(&p(partial_state("x  y")), "x"));
+	assert!(check_incomplete(&p(partial_state("x  "))));
+
+	let p = "x".lit().everything(ret(""));
+	assert!(check_incomplete(&p(partial_state("x"))));
+	assert_eq!(p.parse("test", "x"), Ok("x"));
+}
+
+// statement := identifier ';'
+fn statement<'a>() -> Parser<'a, &'a str>
+{
+	seq2_ret0(identifier().s0(), ";".s0())
+}
+
+#[test]
+fn test_parse_partial()
+{
+	let chunks = ["fo", "o;  b", "ar", " ;", "baz"];
+	let mut buffer = String::new();
+	let mut names = Vec::new();
+	for chunk in chunks.iter()
+	{
+		buffer.push_str(chunk);
+		loop
+		{
+			// Parsers borrow the text they parse so a new one is needed for each call.
+			let result = statement().parse_partial("test", &buffer);
+			match result
+			{
+				PartialStatus::Complete(name, count) =>
+				{
+					names.push(name.to_string());
+					buffer = buffer[count..].to_string();
+				}
+				PartialStatus::Incomplete =>
+				{
+					break;
+				}
+				PartialStatus::Failed(err) =>
+				{
+					panic!("{:?}", err);
+				}
+			}
+		}
+	}
+
+	assert_eq!(names, vec!["foo".to_string(), "bar".to_string()]);
+	assert_eq!(buffer, "baz");
+	let p = statement();
+	assert_eq!(p.parse("test", &buffer).map_err(|err| (err.col, err.mesg)), Err((4, "';'".to_string())));
+	match p.parse_partial("test", "1")
+	{
+		PartialStatus::Failed(err) => assert_eq!((err.col, err.mesg.as_str()), (1, "")),
+		_ => panic!("expected a failure"),
+	}
+}
+
+#[test]
+fn test_parse_partial_bytes()
+{
+	let p = length_data(u8());
+	assert_eq!(p.parse_partial_bytes("test", b"\x03ab"), PartialStatus::Incomplete);
+	assert_eq!(p.parse_partial_bytes("test", b"\x03abcd"), PartialStatus::Complete(&b"abc"[..], 4));
+	assert_eq!(p.parse_partial_bytes("test", b""), PartialStatus::Incomplete);
+
+	let p = magic(b"GIF8").then(length_value(u8(), be_u16()));
+	assert_eq!(p.parse_partial_bytes("test", b"GI"), PartialStatus::Incomplete);
+	assert_eq!(p.parse_partial_bytes("test", b"GIF8\x03\x01\x02"), PartialStatus::Incomplete);
+	assert_eq!(p.parse_partial_bytes("test", b"GIF8\x03\x01\x02\x03"), PartialStatus::Complete(0x0102, 8));
+	assert!(matches!(p.parse_partial_bytes("test", b"GIFx"), PartialStatus::Failed(_)));
+}
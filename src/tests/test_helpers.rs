@@ -1,177 +1,196 @@
 // Functions used by unit tests.
-//use io;
-use io::WriterUtil;
-use misc::*;
-use types::*;
-use Result = result::Result;
+use std::fmt::Debug;
+use crate::types::*;
 
-fn check_char_ok(inText: &str, parser: Parser<char>, expected: char) -> bool
+pub fn unit_state(in_text: &str) -> State<'_>
+{
+	State::new("unit test", in_text)
+}
+
+pub fn bytes_state(in_bytes: &[u8]) -> State<'_>
+{
+	State::from_bytes("unit test", in_bytes)
+}
+
+pub fn partial_state(in_text: &str) -> State<'_>
+{
+	State {partial: true, ..State::new("unit test", in_text)}
+}
+
+pub fn check_char_ok<'a>(in_text: &'a str, parser: &Parser<'a, char>, expected: char) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1,});
+	let result = parser(unit_state(in_text));
 	return check_ok(&result, &expected);
 }
 
-fn check_char_failed(inText: &str, parser: Parser<char>, expected: &str, line: int) -> bool
+pub fn check_char_failed<'a>(in_text: &'a str, parser: &Parser<'a, char>, expected: &str, line: i32) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_failed(&result, expected, line);
 }
 
-fn check_int_ok(inText: &str, parser: Parser<int>, expected: int) -> bool
+pub fn check_int_ok<'a>(in_text: &'a str, parser: &Parser<'a, i64>, expected: i64) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_ok(&result, &expected);
 }
 
-fn check_int_failed(inText: &str, parser: Parser<int>, expected: &str, line: int) -> bool
+pub fn check_int_failed<'a>(in_text: &'a str, parser: &Parser<'a, i64>, expected: &str, line: i32) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_failed(&result, expected, line);
 }
 
-fn check_float_ok(inText: &str, parser: Parser<f64>, expected: f64) -> bool
+pub fn check_float_ok<'a>(in_text: &'a str, parser: &Parser<'a, f64>, expected: f64) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
-	match result		// need this because Eq is missing for f64
-	{
-		result::Ok(ref pass) => check_ok(&result::Ok(Succeeded {new_state: pass.new_state, value: pass.value as float}), &(expected as float)),
-		result::Err(ref failed) => check_ok(&result::Err(*failed), &(expected as float)),
-	}
+	let result = parser(unit_state(in_text));
+	return check_ok(&result, &expected);
 }
 
-fn check_float_failed(inText: &str, parser: Parser<f64>, expected: &str, line: int) -> bool
+pub fn check_float_failed<'a>(in_text: &'a str, parser: &Parser<'a, f64>, expected: &str, line: i32) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_failed(&result, expected, line);
 }
 
-fn check_str_ok(inText: &str, parser: Parser<@~str>, expected: &str) -> bool
+pub fn check_str_ok<'a, S: AsRef<str> + Debug>(in_text: &'a str, parser: &Parser<'a, S>, expected: &str) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1,});
+	let result = parser(unit_state(in_text));
 	return check_ok_strs(&result, expected);
 }
 
-fn check_str_failed(inText: &str, parser: Parser<@~str>, expected: &str, line: int) -> bool
+pub fn check_str_failed<'a, S: Debug>(in_text: &'a str, parser: &Parser<'a, S>, expected: &str, line: i32) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_failed(&result, expected, line);
 }
 
-fn check_str_array_ok(inText: &str, parser: Parser<@~[@~str]>, expected: @~[@~str]) -> bool
+pub fn check_str_array_ok<'a>(in_text: &'a str, parser: &Parser<'a, Vec<&'a str>>, expected: &[&str]) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1,});
+	let result = parser(unit_state(in_text));
 	return check_ok_str_arrays(&result, expected);
 }
 
-fn check_str_array_failed(inText: &str, parser: Parser<@~[@~str]>, expected: &str, line: int) -> bool
+pub fn check_str_array_failed<'a>(in_text: &'a str, parser: &Parser<'a, Vec<&'a str>>, expected: &str, line: i32) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_failed(&result, expected, line);
 }
 
 // ---- Private Functions -----------------------------------------------------
-fn check_ok<T: Copy Durable cmp::Eq>(result: &Status<T>, expected: &T) -> bool
+pub fn check_ok<T: PartialEq + Debug>(result: &Status<'_, T>, expected: &T) -> bool
 {
-	match *result
+	match result
 	{
-		result::Ok(ref pass) =>
+		Ok(pass) =>
 		{
 			if pass.value != *expected
 			{
-				io::stderr().write_line(fmt!("Expected %? but found %?", expected, pass.value));
+				eprintln!("Expected {:?} but found {:?}", expected, pass.value);
 				return false;
 			}
 			return true;
 		}
-		result::Err(ref failure) =>
+		Err(failure) =>
 		{
-			io::stderr().write_line(fmt!("Error: expected %? but found error %s", expected, *failure.mesg));
+			eprintln!("Error: expected {:?} but found error {}", expected, failure.mesg);
 			return false;
 		}
 	}
 }
 
-fn check_ok_strs(result: &Status<@~str>, expected: &str) -> bool
+pub fn check_ok_strs<S: AsRef<str> + Debug>(result: &Status<'_, S>, expected: &str) -> bool
 {
-	match *result
+	match result
 	{
-		result::Ok(ref pass) =>
+		Ok(pass) =>
 		{
-			if *pass.value != expected.to_owned()
+			if pass.value.as_ref() != expected
 			{
-				io::stderr().write_line(fmt!("Expected %? but found %?", expected, pass.value));
+				eprintln!("Expected {:?} but found {:?}", expected, pass.value);
 				return false;
 			}
 			return true;
 		}
-		result::Err(ref failure) =>
+		Err(failure) =>
 		{
-			io::stderr().write_line(fmt!("Error: expected %? but found error %s", expected, *failure.mesg));
+			eprintln!("Error: expected {:?} but found error {}", expected, failure.mesg);
 			return false;
 		}
 	}
 }
 
-fn check_ok_str_arrays(result: &Status<@~[@~str]>, expected: @~[@~str]) -> bool
+pub fn check_ok_str_arrays(result: &Status<'_, Vec<&str>>, expected: &[&str]) -> bool
 {
-	match *result
+	match result
 	{
-		result::Ok(ref pass) =>
+		Ok(pass) =>
 		{
 			if pass.value != expected
 			{
-				io::stderr().write_line(fmt!("Expected %? but found %?", expected, pass.value));
+				eprintln!("Expected {:?} but found {:?}", expected, pass.value);
 				return false;
 			}
 			return true;
 		}
-		result::Err(ref failure) =>
+		Err(failure) =>
+		{
+			eprintln!("Error: expected {:?} but found error {}", expected, failure.mesg);
+			return false;
+		}
+	}
+}
+
+pub fn check_incomplete<T: Debug>(result: &Status<'_, T>) -> bool
+{
+	match result
+	{
+		Ok(pass) =>
 		{
-			io::stderr().write_line(fmt!("Error: expected %? but found error %s", expected, *failure.mesg));
+			eprintln!("Expected incomplete but found {:?}", pass.value);
 			return false;
 		}
+		Err(failure) =>
+		{
+			if !failure.is_incomplete()
+			{
+				eprintln!("Expected incomplete but found error '{}'", failure.mesg);
+				return false;
+			}
+			return true;
+		}
 	}
 }
 
-fn check_failed<T: Copy Durable>(result: &Status<T>, expected: &str, line: int) -> bool
+pub fn check_failed<T: Debug>(result: &Status<'_, T>, expected: &str, line: i32) -> bool
 {
-	match *result
+	match result
 	{
-		result::Ok(ref pass) =>
+		Ok(pass) =>
 		{
-			io::stderr().write_line(fmt!("Expected error '%s' but found %?", expected.to_owned(), pass.value));
+			eprintln!("Expected error '{}' but found {:?}", expected, pass.value);
 			return false;
 		}
-		result::Err(ref failure) =>
+		Err(failure) =>
 		{
-			if !str::eq(failure.mesg, &expected.to_owned())
+			if failure.mesg != expected
 			{
-				io::stderr().write_line(fmt!("Expected error '%s' but found error '%s'", expected.to_owned(), *failure.mesg));
+				eprintln!("Expected error '{}' but found error '{}'", expected, failure.mesg);
 				return false;
 			}
 			if failure.err_state.line != line
 			{
-				io::stderr().write_line(fmt!("Expected error '%s' on line %d but line is %d", expected.to_owned(), line, failure.err_state.line));
+				eprintln!("Expected error '{}' on line {} but line is {}", expected, line, failure.err_state.line);
 				return false;
 			}
 			return true;
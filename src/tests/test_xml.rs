@@ -1,93 +1,97 @@
-// Test for a simple DOM style XML parser. Note that this is not intended to be 
+// Test for a simple DOM style XML parser. Note that this is not intended to be
 // standards compliant or even very useful. Instead it is designed to test a parser
 // that returns objects instead of evaluating in-place.
-//use to_str::to_str;
-use c99_parsers::*;
-use parsers::*;
-use result::*;
-use tests::test_helpers::*;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 
-struct Attribute {name: @~str, value: @~str}
+use crate::c99_parsers::*;
+use crate::parsers::*;
+use crate::tests::test_helpers::*;
+use crate::types::*;
 
-enum Xml
+#[derive(Clone, Debug)]
+struct Attribute<'a> {name: &'a str, value: &'a str}
+
+#[derive(Clone, Debug)]
+enum Xml<'a>
 {
 	// element name, attributes, children, content
-	XXml(@~str, @~[Attribute], @~[Xml], @~str)
+	XXml(&'a str, Vec<Attribute<'a>>, Vec<Xml<'a>>, &'a str)
 }
 
-impl  Xml : ToStr 
+impl fmt::Display for Xml<'_>
 {
-	pure fn to_str() -> ~str
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
 	{
 		match self
 		{
-			XXml(name, attributes, children, content) =>
+			Xml::XXml(name, attributes, children, content) =>
 			{
-				let attrs = vec::map(*attributes, |a| fmt!("%s=\"%s\"", *a.name, *a.value) );
-				let childs = vec::map(*children, |c| c.to_str() );
-				if vec::len(attrs) > 0u
+				let attrs: Vec<String> = attributes.iter().map(|a| format!("{}=\"{}\"", a.name, a.value)).collect();
+				let childs: Vec<String> = children.iter().map(|c| c.to_string()).collect();
+				if !attrs.is_empty()
 				{
-					return fmt!("<%s %s>%s%s</%s>", *name, str::connect(attrs, ~" "), str::connect(childs, ~""), *content, *name);
+					write!(f, "<{} {}>{}{}</{}>", name, attrs.join(" "), childs.concat(), content, name)
 				}
 				else
 				{
-					return fmt!("<%s>%s%s</%s>", *name, str::connect(childs, ~""), *content, *name);
+					write!(f, "<{}>{}{}</{}>", name, childs.concat(), content, name)
 				}
 			}
 		}
 	}
 }
 
-impl  Attribute : ToStr 
+impl fmt::Display for Attribute<'_>
 {
-	pure fn to_str() -> ~str
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
 	{
-		return fmt!("%s = \"%s\"", *self.name, *self.value);
+		write!(f, "{} = \"{}\"", self.name, self.value)
 	}
 }
 
-fn check_xml_ok(inText: &str, expected: &str, parser: Parser<Xml>) -> bool
+fn check_xml_ok<'a>(in_text: &'a str, expected: &str, parser: &Parser<'a, Xml<'a>>) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	match parser(State {file: @~"unit test", text: text, index: 0u, line: 1,})
+	match parser(unit_state(in_text))
 	{
-		result::Ok(ref pass) =>
+		Ok(pass) =>
 		{
-			check_ok(&result::Ok(Succeeded {new_state: pass.new_state, value: @pass.value.to_str()}), &@expected.to_owned())
+			check_ok(&Ok(Succeeded {new_state: pass.new_state, value: pass.value.to_string()}), &expected.to_string())
 		}
-		result::Err(ref failure) =>
+		Err(failure) =>
 		{
-			check_ok_strs(&result::Err(*failure), expected)
+			check_ok_strs::<&str>(&Err(failure), expected)
 		}
 	}
 }
 
-fn check_xml_failed(inText: &str, parser: Parser<Xml>, expected: &str, line: int) -> bool
+fn check_xml_failed<'a>(in_text: &'a str, parser: &Parser<'a, Xml<'a>>, expected: &str, line: i32) -> bool
 {
 	info!("----------------------------------------------------");
-	let text = chars_with_eot(inText);
-	let result = parser(State {file: @~"unit test", text: text, index: 0u, line: 1});
+	let result = parser(unit_state(in_text));
 	return check_failed(&result, expected, line);
 }
 
 // string_body := [^"]*
-fn string_body() -> Parser<@~str>
+fn string_body<'a>() -> Parser<'a, &'a str>
 {
-	fn body(chars: @[char], index: uint) -> uint
+	fn body(text: &str, index: usize) -> usize
 	{
+		let bytes = text.as_bytes();
 		let mut i = index;
 		loop
 		{
-			if chars[i] == EOT
+			if i >= bytes.len()
 			{
 				return 0;
 			}
-			else if chars[i] == '\\' && chars[i+1] == '"'
+			else if bytes[i] == b'\\' && bytes.get(i+1) == Some(&b'"')
 			{
 				i += 2;
 			}
-			else if chars[i] != '"'
+			else if bytes[i] != b'"'
 			{
 				i += 1;
 			}
@@ -97,23 +101,24 @@ fn string_body() -> Parser<@~str>
 			}
 		}
 	}
-	
+
 	scan(body)
 }
 
 // content := (anything but '</')*
-fn content() -> Parser<@~str>
+fn content<'a>() -> Parser<'a, &'a str>
 {
-	fn body(chars: @[char], index: uint) -> uint
+	fn body(text: &str, index: usize) -> usize
 	{
+		let bytes = text.as_bytes();
 		let mut i = index;
 		loop
 		{
-			if chars[i] == EOT
+			if i == bytes.len()
 			{
 				return 0;
 			}
-			else if chars[i] == '<' && chars[i+1u] == '/'
+			else if bytes[i] == b'<' && bytes.get(i+1) == Some(&b'/')
 			{
 				return i - index;
 			}
@@ -123,48 +128,44 @@ fn content() -> Parser<@~str>
 			}
 		}
 	}
-	
+
 	scan(body)
 }
 
-fn xml_parser() -> Parser<Xml>
+fn xml_parser<'a>() -> Parser<'a, Xml<'a>>
 {
 	let name = identifier().s0();
-	
-	let dummy = XXml(@~"dummy", @~[], @~[], @~"");
-	let element_ptr = @mut ret(dummy);
-	let element_ref = forward_ref(element_ptr); 
-	
+
+	let dummy = Xml::XXml("dummy", vec![], vec![], "");
+	let element_ptr = Rc::new(RefCell::new(ret(dummy.clone())));
+	let element_ref = forward_ref(element_ptr.clone());
+
 	// attribute := name '=' '"' string_body '"'
-	let attribute = do seq5(name, "=".s0(), "\"".s0(), string_body(), "\"".s0())
-	|name, _a2, _a3, body, _a5| {
-		result::Ok(Attribute {name: name, value: body})
-	};
-	
+	let attribute = seq5(name.clone(), "=".s0(), "\"".s0(), string_body(), "\"".s0(),
+		|name, _a2, _a3, body, _a5| Ok(Attribute {name, value: body}));
+
 	// empty_element := '<' name attribute* '/>'
-	let empty_element = do seq4("<".s0(), name, attribute.r0(), "/>".s0())
-	|_a1, name, attrs, _a4| {
-		result::Ok(XXml(name, attrs, @~[], @~""))
-	};
-	
+	let empty_element = seq4("<".s0(), name.clone(), attribute.r0(), "/>".s0(),
+		|_a1, name, attrs, _a4| Ok(Xml::XXml(name, attrs, vec![], "")));
+
 	// complex_element := '<' name attribute* '>' element* content '</' name '>'
-	let complex_element = do seq9("<".s0(), name, attribute.r0(), ">".s0(), element_ref.r0(), content(), "</".s0(), name, ">".s0())
-	|_a1, name1, attrs, _a4, children, chars, _a5, name2, _a7|
-	{
-		if name1 == name2
-		{
-			result::Ok(XXml(name1, attrs, children, chars))
-		}
-		else
+	let complex_element = seq9("<".s0(), name.clone(), attribute.r0(), ">".s0(), element_ref.r0(), content(), "</".s0(), name, ">".s0(),
+		|_a1, name1, attrs, _a4, children, chars, _a5, name2, _a7|
 		{
-			result::Err(@fmt!("end tag '%s' but found '%s'", *name1, *name2))
-		}
-	};
-	
+			if name1 == name2
+			{
+				Ok(Xml::XXml(name1, attrs, children, chars))
+			}
+			else
+			{
+				Err(format!("end tag '{}' but found '{}'", name1, name2))
+			}
+		});
+
 	// element := empty_element | complex_element
 	let element = empty_element.or(complex_element);
-	*element_ptr = element;
-	
+	*element_ptr.borrow_mut() = element.clone();
+
 	// start := s0 element EOT
 	let s = ret(dummy).s0();
 	element.everything(s)
@@ -174,41 +175,38 @@ fn xml_parser() -> Parser<Xml>
 fn test_simple_element()
 {
 	let p = xml_parser();
-	
-	assert check_xml_ok("<trivial/>", "<trivial></trivial>", p);
-	assert check_xml_ok("<trivial first=\"number one\"/>", "<trivial first=\"number one\"></trivial>", p);
-	assert check_xml_ok("<trivial first=\"number one\" second=\"number two\"/>", "<trivial first=\"number one\" second=\"number two\"></trivial>", p);
-	assert check_xml_ok("  <  trivial first \t =    \"number one\"  \t/>", "<trivial first=\"number one\"></trivial>", p);
-	assert check_xml_failed("<trivial", p, "'/>' or '>'", 1);
-	assert check_xml_failed("<trivial first=\"number one/>", p, "'/>' or '>'", 1);
+
+	assert!(check_xml_ok("<trivial/>", "<trivial></trivial>", &p));
+	assert!(check_xml_ok("<trivial first=\"number one\"/>", "<trivial first=\"number one\"></trivial>", &p));
+	assert!(check_xml_ok("<trivial first=\"number one\" second=\"number two\"/>", "<trivial first=\"number one\" second=\"number two\"></trivial>", &p));
+	assert!(check_xml_ok("  <  trivial first \t =    \"number one\"  \t/>", "<trivial first=\"number one\"></trivial>", &p));
+	assert!(check_xml_failed("<trivial", &p, "'/>' or '>'", 1));
+	assert!(check_xml_failed("<trivial first=\"number one/>", &p, "'/>' or '>'", 1));
 }
 
 #[test]
 fn test_element()
 {
 	let p = xml_parser();
-	
-	assert check_xml_ok("<simple>\n  \n</simple>", "<simple></simple>", p);
-	assert check_xml_failed("<simple></oops>", p, "end tag 'simple' but found 'oops'", 1);
-	assert check_xml_ok("<simple alpha = \"A\" beta=\"12\"></simple>", "<simple alpha=\"A\" beta=\"12\"></simple>", p);
-	
-	assert check_xml_ok("<parent><child></child></parent>", "<parent><child></child></parent>", p);
-	assert check_xml_ok("<parent><child/></parent>", "<parent><child></child></parent>", p);
-	assert check_xml_ok("<parent><child1/><child2/></parent>", "<parent><child1></child1><child2></child2></parent>", p);
-	assert check_xml_ok("<parent><child1><child2></child2></child1></parent>", "<parent><child1><child2></child2></child1></parent>", p);
-	
-	assert check_xml_ok("<parent>some text</parent>", "<parent>some text</parent>", p);
-	assert check_xml_ok("<parent><child/>blah blah</parent>", "<parent><child></child>blah blah</parent>", p);
-	assert check_xml_failed("<simple>\r  \n  \r\n</oops>", p, "end tag 'simple' but found 'oops'", 4);
+
+	assert!(check_xml_ok("<simple>\n  \n</simple>", "<simple></simple>", &p));
+	assert!(check_xml_failed("<simple></oops>", &p, "end tag 'simple' but found 'oops'", 1));
+	assert!(check_xml_ok("<simple alpha = \"A\" beta=\"12\"></simple>", "<simple alpha=\"A\" beta=\"12\"></simple>", &p));
+
+	assert!(check_xml_ok("<parent><child></child></parent>", "<parent><child></child></parent>", &p));
+	assert!(check_xml_ok("<parent><child/></parent>", "<parent><child></child></parent>", &p));
+	assert!(check_xml_ok("<parent><child1/><child2/></parent>", "<parent><child1></child1><child2></child2></parent>", &p));
+	assert!(check_xml_ok("<parent><child1><child2></child2></child1></parent>", "<parent><child1><child2></child2></child1></parent>", &p));
+
+	assert!(check_xml_ok("<parent>some text</parent>", "<parent>some text</parent>", &p));
+	assert!(check_xml_ok("<parent><child/>blah blah</parent>", "<parent><child></child>blah blah</parent>", &p));
+	assert!(check_xml_failed("<simple>\r  \n  \r\n</oops>", &p, "end tag 'simple' but found 'oops'", 4));
 }
 
-#[testXX]
+#[test]
+#[ignore]
 fn test_x()
 {
 	let p = xml_parser();
-	assert check_xml_ok("<trivial/>", "<trivial></trivial>", p);
+	assert!(check_xml_ok("<trivial/>", "<trivial></trivial>", &p));
 }
-
-
-
-
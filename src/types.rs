@@ -1,22 +1,398 @@
 //! Types used by all parse functions.
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-// TODO: should be able to get rid of all the Durable bounds once
-// https://github.com/mozilla/rust/issues/2992 is fixed
+use unicode_segmentation::GraphemeCursor;
 
 /// Type for parse functions.
-pub type Parser<T: Copy Durable> = fn@ (State) -> Status<T>;
+///
+/// Parsers are reference counted closures so they are cheap to clone and can be
+/// freely shared between the parsers they are composed into. The lifetime is that
+/// of the text being parsed which allows parsers to return slices of the input.
+pub type Parser<'a, T> = Rc<dyn Fn(State<'a>) -> Status<'a, T> + 'a>;
 
-/// Input argument for parse functions. File is not interpreted and need 
-/// not be a path. Text is assumed to end with EOT. Lines are 1-based.
-pub struct State {file: @~str, text: @[char], index: uint, line: int}
+/// How State counts columns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColMode
+{
+	/// Each Unicode scalar value is a column.
+	Chars,
+
+	/// Each extended grapheme cluster is a column, e.g. "e\u{301}" is one column.
+	Graphemes,
+}
+
+/// Input argument for parse functions. File is not interpreted and need
+/// not be a path. Index is a byte offset into bytes. Lines and cols are 1-based.
+///
+/// When parsing text bytes is text.as_bytes(). When parsing binary data binary
+/// is set and text is empty so text parsers will see no input (and lines will not
+/// advance). Parsers which return slices of text fail on binary input.
+/// Partial is set when more input may follow the end of bytes (see parse_partial).
+/// Parse_id is unique for each input and is used by memo to discard stale results.
+///
+/// Parsers should use advance to move through text so that line and col stay in
+/// sync with index. Tabs advance col to the next multiple of tab_width (plus one).
+/// Skipper is used by s0 and s1 to skip white space. Indent is the column of the
+/// innermost indented block (it's zero outside of blocks, see indent_parsers).
+/// States are cheap to clone (the skipper is reference counted).
+#[derive(Clone, Debug)]
+pub struct State<'a>
+{
+	pub file: &'a str,
+	pub text: &'a str,
+	pub bytes: &'a [u8],
+	pub binary: bool,
+	pub index: usize,
+	pub line: i32,
+	pub col: usize,
+	pub tab_width: usize,
+	pub col_mode: ColMode,
+	pub skipper: Skipper<'a>,
+	pub indent: usize,
+	pub partial: bool,
+	pub parse_id: usize,
+}
+
+/// Skips the white space (and possibly comments) after tokens parsed with s0 and s1.
+/// The function is called with the state after the token and returns the state after
+/// the white space. The default skips [ \t\r\n]*. Custom skippers can be created from
+/// functions, e.g. `Skipper(Rc::new(skip_dashes))`, or with Skipper::parser.
+#[derive(Clone)]
+pub struct Skipper<'a>(pub Rc<dyn Fn(State<'a>) -> State<'a> + 'a>);
+
+/// A location within the input. Offset is a byte offset. Line and col are 1-based
+/// (for binary input col is offset + 1).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {pub offset: usize, pub line: usize, pub col: usize}
+
+/// The input matched by a parser (see spanned). End is just past the matched input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {pub start: Position, pub end: Position}
+
+impl Span
+{
+	/// Returns the byte range of the matched input.
+	pub fn range(&self) -> Range<usize>
+	{
+		self.start.offset..self.end.offset
+	}
+}
 
 /// Return type of parse functions.
-pub type Status<T: Copy Durable> = Result<Succeeded<T>, Failed>;
+pub type Status<'a, T> = Result<Succeeded<'a, T>, Failed<'a>>;
 
-/// new_state will be like the input state except that index and line may 
+/// new_state will be like the input state except that index and line may
 /// advance. Value is an arbitrary value associated with the parse.
-pub struct Succeeded<T: Copy Durable> {new_state: State, value: T}
+#[derive(Clone, Debug)]
+pub struct Succeeded<'a, T> {pub new_state: State<'a>, pub value: T}
 
-/// old_state should be identical to the input state. err_state is where 
+/// old_state should be identical to the input state. err_state is where
 /// the error happened.
-pub struct Failed {old_state: State, err_state: State, mesg: @~str}
+///
+/// Expected is the set of tokens which would have allowed parsing to continue,
+/// e.g. "')'". Context is the labels of the err and note parsers which were in
+/// progress when the error happened (innermost first). Mesg is normally the
+/// expected tokens joined with " or ", but may be an arbitrary message (e.g. from fails).
+#[derive(Clone, Debug)]
+pub struct Failed<'a> {pub old_state: State<'a>, pub err_state: State<'a>, pub mesg: String, pub expected: Vec<String>, pub context: Vec<String>}
+
+impl<'a> Failed<'a>
+{
+	/// Returns a failure where expected was expected at err_state (expected may be empty).
+	pub fn new(old_state: State<'a>, err_state: State<'a>, expected: String) -> Failed<'a>
+	{
+		let tokens = if expected.is_empty() {Vec::new()} else {vec![expected.clone()]};
+		Failed {old_state, err_state, mesg: expected, expected: tokens, context: Vec::new()}
+	}
+
+	/// Returns a failure with an arbitrary message.
+	pub fn message(old_state: State<'a>, err_state: State<'a>, mesg: String) -> Failed<'a>
+	{
+		Failed {old_state, err_state, mesg, expected: Vec::new(), context: Vec::new()}
+	}
+
+	/// Returns the character (or, for binary input, the byte) at err_state or None
+	/// if err_state is at the end of the input.
+	pub fn found(&self) -> Option<String>
+	{
+		if self.err_state.is_binary()
+		{
+			self.err_state.rest_bytes().first().map(|b| format!("0x{:02X}", b))
+		}
+		else
+		{
+			self.err_state.peek().map(|ch| ch.to_string())
+		}
+	}
+
+	/// Returns the byte range of the unexpected input.
+	pub fn span(&self) -> Range<usize>
+	{
+		let len = if self.err_state.is_binary()
+		{
+			self.err_state.rest_bytes().len().min(1)
+		}
+		else
+		{
+			self.err_state.peek().map_or(0, |ch| ch.len_utf8())
+		};
+		self.err_state.index..self.err_state.index + len
+	}
+
+	/// Returns true if the parser ran out of partial input, i.e. it might
+	/// succeed if it was given more input.
+	pub fn is_incomplete(&self) -> bool
+	{
+		self.err_state.partial && self.err_state.at_end()
+	}
+}
+
+impl<'a> State<'a>
+{
+	/// Returns a state positioned at the start of text.
+	pub fn new(file: &'a str, text: &'a str) -> State<'a>
+	{
+		State {file, text, bytes: text.as_bytes(), binary: false, index: 0, line: 1, col: 1, tab_width: 1, col_mode: ColMode::Chars, skipper: Skipper::space(), indent: 0, partial: false, parse_id: next_parse_id()}
+	}
+
+	/// Returns a state positioned at the start of binary data.
+	pub fn from_bytes(file: &'a str, bytes: &'a [u8]) -> State<'a>
+	{
+		State {file, text: "", bytes, binary: true, index: 0, line: 1, col: 1, tab_width: 1, col_mode: ColMode::Chars, skipper: Skipper::space(), indent: 0, partial: false, parse_id: next_parse_id()}
+	}
+
+	/// Returns the state with a different tab width (the default is 1).
+	pub fn with_tab_width(self, tab_width: usize) -> State<'a>
+	{
+		assert!(tab_width > 0, "tab width must be positive");
+		State {tab_width, ..self}
+	}
+
+	/// Returns the state with a different column mode (the default is ColMode::Chars).
+	pub fn with_col_mode(self, col_mode: ColMode) -> State<'a>
+	{
+		State {col_mode, ..self}
+	}
+
+	/// Returns the state with a different skipper (the default is Skipper::space()).
+	pub fn with_skipper(self, skipper: Skipper<'a>) -> State<'a>
+	{
+		State {skipper, ..self}
+	}
+
+	/// Returns the state after the white space at index (as determined by skipper). Within
+	/// indented blocks white space isn't skipped if it would end on a later line at a column
+	/// that isn't past the block's column (so blocks and line folds end there).
+	pub fn skip_space(&self) -> State<'a>
+	{
+		let state = (self.skipper.0)(self.clone());
+		if self.indent > 0 && state.line > self.line && !state.at_end() && state.col <= self.indent
+		{
+			self.clone()
+		}
+		else
+		{
+			state
+		}
+	}
+
+	/// Returns the state after the next count bytes. For text "\r\n", "\r", and "\n"
+	/// each start a new line. Count must end on a character boundary.
+	pub fn advance(&self, count: usize) -> State<'a>
+	{
+		let end = self.index + count;
+		if self.is_binary()
+		{
+			return State {index: end, ..self.clone()};		// position computes binary columns
+		}
+
+		let bytes = self.text.as_bytes();
+		let mut line = self.line;
+		let mut col = self.col;
+		for (offset, ch) in self.text[self.index..end].char_indices()
+		{
+			let i = self.index + offset;
+			match ch
+			{
+				'\n' if i > 0 && bytes[i - 1] == b'\r' =>
+				{
+				}
+				'\r' | '\n' =>
+				{
+					line += 1;
+					col = 1;
+				}
+				'\t' =>
+				{
+					col = ((col - 1) / self.tab_width + 1) * self.tab_width + 1;
+				}
+				_ if self.col_mode == ColMode::Chars || is_grapheme_start(self.text, i) =>
+				{
+					col += 1;
+				}
+				_ =>
+				{
+				}
+			}
+		}
+		State {index: end, line, col, ..self.clone()}
+	}
+
+	/// Returns true if the input is binary data instead of text.
+	pub fn is_binary(&self) -> bool
+	{
+		self.binary
+	}
+
+	/// Returns the text which has not been parsed yet.
+	pub fn rest(&self) -> &'a str
+	{
+		self.text.get(self.index..).unwrap_or("")
+	}
+
+	/// Returns the bytes which have not been parsed yet.
+	pub fn rest_bytes(&self) -> &'a [u8]
+	{
+		&self.bytes[self.index..]
+	}
+
+	/// Returns the next character or None if all of the text has been parsed.
+	pub fn peek(&self) -> Option<char>
+	{
+		self.rest().chars().next()
+	}
+
+	/// Returns true if all of the input has been parsed.
+	pub fn at_end(&self) -> bool
+	{
+		self.index >= self.bytes.len()
+	}
+
+	/// Returns the location of index.
+	pub fn position(&self) -> Position
+	{
+		let col = if self.is_binary() {self.index + 1} else {self.col};
+		Position {offset: self.index, line: self.line as usize, col}
+	}
+}
+
+impl<'a> Skipper<'a>
+{
+	/// Skips [ \t\r\n]*.
+	pub fn space() -> Skipper<'a>
+	{
+		Skipper(Rc::new(|input| skip_while(input, |_| 0)))
+	}
+
+	/// Skips white space, /* */ comments, and // comments.
+	pub fn c_comments() -> Skipper<'a>
+	{
+		Skipper(Rc::new(|input| skip_while(input, |rest|
+		{
+			if let Some(body) = rest.strip_prefix("/*")
+			{
+				body.find("*/").map_or(rest.len(), |i| i + 4)	// unterminated comments run to the end
+			}
+			else if rest.starts_with("//")
+			{
+				rest.find(['\r', '\n']).unwrap_or(rest.len())
+			}
+			else
+			{
+				0
+			}
+		})))
+	}
+
+	/// Skips whatever parser matches.
+	///
+	/// ```
+	/// # use rparse::*;
+	/// let space = Skipper::parser(alt!(match1(is_whitespace), line_comment()).r0());
+	/// let input = State::new("test", "x  // comment\n y").with_skipper(space);
+	/// let p = seq!("x".s0(), "y".s0());
+	/// assert_eq!(p.parse_from(input), Ok(("x", "y")));
+	/// ```
+	pub fn parser<T: 'a>(parser: Parser<'a, T>) -> Skipper<'a>
+	{
+		// Parser is called with the default skipper so that it can use s0.
+		let space = Skipper::space();
+		Skipper(Rc::new(move |input: State<'a>|
+		{
+			match parser(State {skipper: space.clone(), ..input.clone()})
+			{
+				Ok(pass) => State {skipper: input.skipper, ..pass.new_state},
+				Err(_) => input,
+			}
+		}))
+	}
+
+	/// Skips white space and # comments.
+	pub fn hash_comments() -> Skipper<'a>
+	{
+		Skipper(Rc::new(|input| skip_while(input, |rest|
+		{
+			if rest.starts_with('#') {rest.find(['\r', '\n']).unwrap_or(rest.len())} else {0}
+		})))
+	}
+}
+
+// Skippers are equal if they use the same function.
+impl PartialEq for Skipper<'_>
+{
+	fn eq(&self, other: &Self) -> bool
+	{
+		Rc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+impl fmt::Debug for Skipper<'_>
+{
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+	{
+		write!(f, "Skipper")
+	}
+}
+
+// Skips white space and the text that comment says to skip (comment returns the number
+// of bytes at the start of its argument to skip).
+fn skip_while<'a>(input: State<'a>, comment: impl Fn(&str) -> usize) -> State<'a>
+{
+	let bytes = input.text.as_bytes();
+	let mut i = input.index;
+	while i < bytes.len()
+	{
+		match bytes[i]
+		{
+			b' ' | b'\t' | b'\r' | b'\n' =>
+			{
+				i += 1;
+			}
+			_ =>
+			{
+				let count = comment(&input.text[i..]);
+				if count == 0
+				{
+					break;
+				}
+				i += count;
+			}
+		}
+	}
+	input.advance(i - input.index)
+}
+
+fn is_grapheme_start(text: &str, index: usize) -> bool
+{
+	GraphemeCursor::new(index, text.len(), true).is_boundary(text, 0).unwrap_or(true)
+}
+
+/// Returns a new value for State.parse_id.
+pub fn next_parse_id() -> usize
+{
+	static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+	NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}